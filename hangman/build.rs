@@ -13,9 +13,19 @@
 // limitations under the License.
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
+use std::process::Command;
+
+/// Run git in the repository and return its trimmed output, if it succeeds
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
 
 fn main() {
     // Put the linker script somewhere the linker can find it
@@ -26,9 +36,63 @@ fn main() {
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
+    // Convert the recorded trace for the `sim` feature into little-endian f32s. Always create the
+    // file, even if empty, so that it can be unconditionally included.
+    let mut trace = Vec::new();
+    if let Some(path) = env::var_os("SIM_TRACE") {
+        let path = PathBuf::from(path);
+        println!("cargo:rerun-if-changed={}", path.display());
+        for line in fs::read_to_string(&path).unwrap().lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let column = line.rsplit(',').next().unwrap().trim();
+            let Ok(kg) = column.parse::<f32>() else {
+                panic!("Invalid weight in {}: {line}", path.display());
+            };
+            trace.extend_from_slice(&kg.to_le_bytes());
+        }
+    }
+    File::create(out.join("sim_trace.bin"))
+        .unwrap()
+        .write_all(&trace)
+        .unwrap();
+
+    // Record the exact build so that field units can be traced back to it. Builds outside of a git
+    // checkout, e.g. from a source archive, are marked as unknown.
+    let commit = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+        .is_some_and(|status| !status.is_empty());
+    println!("cargo:rustc-env=GIT_COMMIT={commit}");
+    println!(
+        "cargo:rustc-env=GIT_DIRTY={}",
+        if dirty { "-dirty" } else { "" }
+    );
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap()
+    );
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        let git_dir = PathBuf::from(git_dir);
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("index").display());
+        if let Some(head) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={}", git_dir.join(head).display());
+        }
+    }
+    // Editing sources changes the dirty flag
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=../hangman_utils/src");
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=memory.x");
     println!("cargo:rerun-if-env-changed=ADVERTISED_NAME");
     println!("cargo:rerun-if-env-changed=DEVICE_ID");
     println!("cargo:rerun-if-env-changed=DEVICE_VERSION_NUMBER");
+    println!("cargo:rerun-if-env-changed=SIM_PROFILE");
+    println!("cargo:rerun-if-env-changed=SIM_TRACE");
+    println!("cargo:rerun-if-env-changed=SIM_TRACE_RATE_HZ");
+    println!("cargo:rerun-if-env-changed=SIM_NOISE_KG");
+    println!("cargo:rerun-if-env-changed=SIM_DRIFT_KG_PER_MIN");
 }
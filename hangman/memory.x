@@ -12,9 +12,13 @@ MEMORY
   /* Reserve one 4kB page of flash for constants that should not be overwritten during normal programming */
   USER_CONSTANTS (r) : ORIGIN = 0x3F000, LENGTH = 0x1000
   /* MBR + SoftDevice require some amount of RAM. It'll tell us at boot (via logs) what the right
-  value is */
+  value is ("softdevice RAM: N bytes"), and panics with the required start address if it's too
+  little. It grows with the number of connections, the ATT MTU and the attribute table, see
+  MAX_CONNECTIONS, ATT_MTU and ATTR_TAB_SIZE in src/ble/mod.rs. Take it from a build with all
+  optional services enabled (--features weight-scale,ble-console), as smaller builds need less. */
+  /* 11264 bytes with the default 1408 byte attribute table, plus 640 bytes for ATTR_TAB_SIZE */
   /* Artificially constraining RAM to that of the smallest nRF52 chip */
-  RAM : ORIGIN = 0x20000000 + 7952, LENGTH = 24K - 7952
+  RAM : ORIGIN = 0x20000000 + 11904, LENGTH = 24K - 11904
 }
 
 /* This is where the call stack will be allocated. */
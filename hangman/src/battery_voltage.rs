@@ -38,6 +38,19 @@ const LOW_BATTERY_THRESHOLD_MV: u32 = 2750;
 // ADS1230 minimum supply voltage is 2.7V
 const CRITICAL_BATTERY_THRESHOLD_MV: u32 = 2700;
 
+/// Battery level at each voltage, from the shutdown threshold up
+///
+/// Rough discharge curve of a pair of alkaline cells under load, rescaled so that 0% is where we
+/// shut down rather than where the cells are fully depleted.
+const DISCHARGE_CURVE: &[(u32, u8)] = &[
+    (CRITICAL_BATTERY_THRESHOLD_MV, 0),
+    (LOW_BATTERY_THRESHOLD_MV, 5),
+    (2850, 20),
+    (2950, 45),
+    (3050, 75),
+    (3150, 100),
+];
+
 /// Samples battery voltage
 ///
 /// Should only be called once
@@ -83,3 +96,9 @@ pub fn is_low() -> bool {
 pub fn is_critically_low() -> bool {
     get_startup_reading().expect("Battery to be sampled") <= CRITICAL_BATTERY_THRESHOLD_MV
 }
+
+/// Battery level in percent, estimated from the startup reading
+pub fn level_pct() -> u8 {
+    let millivolts = get_startup_reading().expect("Battery to be sampled");
+    hangman_utils::battery::level_pct(millivolts, DISCHARGE_CURVE)
+}
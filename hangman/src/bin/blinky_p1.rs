@@ -82,7 +82,10 @@ async fn main(spawner: Spawner) -> ! {
 
     let p = embassy_nrf::init(config());
 
-    let sd = ble::init_softdevice();
+    let sd = ble::init_softdevice(ble::DeviceInfo {
+        model: core::env!("CARGO_BIN_NAME"),
+        hardware_revision: "P1.0",
+    });
     spawner.must_spawn(softdevice_task(sd));
 
     // It's recommended to start the SoftDevice before doing anything else
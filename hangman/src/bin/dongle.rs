@@ -38,6 +38,8 @@ use hangman::console;
 use hangman::{
     battery_voltage, ble, blocking_hal,
     button::{self, Button},
+    calibration_mode,
+    led::Led,
     make_static, pac, util,
     weight::{self, Hx711},
     MeasureCommandChannel, SharedDelay,
@@ -100,6 +102,7 @@ async fn main(spawner: Spawner) -> ! {
     }
     weight::init(weight::Config {
         sampling_interval_hz: 10,
+        oversampling_ratio: 1,
     });
 
     // This will reset the GPIO latch signal
@@ -108,12 +111,7 @@ async fn main(spawner: Spawner) -> ! {
     let delay: &'static SharedDelay =
         make_static!(SharedDelay, Mutex::new(SysTickDelay::new(syst)));
 
-    // For debugging
-    let _green_led = gpio::Output::new(
-        p.P0_06.degrade(),
-        gpio::Level::High,
-        gpio::OutputDrive::Standard,
-    );
+    let mut green_led = Led::new(p.P0_06.degrade(), button::Polarity::ActiveLow);
 
     // orange DATA 0.17
     let hx711_data = gpio::Input::new(p.P0_17.degrade(), gpio::Pull::None);
@@ -126,13 +124,33 @@ async fn main(spawner: Spawner) -> ! {
     );
     let hx711 = Hx711::new(hx711_data, hx711_clock, delay);
 
+    // Second HX711 for a scale built from two load cells
+    // DATA 0.22
+    // CLK 0.24
+    #[cfg(feature = "second-cell")]
+    let second_hx711 = Some(Hx711::new(
+        gpio::Input::new(p.P0_22.degrade(), gpio::Pull::None),
+        gpio::Output::new(
+            p.P0_24.degrade(),
+            // Set high initially to power down chip
+            gpio::Level::High,
+            gpio::OutputDrive::Standard,
+        ),
+        delay,
+    ));
+    #[cfg(not(feature = "second-cell"))]
+    let second_hx711 = None;
+
     // USB setup
     // Hack: pretend USB is already connected. not a bad assumption since this is a dongle
     // There might be a race condition at startup between USB init and SD init.
     let usb_detect_ref: &SoftwareVbusDetect =
         make_static!(SoftwareVbusDetect, SoftwareVbusDetect::new(true, true));
 
-    let sd = ble::init_softdevice();
+    let sd = ble::init_softdevice(ble::DeviceInfo {
+        model: core::env!("CARGO_BIN_NAME"),
+        hardware_revision: "nRF52840 dongle",
+    });
     spawner.must_spawn(softdevice_task(sd, usb_detect_ref));
 
     // It's recommended to start the SoftDevice before doing anything else
@@ -150,7 +168,12 @@ async fn main(spawner: Spawner) -> ! {
     let (usb, class) = console::board::setup_usb(p.USBD, Irqs, usb_detect_ref);
 
     let ch: &MeasureCommandChannel = make_static!(MeasureCommandChannel, Channel::new());
-    spawner.must_spawn(weight::task_function(ch.receiver(), hx711, sd));
+    spawner.must_spawn(weight::task_function(
+        ch.receiver(),
+        hx711,
+        second_hx711,
+        sd,
+    ));
     // Sample battery voltage while sampling to get a reading under load
     ch.sender()
         .send(weight::Command::StartSampling(weight::SampleType::Raw(
@@ -164,7 +187,18 @@ async fn main(spawner: Spawner) -> ! {
     #[cfg(feature = "console")]
     {
         spawner.must_spawn(console::task::usb_task(usb));
-        spawner.must_spawn(console::task::echo_task(class));
+        spawner.must_spawn(console::task::console_task(class, ch.sender()));
+    }
+
+    // Use user button for wakeup
+    let mut wakeup_button = Button::new(p.P1_06.degrade(), button::Polarity::ActiveLow, true);
+    let woken_by_button = wakeup_button.is_pressed();
+    // Double-pressing the button to wake up the scale toggles broadcast mode for this session
+    let mut toggle_broadcast = false;
+    if calibration_mode::requested(&mut wakeup_button, &mut green_led).await {
+        calibration_mode::run(&mut wakeup_button, &mut green_led, ch.sender()).await;
+    } else if woken_by_button {
+        toggle_broadcast = ble::broadcast::double_pressed(&mut wakeup_button).await;
     }
 
     ch.sender().send(weight::Command::Tare).await;
@@ -172,9 +206,12 @@ async fn main(spawner: Spawner) -> ! {
     // TODO: make this deterministic
     Timer::after(Duration::from_millis(1000)).await;
 
-    // Use user button for wakeup
-    let wakeup_button = Button::new(p.P1_06.degrade(), button::Polarity::ActiveLow, true);
-    spawner.must_spawn(ble::task_fn(sd, ch.sender(), wakeup_button));
+    spawner.must_spawn(ble::task_fn(
+        sd,
+        ch.sender(),
+        wakeup_button,
+        toggle_broadcast,
+    ));
 
     loop {
         core::future::pending::<()>().await;
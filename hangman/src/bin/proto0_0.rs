@@ -36,6 +36,8 @@ use embedded_alloc::Heap;
 use hangman::{
     battery_voltage, ble, blocking_hal,
     button::{self, Button},
+    calibration_mode,
+    led::Led,
     make_static, pac, util,
     weight::{self, Hx711},
     MeasureCommandChannel, SharedDelay,
@@ -91,6 +93,7 @@ async fn main(spawner: Spawner) -> ! {
     }
     weight::init(weight::Config {
         sampling_interval_hz: 80,
+        oversampling_ratio: 1,
     });
 
     let p = embassy_nrf::init(config());
@@ -115,7 +118,10 @@ async fn main(spawner: Spawner) -> ! {
     let usb_detect_ref: &SoftwareVbusDetect =
         make_static!(SoftwareVbusDetect, SoftwareVbusDetect::new(true, true));
 
-    let sd = ble::init_softdevice();
+    let sd = ble::init_softdevice(ble::DeviceInfo {
+        model: core::env!("CARGO_BIN_NAME"),
+        hardware_revision: "P0.0",
+    });
     spawner.must_spawn(softdevice_task(sd, usb_detect_ref));
 
     // It's recommended to start the SoftDevice before doing anything else
@@ -130,7 +136,7 @@ async fn main(spawner: Spawner) -> ! {
     };
 
     let ch: &MeasureCommandChannel = make_static!(MeasureCommandChannel, Channel::new());
-    spawner.must_spawn(weight::task_function(ch.receiver(), hx711, sd));
+    spawner.must_spawn(weight::task_function(ch.receiver(), hx711, None, sd));
 
     // Sample battery voltage while sampling to get a reading under load
     ch.sender()
@@ -142,14 +148,20 @@ async fn main(spawner: Spawner) -> ! {
     defmt::info!("Battery voltage: {=u32} mV", battery_voltage);
     ch.sender().send(weight::Command::StopSampling).await;
 
+    // Use SW1 = power button for wakeup
+    let mut wakeup_button = Button::new(p.P0_29.degrade(), button::Polarity::ActiveLow, true);
+    // The dongle's green LED
+    let mut led = Led::new(p.P0_06.degrade(), button::Polarity::ActiveLow);
+    if calibration_mode::requested(&mut wakeup_button, &mut led).await {
+        calibration_mode::run(&mut wakeup_button, &mut led, ch.sender()).await;
+    }
+
     ch.sender().send(weight::Command::Tare).await;
     // Allow time for tare to complete before starting advertising
     // TODO: make this deterministic
     Timer::after(Duration::from_millis(1000)).await;
 
-    // Use SW1 = power button for wakeup
-    let wakeup_button = Button::new(p.P0_29.degrade(), button::Polarity::ActiveLow, true);
-    spawner.must_spawn(ble::task_fn(sd, ch.sender(), wakeup_button));
+    spawner.must_spawn(ble::task_fn(sd, ch.sender(), wakeup_button, false));
 
     loop {
         core::future::pending::<()>().await;
@@ -36,6 +36,8 @@ use embedded_alloc::Heap;
 use hangman::{
     battery_voltage, ble, blocking_hal,
     button::{self, Button},
+    calibration_mode,
+    led::Led,
     make_static, pac, sleep, util,
     weight::{self, Ads1230},
     MeasureCommandChannel, SharedDelay,
@@ -91,6 +93,7 @@ async fn main(spawner: Spawner) -> ! {
     }
     weight::init(weight::Config {
         sampling_interval_hz: 80,
+        oversampling_ratio: 1,
     });
 
     let p = embassy_nrf::init(config());
@@ -98,7 +101,10 @@ async fn main(spawner: Spawner) -> ! {
     let delay: &'static SharedDelay =
         make_static!(SharedDelay, Mutex::new(SysTickDelay::new(syst)));
 
-    let sd = ble::init_softdevice();
+    let sd = ble::init_softdevice(ble::DeviceInfo {
+        model: core::env!("CARGO_BIN_NAME"),
+        hardware_revision: "P1.0",
+    });
     spawner.must_spawn(softdevice_task(sd));
 
     // It's recommended to start the SoftDevice before doing anything else
@@ -144,7 +150,7 @@ async fn main(spawner: Spawner) -> ! {
     adc.schedule_offset_calibration().await;
 
     let ch: &MeasureCommandChannel = make_static!(MeasureCommandChannel, Channel::new());
-    spawner.must_spawn(weight::task_function(ch.receiver(), adc, sd));
+    spawner.must_spawn(weight::task_function(ch.receiver(), adc, None, sd));
 
     // Sample battery voltage while sampling to get a reading under load
     ch.sender()
@@ -156,15 +162,31 @@ async fn main(spawner: Spawner) -> ! {
     defmt::info!("Battery voltage: {=u32} mV", battery_voltage);
     ch.sender().send(weight::Command::StopSampling).await;
 
-    // This will run the offset calibration that we scheduled above
+    // Use SW1 = power button for wakeup
+    let mut wakeup_button = Button::new(p.P0_09.degrade(), button::Polarity::ActiveLow, true);
+    let mut led = Led::new(p.P0_26.degrade(), button::Polarity::ActiveLow);
+    let woken_by_button = wakeup_button.is_pressed();
+    // Double-pressing the button to wake up the scale toggles broadcast mode for this session
+    let mut toggle_broadcast = false;
+    if calibration_mode::requested(&mut wakeup_button, &mut led).await {
+        calibration_mode::run(&mut wakeup_button, &mut led, ch.sender()).await;
+    } else if woken_by_button {
+        toggle_broadcast = ble::broadcast::double_pressed(&mut wakeup_button).await;
+    }
+
+    // The offset calibration that we scheduled above runs as part of the measurement task's boot-time
+    // self-test
     ch.sender().send(weight::Command::Tare).await;
     // Allow time for tare to complete before starting advertising
     // TODO: make this deterministic
     Timer::after(Duration::from_millis(1000)).await;
 
-    // Use SW1 = power button for wakeup
-    let wakeup_button = Button::new(p.P0_09.degrade(), button::Polarity::ActiveLow, true);
-    spawner.must_spawn(ble::task_fn(sd, ch.sender(), wakeup_button));
+    spawner.must_spawn(ble::task_fn(
+        sd,
+        ch.sender(),
+        wakeup_button,
+        toggle_broadcast,
+    ));
 
     loop {
         core::future::pending::<()>().await;
@@ -14,11 +14,15 @@
 
 use super::ADVERTISED_NAME;
 use arrayvec::ArrayVec;
+use embassy_time::Duration;
 use nrf_softdevice::ble::peripheral::AdvertiseError;
 use nrf_softdevice::ble::{Connection, Phy, TxPower};
 use nrf_softdevice::{ble, raw as raw_sd, Softdevice};
 
 const ADVERTISING_TIMEOUT_SEC: u16 = 3 * 60;
+/// Same as the default of `ble::peripheral::Config`
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(250);
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(100);
 
 #[rustfmt::skip]
 const SCAN_RESPONSE_DATA: &[u8] = &[
@@ -43,11 +47,31 @@ fn advertising_data(name: &[u8]) -> Result<ArrayVec<u8, 27>, ()> {
     Ok(advertising_data)
 }
 
+/// Advertise for a connection at boot
 pub(crate) async fn start(sd: &Softdevice) -> Result<Connection, AdvertiseError> {
+    advertise(sd, ADVERTISING_TIMEOUT_SEC, DEFAULT_INTERVAL).await
+}
+
+/// Advertise for the peer to reconnect after a disconnect, more often than at boot so that the
+/// link comes back quickly
+pub(crate) async fn restart(
+    sd: &Softdevice,
+    timeout_sec: u16,
+) -> Result<Connection, AdvertiseError> {
+    advertise(sd, timeout_sec, RECONNECT_INTERVAL).await
+}
+
+async fn advertise(
+    sd: &Softdevice,
+    timeout_sec: u16,
+    interval: Duration,
+) -> Result<Connection, AdvertiseError> {
     let advertising_data = advertising_data(ADVERTISED_NAME).expect("Valid advertising data");
     let config = ble::peripheral::Config {
+        // Interval is passed as # of 0.625 ms periods
+        interval: (interval.as_micros() / 625) as u32,
         // Timeout is passed as # of 10 ms periods
-        timeout: Some(ADVERTISING_TIMEOUT_SEC * (1000 / 10)),
+        timeout: Some(timeout_sec * (1000 / 10)),
         // Primary PHY must be 1M
         primary_phy: Phy::M1,
         secondary_phy: Phy::M2,
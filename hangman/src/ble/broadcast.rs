@@ -0,0 +1,165 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connectionless broadcast of the weight, like WH-C06 style hanging scales
+//!
+//! Instead of waiting for a connection, the scale advertises non-connectably and updates the
+//! manufacturer-specific data with the current tared weight every broadcast interval, so any
+//! number of phones can watch it at once. See `hangman_utils::wh_c06` for the layout.
+//!
+//! Broadcasting stops, and the scale goes to sleep, when the button is pressed or once the weight
+//! hasn't changed for `IDLE_TIMEOUT`.
+
+extern crate alloc;
+
+use super::{MeasureChannel, ADVERTISED_NAME};
+use crate::button::Button;
+use crate::error_log::{self, ErrorCode};
+use crate::weight;
+use alloc::boxed::Box;
+use arrayvec::ArrayVec;
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant};
+use hangman_utils::wh_c06;
+use nrf_softdevice::ble::peripheral::AdvertiseError;
+use nrf_softdevice::ble::TxPower;
+use nrf_softdevice::{ble, raw as raw_sd, Softdevice};
+
+/// How soon after waking the scale the button must be pressed again to count as a double press
+const DOUBLE_PRESS_WINDOW: Duration = Duration::from_secs(1);
+/// Advertising interval, no longer than the shortest broadcast interval so that every update is
+/// sent at least once
+const ADVERTISING_INTERVAL: Duration = Duration::from_millis(100);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+/// Change in weight that counts as the scale being used
+const IDLE_THRESHOLD_KG: f32 = 0.5;
+
+/// Latest tared weight in kg
+static WEIGHT: Signal<CriticalSectionRawMutex, f32> = Signal::new();
+
+/// Whether the button is pressed again within `DOUBLE_PRESS_WINDOW` of the press that woke the
+/// scale up. Should be called right after boot, if the button woke the scale up.
+pub async fn double_pressed(button: &mut Button) -> bool {
+    button.wait_for_release().await;
+    let pressed = with_timeout(DOUBLE_PRESS_WINDOW, button.wait_for_press())
+        .await
+        .is_ok();
+    if pressed {
+        button.wait_for_release().await;
+    }
+    pressed
+}
+
+fn advertising_data(weight_kg: f32) -> ArrayVec<u8, 31> {
+    let mut advertising_data: ArrayVec<u8, 31> = ArrayVec::new();
+    advertising_data.push(2);
+    advertising_data.push(raw_sd::BLE_GAP_AD_TYPE_FLAGS as u8);
+    advertising_data.push(
+        (raw_sd::BLE_GAP_ADV_FLAG_LE_GENERAL_DISC_MODE
+            | raw_sd::BLE_GAP_ADV_FLAG_BR_EDR_NOT_SUPPORTED) as u8,
+    );
+    advertising_data.extend(wh_c06::advertising_data(weight_kg));
+    advertising_data
+}
+
+fn scan_response_data() -> ArrayVec<u8, 31> {
+    let mut scan_response_data: ArrayVec<u8, 31> = ArrayVec::new();
+    scan_response_data.push(ADVERTISED_NAME.len() as u8 + 1);
+    scan_response_data.push(raw_sd::BLE_GAP_AD_TYPE_COMPLETE_LOCAL_NAME as u8);
+    scan_response_data
+        .try_extend_from_slice(ADVERTISED_NAME)
+        .expect("Name fits in the scan response");
+    scan_response_data
+}
+
+/// Advertise `weight_kg` for `duration`
+async fn advertise(
+    sd: &Softdevice,
+    weight_kg: f32,
+    duration: Duration,
+) -> Result<(), AdvertiseError> {
+    let advertising_data = advertising_data(weight_kg);
+    let scan_response_data = scan_response_data();
+    let config = ble::peripheral::Config {
+        // Interval is passed as # of 0.625 ms periods
+        interval: (ADVERTISING_INTERVAL.as_micros() / 625) as u32,
+        // Timeout is passed as # of 10 ms periods
+        timeout: Some((duration.as_millis() / 10) as u16),
+        // Same as when advertising for connections
+        tx_power: TxPower::Minus4dBm,
+        ..Default::default()
+    };
+    let adv = ble::peripheral::NonconnectableAdvertisement::ScannableUndirected {
+        adv_data: advertising_data.as_slice(),
+        scan_data: scan_response_data.as_slice(),
+    };
+    match ble::peripheral::advertise(sd, adv, &config).await {
+        Ok(()) | Err(AdvertiseError::Timeout) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Broadcast the tared weight every `interval` until the button is pressed or the scale is idle
+pub(crate) async fn run(
+    sd: &Softdevice,
+    measure_ch: &MeasureChannel,
+    button: &mut Button,
+    interval: Duration,
+) {
+    WEIGHT.reset();
+    let measurement_cb = Box::new(|_: Duration, weight_kg: f32| WEIGHT.signal(weight_kg));
+    measure_ch
+        .send(weight::Command::StartSampling(weight::SampleType::Tared(
+            Some(measurement_cb),
+        )))
+        .await;
+
+    let broadcast = async {
+        let mut weight_kg = 0.0;
+        let mut active_weight_kg = 0.0;
+        let mut active_at = Instant::now();
+        loop {
+            if let Some(latest) = WEIGHT.try_take() {
+                weight_kg = latest;
+            }
+            if (weight_kg - active_weight_kg).abs() > IDLE_THRESHOLD_KG {
+                active_weight_kg = weight_kg;
+                active_at = Instant::now();
+            } else if active_at.elapsed() > IDLE_TIMEOUT {
+                defmt::info!("Idle, stopping broadcast");
+                return;
+            }
+            if let Err(err) = advertise(sd, weight_kg, interval).await {
+                match err {
+                    AdvertiseError::Raw(err) => {
+                        defmt::error!("Advertising error: {=u32}", err as u32);
+                    }
+                    _ => defmt::error!("Failed to broadcast"),
+                }
+                error_log::record(ErrorCode::AdvertisingFailed);
+                return;
+            }
+        }
+    };
+    let pressed = async {
+        // Ignore the press that woke the scale up if it's still held
+        button.wait_for_release().await;
+        button.wait_for_press().await;
+    };
+    select(pressed, broadcast).await;
+    // Don't wake up again right away
+    button.wait_for_release().await;
+}
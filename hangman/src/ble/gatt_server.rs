@@ -15,23 +15,111 @@
 extern crate alloc;
 
 use super::gatt_types::{
-    CalibrationCurve, ControlOpcode, DataOpcode, DataPoint, DATA_PAYLOAD_SIZE,
+    CellsPoint, ControlOpcode, DataOpcode, DataPoint, DeviceString, StreamPacket,
+    CERTIFICATE_CHUNK_SIZE, DATA_PAYLOAD_SIZE,
 };
-use super::MeasureChannel;
-use crate::{battery_voltage, weight};
+#[cfg(feature = "ble-console")]
+use super::gatt_types::{ConsoleData, CONSOLE_DATA_SIZE};
+use super::peers::{self, PeerId};
+use super::{DeviceInfo, MeasureChannel};
+#[cfg(feature = "ble-console")]
+use crate::console::command;
+use crate::error_log::{self, ErrorCode};
+use crate::nonvolatile::CalibrationRecord;
+use crate::{battery_voltage, build_info, weight};
 use alloc::boxed::Box;
-use embassy_time::Duration;
-use nrf_softdevice::ble::gatt_server::NotifyValueError;
+#[cfg(feature = "ble-console")]
+use arrayvec::ArrayString;
+use arrayvec::ArrayVec;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_futures::select::select;
+#[cfg(feature = "ble-console")]
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, pipe::Pipe};
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use hangman_utils::stream;
+#[cfg(feature = "weight-scale")]
+use hangman_utils::{stability, weight_scale};
+use nrf_softdevice::ble::gatt_server::{self, NotifyValueError};
 use nrf_softdevice::ble::Connection;
-use nrf_softdevice::Softdevice;
+use nrf_softdevice::{RawError, Softdevice};
 use once_cell::sync::OnceCell;
 
-#[nrf_softdevice::gatt_server]
+/// Written out instead of using `#[nrf_softdevice::gatt_server]`, which drops the `#[cfg]` of
+/// optional services from the code that it generates
 struct Server {
     progressor: ProgressorService,
+    hangman: HangmanService,
+    battery: BatteryService,
+    device_information: DeviceInformationService,
+    #[cfg(feature = "weight-scale")]
+    weight_scale: WeightScaleService,
+    #[cfg(feature = "ble-console")]
+    uart: NordicUartService,
+}
+
+enum ServerEvent {
+    Progressor(ProgressorServiceEvent),
+    Hangman(HangmanServiceEvent),
+    Battery(BatteryServiceEvent),
+    DeviceInformation(DeviceInformationServiceEvent),
+    #[cfg(feature = "weight-scale")]
+    WeightScale(WeightScaleServiceEvent),
+    #[cfg(feature = "ble-console")]
+    Uart(NordicUartServiceEvent),
+}
+
+impl gatt_server::Server for Server {
+    type Event = ServerEvent;
+
+    fn on_write(
+        &self,
+        _conn: &Connection,
+        handle: u16,
+        _op: gatt_server::WriteOp,
+        _offset: usize,
+        data: &[u8],
+    ) -> Option<Self::Event> {
+        use gatt_server::Service;
+
+        if let Some(e) = self.progressor.on_write(handle, data) {
+            return Some(ServerEvent::Progressor(e));
+        }
+        if let Some(e) = self.hangman.on_write(handle, data) {
+            return Some(ServerEvent::Hangman(e));
+        }
+        if let Some(e) = self.battery.on_write(handle, data) {
+            return Some(ServerEvent::Battery(e));
+        }
+        if let Some(e) = self.device_information.on_write(handle, data) {
+            return Some(ServerEvent::DeviceInformation(e));
+        }
+        #[cfg(feature = "weight-scale")]
+        if let Some(e) = self.weight_scale.on_write(handle, data) {
+            return Some(ServerEvent::WeightScale(e));
+        }
+        #[cfg(feature = "ble-console")]
+        if let Some(e) = self.uart.on_write(handle, data) {
+            return Some(ServerEvent::Uart(e));
+        }
+        None
+    }
 }
 
 impl Server {
+    fn new(sd: &mut Softdevice) -> Result<Self, gatt_server::RegisterError> {
+        Ok(Self {
+            progressor: ProgressorService::new(sd)?,
+            hangman: HangmanService::new(sd)?,
+            battery: BatteryService::new(sd)?,
+            device_information: DeviceInformationService::new(sd)?,
+            #[cfg(feature = "weight-scale")]
+            weight_scale: WeightScaleService::new(sd)?,
+            #[cfg(feature = "ble-console")]
+            uart: NordicUartService::new(sd)?,
+        })
+    }
+
     fn get() -> &'static Self {
         GATT_SERVER.get().expect("GATT_SERVER to be initialized")
     }
@@ -50,10 +138,305 @@ struct ProgressorService {
     control: ControlOpcode,
 }
 
+/// Hangman-specific extensions that don't fit into the Progressor API
+#[nrf_softdevice::gatt_service(uuid = "d1a10001-6b4e-4c8f-9a0d-3f5e2c7b8a90")]
+struct HangmanService {
+    /// Per-load cell weights. Only sent on scales with more than one load cell.
+    #[characteristic(uuid = "d1a10002-6b4e-4c8f-9a0d-3f5e2c7b8a90", notify)]
+    cells: CellsPoint,
+    /// Progressor weights packed several to a notification, for high sample rates
+    #[characteristic(uuid = "d1a10003-6b4e-4c8f-9a0d-3f5e2c7b8a90", notify)]
+    stream: StreamPacket,
+}
+
+/// Standard Battery Service, so that generic tools and OS battery widgets can show the battery level
+#[nrf_softdevice::gatt_service(uuid = "180f")]
+struct BatteryService {
+    /// Battery level in percent
+    #[characteristic(uuid = "2a19", read, notify)]
+    battery_level: u8,
+}
+
+/// Standard Device Information Service, to trace a unit to its exact build
+#[nrf_softdevice::gatt_service(uuid = "180a")]
+struct DeviceInformationService {
+    #[characteristic(uuid = "2a29", read)]
+    manufacturer_name: DeviceString,
+    #[characteristic(uuid = "2a24", read)]
+    model_number: DeviceString,
+    /// `DEVICE_ID`
+    #[characteristic(uuid = "2a25", read)]
+    serial_number: DeviceString,
+    #[characteristic(uuid = "2a27", read)]
+    hardware_revision: DeviceString,
+    /// `DEVICE_VERSION_NUMBER`, as reported by the Progressor API
+    #[characteristic(uuid = "2a26", read)]
+    firmware_revision: DeviceString,
+    /// Git commit, dirty flag, and build profile
+    #[characteristic(uuid = "2a28", read)]
+    software_revision: DeviceString,
+}
+
+/// Standard Weight Scale Service, so that generic health apps and gateways can use the scale as an
+/// ordinary scale. Weighing starts when the peer enables indications.
+#[cfg(feature = "weight-scale")]
+#[nrf_softdevice::gatt_service(uuid = "181d")]
+struct WeightScaleService {
+    #[characteristic(uuid = "2a9e", read)]
+    weight_scale_feature: u32,
+    /// One stable weight per weighing, in kg or lb depending on the configured units
+    #[characteristic(uuid = "2a9d", indicate)]
+    weight_measurement: [u8; weight_scale::MEASUREMENT_SIZE],
+}
+
+/// Nordic UART Service, the de facto standard serial port over BLE that terminal apps support, for
+/// the text console
+#[cfg(feature = "ble-console")]
+#[nrf_softdevice::gatt_service(uuid = "6e400001-b5a3-f393-e0a9-e50e24dcca9e")]
+struct NordicUartService {
+    /// Input typed by the peer
+    #[characteristic(
+        uuid = "6e400002-b5a3-f393-e0a9-e50e24dcca9e",
+        write,
+        write_without_response
+    )]
+    rx: ConsoleData,
+    /// Console output
+    #[characteristic(uuid = "6e400003-b5a3-f393-e0a9-e50e24dcca9e", notify)]
+    tx: ConsoleData,
+}
+
+/// Weight resolution advertised by the Weight Scale Service. The noise is well below this once the
+/// weight is stable.
+#[cfg(feature = "weight-scale")]
+const WEIGHT_SCALE_RESOLUTION: weight_scale::Resolution = weight_scale::Resolution::Kg0_05;
+/// Weights below this aren't reported by the Weight Scale Service
+#[cfg(feature = "weight-scale")]
+const WEIGHT_SCALE_MIN_KG: f32 = 1.0;
+
+const MANUFACTURER_NAME: &str = "Hangman";
+
+/// Console input buffered while a command runs
+#[cfg(feature = "ble-console")]
+const CONSOLE_INPUT_SIZE: usize = 128;
+#[cfg(feature = "ble-console")]
+type ConsoleInput = Pipe<NoopRawMutex, CONSOLE_INPUT_SIZE>;
+
+/// How long to wait before retrying a notification once the SoftDevice's queue of outgoing
+/// packets is full, about one connection interval
+const NOTIFY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+/// How long to keep retrying a notification before giving up on it
+const NOTIFY_RETRY_TIMEOUT: Duration = Duration::from_millis(250);
+
 static GATT_SERVER: OnceCell<Server> = OnceCell::new();
+/// Whether the connections were ended to go to sleep
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Notifications that a peer has enabled
+#[derive(Default)]
+struct Subscriptions {
+    /// Progressor weights
+    data: Cell<bool>,
+    /// Per-load cell weights
+    cells: Cell<bool>,
+    /// Packed Progressor weights
+    stream: Cell<bool>,
+    /// Console output
+    #[cfg(feature = "ble-console")]
+    console: Cell<bool>,
+}
+
+/// How a connection ended
+#[derive(Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub(crate) enum Disconnect {
+    /// The link was lost or closed by the peer, which may reconnect
+    Dropped,
+    /// A peer asked to shut down, or the battery is low
+    Shutdown,
+}
+
+pub(crate) fn init(sd: &mut Softdevice, device_info: &DeviceInfo) -> Result<(), ()> {
+    let server = Server::new(sd).unwrap();
+    let service = &server.device_information;
+    let values = [
+        service.manufacturer_name_set(&DeviceString::new(MANUFACTURER_NAME)),
+        service.model_number_set(&DeviceString::new(device_info.model)),
+        service.serial_number_set(&DeviceString::new(env!("DEVICE_ID"))),
+        service.hardware_revision_set(&DeviceString::new(device_info.hardware_revision)),
+        service.firmware_revision_set(&DeviceString::new(build_info::VERSION)),
+        service.software_revision_set(&DeviceString::new(build_info::DESCRIPTION)),
+    ];
+    if values.iter().any(Result::is_err) {
+        defmt::error!("Failed to set device information");
+    }
+    #[cfg(feature = "weight-scale")]
+    if server
+        .weight_scale
+        .weight_scale_feature_set(&weight_scale::feature(WEIGHT_SCALE_RESOLUTION))
+        .is_err()
+    {
+        defmt::error!("Failed to set weight scale feature");
+    }
+    GATT_SERVER.set(server).map_err(|_| ())
+}
+
+/// Disconnect every peer and go to sleep without waiting for them to reconnect
+fn shut_down() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+    peers::publish(peers::Event::Shutdown);
+}
+
+/// Forward events to the peer, as far as it subscribed to them
+async fn forward_events(conn: &Connection, subscriptions: &Subscriptions) -> ! {
+    let server = Server::get();
+    let mut subscriber = peers::subscribe();
+    let mut packer = stream::Packer::new(stream::MAX_PACKET_SIZE);
+    // Adding a sample sends up to two packets: the previous one and the one that it completes
+    let mut packets: ArrayVec<StreamPacket, 2> = ArrayVec::new();
+    loop {
+        let event = if packer.is_empty() {
+            peers::next_event(&mut subscriber).await
+        } else {
+            let latency = Duration::from_micros(stream::MAX_LATENCY_US.into());
+            match with_timeout(latency, peers::next_event(&mut subscriber)).await {
+                Ok(event) => event,
+                Err(_) => {
+                    // Don't hold back the last samples of a measurement
+                    packer.flush(|packet| packets.push(StreamPacket::new(packet)));
+                    for packet in packets.drain(..) {
+                        notify_with_retry(|| server.hangman.stream_notify(conn, &packet)).await;
+                    }
+                    continue;
+                }
+            }
+        };
+        match event {
+            peers::Event::Weight {
+                timestamp_us,
+                weight,
+                cells,
+            } => {
+                if subscriptions.stream.get() {
+                    // A notification fits the ATT MTU minus the 3 byte ATT header
+                    packer.set_max_size(usize::from(conn.att_mtu()) - 3);
+                    packer.add_sample(timestamp_us, weight, |packet| {
+                        packets.push(StreamPacket::new(packet));
+                    });
+                    for packet in packets.drain(..) {
+                        notify_with_retry(|| server.hangman.stream_notify(conn, &packet)).await;
+                    }
+                }
+                if subscriptions.data.get() {
+                    let data: DataPoint = DataOpcode::Weight(weight, timestamp_us).into();
+                    notify_with_retry(|| server.progressor.data_notify(conn, &data)).await;
+                }
+                if cells.len() > 1 && subscriptions.cells.get() {
+                    // Unlike the Progressor weight, per-cell weights are in the configured units
+                    let units = weight::units();
+                    let cells: ArrayVec<f32, { weight::MAX_CELLS }> =
+                        cells.iter().map(|&kg| units.convert(kg)).collect();
+                    let point = CellsPoint::new(timestamp_us, &cells);
+                    notify_with_retry(|| server.hangman.cells_notify(conn, &point)).await;
+                }
+            }
+            peers::Event::Shutdown => {
+                if conn.disconnect().is_err() {
+                    defmt::error!("Failed to disconnect");
+                }
+            }
+        }
+    }
+}
+
+/// Run the text console for the peer, as long as it's in control
+#[cfg(feature = "ble-console")]
+async fn console(
+    conn: &Connection,
+    peer: PeerId,
+    input: &ConsoleInput,
+    subscriptions: &Subscriptions,
+    measure_ch: &MeasureChannel,
+) -> ! {
+    let server = Server::get();
+    let mut buf = [0; CONSOLE_INPUT_SIZE];
+    let mut editor = command::LineBuffer::default();
+    loop {
+        let n = input.read(&mut buf).await;
+        for &byte in &buf[..n] {
+            let Some(line) = editor.push(byte) else {
+                continue;
+            };
+            let response = if peers::take_control(peer) {
+                command::respond(&line, measure_ch).await
+            } else {
+                defmt::warn!(
+                    "Peer {=u32} is only observing, ignoring console input",
+                    peer
+                );
+                error_log::record(ErrorCode::InvalidCommand);
+                let mut response = ArrayString::new();
+                response.push_str("\r\nAnother device is in control\r\n");
+                response.push_str(command::PROMPT);
+                response
+            };
+            if !subscriptions.console.get() {
+                defmt::warn!("Console output isn't enabled");
+                continue;
+            }
+            // A notification fits the ATT MTU minus the 3 byte ATT header
+            let chunk_size = (usize::from(conn.att_mtu()) - 3).min(CONSOLE_DATA_SIZE);
+            for chunk in response.as_bytes().chunks(chunk_size) {
+                let data = ConsoleData::new(chunk);
+                notify_with_retry(|| server.uart.tx_notify(conn, &data)).await;
+            }
+        }
+    }
+}
 
-pub(crate) fn init(sd: &mut Softdevice) -> Result<(), ()> {
-    GATT_SERVER.set(Server::new(sd).unwrap()).map_err(|_| ())
+/// Send a notification, retrying for up to `NOTIFY_RETRY_TIMEOUT` while the SoftDevice's queue of
+/// outgoing packets is full
+///
+/// Meanwhile, further events queue up for the peer. If the radio doesn't catch up, the oldest ones
+/// are dropped, see `peers`.
+async fn notify_with_retry(mut notify: impl FnMut() -> Result<(), NotifyValueError>) {
+    let deadline = Instant::now() + NOTIFY_RETRY_TIMEOUT;
+    loop {
+        match notify() {
+            Ok(()) => {
+                peers::record_sent();
+                return;
+            }
+            Err(NotifyValueError::Raw(RawError::Resources)) if Instant::now() < deadline => {
+                peers::record_retry();
+                Timer::after(NOTIFY_RETRY_INTERVAL).await;
+            }
+            Err(_) => {
+                defmt::error!("Notify failed");
+                error_log::record(ErrorCode::NotifyFailed);
+                peers::record_failed();
+                return;
+            }
+        }
+    }
+}
+
+/// Ask for the longest link layer packets that both sides support, so that long notifications
+/// don't need to be fragmented
+fn request_data_length_update(conn: &Connection) {
+    let Some(handle) = conn.handle() else {
+        return;
+    };
+    // SAFETY: null parameters let the SoftDevice pick the largest supported values
+    let ret = unsafe {
+        nrf_softdevice::raw::sd_ble_gap_data_length_update(
+            handle,
+            core::ptr::null(),
+            core::ptr::null_mut(),
+        )
+    };
+    if ret != nrf_softdevice::raw::NRF_SUCCESS {
+        defmt::warn!("Data length update failed: {=u32}", ret);
+    }
 }
 
 fn notify_data(data: DataOpcode, connection: &Connection) -> Result<(), NotifyValueError> {
@@ -62,6 +445,46 @@ fn notify_data(data: DataOpcode, connection: &Connection) -> Result<(), NotifyVa
         .data_notify(connection, &data.into())
 }
 
+/// Measure continuously and indicate each stable weight on the Weight Scale Service
+#[cfg(feature = "weight-scale")]
+fn start_weighing(conn: &Connection, measure_ch: &MeasureChannel) {
+    let settings = stability::Settings {
+        // 0.25 second
+        block_len: (weight::output_rate_hz() / 4).max(1) as u32,
+        // 1 second in total
+        n_blocks: 4,
+        max_std_dev: 0.1,
+    };
+    let mut detector = weight_scale::Detector::new(settings, WEIGHT_SCALE_MIN_KG);
+    let measurement_cb = Box::new({
+        let conn = conn.clone();
+        move |_: Duration, weight_kg: f32| {
+            let Some(weight_kg) = detector.add_sample(weight_kg) else {
+                return;
+            };
+            defmt::info!("Stable weight: {=f32} kg", weight_kg);
+            let measurement = weight_scale::measurement(weight_kg, weight::units().unit);
+            if Server::get()
+                .weight_scale
+                .weight_measurement_indicate(&conn, &measurement)
+                .is_err()
+            {
+                defmt::error!("Failed to indicate weight measurement");
+                error_log::record(ErrorCode::NotifyFailed);
+            }
+        }
+    });
+    if measure_ch
+        .try_send(weight::Command::StartSampling(weight::SampleType::Tared(
+            Some(measurement_cb),
+        )))
+        .is_err()
+    {
+        defmt::error!("Failed to send StartSampling");
+        error_log::record(ErrorCode::CommandQueueFull);
+    }
+}
+
 /// Test function for sending out raw notifications
 #[allow(dead_code)]
 fn raw_notify_data(
@@ -82,42 +505,42 @@ fn on_control_message(message: ControlOpcode, conn: &Connection, measure_ch: &Me
         defmt::info!("ProgressorService.ControlWrite: {}", message);
     } else {
         defmt::warn!("ProgressorService.ControlWrite: {}", message);
+        if let ControlOpcode::Invalid = message {
+            error_log::record(ErrorCode::InvalidCommand);
+        }
     }
     match message {
         ControlOpcode::Tare => {
             if measure_ch.try_send(weight::Command::Tare).is_err() {
                 defmt::error!("Failed to send Tare");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         ControlOpcode::StartMeasurement => {
-            let notify_cb = Box::new({
-                let conn = conn.clone();
-                move |duration_since_start: Duration, measurement: f32| {
-                    if notify_data(
-                        DataOpcode::Weight(
-                            measurement,
-                            u32::try_from(duration_since_start.as_micros()).unwrap(),
-                        ),
-                        &conn,
-                    )
-                    .is_err()
-                    {
-                        defmt::error!("Notify failed");
-                    }
-                }
-            });
+            // Every peer gets the measurements, not just the one that started them
+            let notify_cb = Box::new(
+                |duration_since_start: Duration, measurement: f32, cells: &[f32]| {
+                    peers::publish(peers::Event::Weight {
+                        timestamp_us: u32::try_from(duration_since_start.as_micros()).unwrap(),
+                        weight: measurement,
+                        cells: cells.iter().copied().collect(),
+                    });
+                },
+            );
             if measure_ch
-                .try_send(weight::Command::StartSampling(weight::SampleType::Tared(
-                    Some(notify_cb),
-                )))
+                .try_send(weight::Command::StartSampling(
+                    weight::SampleType::TaredCells(Some(notify_cb)),
+                ))
                 .is_err()
             {
                 defmt::error!("Failed to send StartSampling");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         ControlOpcode::StopMeasurement => {
             if measure_ch.try_send(weight::Command::StopSampling).is_err() {
                 defmt::error!("Failed to send StopSampling");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         ControlOpcode::SampleBattery => {
@@ -125,91 +548,606 @@ fn on_control_message(message: ControlOpcode, conn: &Connection, measure_ch: &Me
                 battery_voltage::get_startup_reading().expect("Battery to have been sampled");
             if notify_data(DataOpcode::BatteryVoltage(battery_voltage_mv), conn).is_err() {
                 defmt::error!("Battery voltage response failed to send");
+                error_log::record(ErrorCode::NotifyFailed);
             }
         }
         ControlOpcode::GetAppVersion => {
-            if notify_data(
-                DataOpcode::AppVersion(env!("DEVICE_VERSION_NUMBER").as_bytes()),
-                conn,
-            )
-            .is_err()
-            {
+            if notify_data(DataOpcode::AppVersion(build_info::VERSION.as_bytes()), conn).is_err() {
                 defmt::error!("Response to GetAppVersion failed");
+                error_log::record(ErrorCode::NotifyFailed);
             };
         }
         ControlOpcode::GetProgressorID => {
-            if notify_data(
-                DataOpcode::ProgressorId(env!("DEVICE_ID").parse().unwrap()),
-                conn,
-            )
-            .is_err()
-            {
+            if notify_data(DataOpcode::ProgressorId(crate::build_info::DEVICE_ID), conn).is_err() {
                 defmt::error!("Response to GetProgressorID failed");
+                error_log::record(ErrorCode::NotifyFailed);
             };
         }
-        ControlOpcode::Shutdown => {
-            // no-op. The peer should disconnect, which sends us to system oFF.
+        ControlOpcode::GetNotificationStats => {
+            if notify_data(DataOpcode::NotificationStats(peers::stats()), conn).is_err() {
+                defmt::error!("Response to GetNotificationStats failed");
+                error_log::record(ErrorCode::NotifyFailed);
+            };
         }
-        ControlOpcode::AddCalibrationPoint(known_weight) => {
+        ControlOpcode::Shutdown => shut_down(),
+        ControlOpcode::AddCalibrationPoint(known_weight, branch) => {
+            let opcode = match branch {
+                weight::Branch::Loading => 0x69,
+                weight::Branch::Unloading => 0xA6,
+            };
+            let point_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<Option<weight::CalibrationPoint>, weight::Rejected>| {
+                    let response = match result {
+                        Ok(point) => DataOpcode::CalibrationPoint(point),
+                        Err(rejected) => DataOpcode::Rejected(opcode, rejected),
+                    };
+                    if notify_data(response, &conn).is_err() {
+                        defmt::error!("Response to AddCalibrationPoint failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
             if measure_ch
-                .try_send(weight::Command::AddCalibrationPoint(known_weight))
+                .try_send(weight::Command::AddCalibrationPoint(
+                    known_weight,
+                    branch,
+                    Some(point_cb),
+                ))
                 .is_err()
             {
                 defmt::error!("Failed to send AddCalibrationPoint");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         ControlOpcode::SaveCalibration => {
+            let result_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<(), weight::CalibrationRejection>| {
+                    if notify_data(DataOpcode::SaveCalibration(result.err()), &conn).is_err() {
+                        defmt::error!("Response to SaveCalibration failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
             if measure_ch
-                .try_send(weight::Command::SaveCalibration)
+                .try_send(weight::Command::SaveCalibration(Some(result_cb)))
                 .is_err()
             {
                 defmt::error!("Failed to send SaveCalibration");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         ControlOpcode::GetCalibrationCurve => {
-            // The calibration curve is passed in via environment variable as a string of
-            // hex-encoded bytes for convenience. Cache the decoded bytes.
-            static CALIBRATION_CURVE: OnceCell<CalibrationCurve> = OnceCell::new();
-            let curve = CALIBRATION_CURVE.get_or_init(|| {
-                let mut buffer: CalibrationCurve = CalibrationCurve::default();
-                let Ok(_) = hex::decode_to_slice(
-                    env!("CALIBRATION_CURVE").as_bytes(),
-                    buffer.as_mut_slice(),
-                ) else {
-                    defmt::panic!("Invalid hex string provided for calibration curve");
-                };
-                buffer
+            let curve_cb = Box::new({
+                let conn = conn.clone();
+                move |m: f32, b: i32| {
+                    if notify_data(DataOpcode::CalibrationCurve(m, b), &conn).is_err() {
+                        defmt::error!("Failed to notify calibration curve");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::GetCalibration(Some(curve_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send GetCalibration");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetCalibrationCurve(m, b) => {
+            let result_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<(), weight::CalibrationRejection>| {
+                    if notify_data(DataOpcode::SaveCalibration(result.err()), &conn).is_err() {
+                        defmt::error!("Response to SetCalibrationCurve failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
             });
-            if notify_data(DataOpcode::CalibrationCurve(*curve), conn).is_err() {
-                defmt::error!("Failed to notify calibration curve");
+            if measure_ch
+                .try_send(weight::Command::SetCalibration(m, b, Some(result_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetCalibration");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::GetErrorInfo => {
+            let fault = weight::self_test::last_fault();
+            let log = error_log::snapshot();
+            let count = log.entries().len() as u8;
+            let result = if log.is_empty() {
+                notify_data(
+                    DataOpcode::ErrorInfo {
+                        fault,
+                        index: 0,
+                        count,
+                        entry: None,
+                    },
+                    conn,
+                )
+            } else {
+                log.entries()
+                    .iter()
+                    .enumerate()
+                    .try_for_each(|(index, entry)| {
+                        notify_data(
+                            DataOpcode::ErrorInfo {
+                                fault,
+                                index: index as u8,
+                                count,
+                                entry: Some(*entry),
+                            },
+                            conn,
+                        )
+                    })
+            };
+            if result.is_err() {
+                defmt::error!("Response to GetErrorInfo failed");
+                error_log::record(ErrorCode::NotifyFailed);
+            }
+        }
+        ControlOpcode::ClearErrorInfo => {
+            error_log::clear();
+            weight::self_test::set_last_fault(None);
+            if measure_ch.try_send(weight::Command::SaveErrorLog).is_err() {
+                defmt::error!("Failed to send SaveErrorLog");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::RunSelfTest => {
+            let report_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<weight::self_test::Report, weight::Rejected>| {
+                    let response = match result {
+                        Ok(report) => DataOpcode::SelfTest(report),
+                        Err(rejected) => DataOpcode::Rejected(0xA0, rejected),
+                    };
+                    if notify_data(response, &conn).is_err() {
+                        defmt::error!("Response to RunSelfTest failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::SelfTest(Some(report_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send SelfTest");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::CharacterizeNoise(n_samples) => {
+            let report_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<weight::noise::Report, weight::Rejected>| {
+                    let result = match result {
+                        Ok(report) => notify_data(DataOpcode::NoiseSummary(report), &conn)
+                            .and_then(|()| {
+                                notify_data(
+                                    DataOpcode::AllanDeviation(report.allan_deviation),
+                                    &conn,
+                                )
+                            }),
+                        Err(rejected) => notify_data(DataOpcode::Rejected(0xA2, rejected), &conn),
+                    };
+                    if result.is_err() {
+                        defmt::error!("Response to CharacterizeNoise failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            let n_samples = n_samples.map_or_else(weight::noise::default_n_samples, |n| n as usize);
+            if measure_ch
+                .try_send(weight::Command::CharacterizeNoise(
+                    n_samples,
+                    Some(report_cb),
+                ))
+                .is_err()
+            {
+                defmt::error!("Failed to send CharacterizeNoise");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::GetCalibrationHistory => {
+            let history_cb = Box::new({
+                let conn = conn.clone();
+                move |history: &[CalibrationRecord]| {
+                    let count = history.len() as u8;
+                    let result = if history.is_empty() {
+                        notify_data(
+                            DataOpcode::CalibrationHistoryEntry {
+                                index: 0,
+                                count,
+                                record: None,
+                            },
+                            &conn,
+                        )
+                    } else {
+                        history.iter().enumerate().try_for_each(|(index, record)| {
+                            notify_data(
+                                DataOpcode::CalibrationHistoryEntry {
+                                    index: index as u8,
+                                    count,
+                                    record: Some(*record),
+                                },
+                                &conn,
+                            )
+                        })
+                    };
+                    if result.is_err() {
+                        defmt::error!("Response to GetCalibrationHistory failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::GetCalibrationHistory(Some(history_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send GetCalibrationHistory");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::GetCertificates => {
+            let certificates_cb = Box::new({
+                let conn = conn.clone();
+                move |certificates: &[weight::Certificate]| {
+                    let count = certificates.len() as u8;
+                    let result = if certificates.is_empty() {
+                        notify_data(
+                            DataOpcode::CertificateChunk {
+                                count,
+                                cell: 0,
+                                offset: 0,
+                                len: 0,
+                                data: [0; CERTIFICATE_CHUNK_SIZE],
+                            },
+                            &conn,
+                        )
+                    } else {
+                        certificates.iter().try_for_each(|certificate| {
+                            let encoded = certificate.encode();
+                            encoded
+                                .chunks(CERTIFICATE_CHUNK_SIZE)
+                                .enumerate()
+                                .try_for_each(|(i, chunk)| {
+                                    let mut data = [0; CERTIFICATE_CHUNK_SIZE];
+                                    data[..chunk.len()].copy_from_slice(chunk);
+                                    notify_data(
+                                        DataOpcode::CertificateChunk {
+                                            count,
+                                            cell: certificate.cell,
+                                            offset: (i * CERTIFICATE_CHUNK_SIZE) as u8,
+                                            len: chunk.len() as u8,
+                                            data,
+                                        },
+                                        &conn,
+                                    )
+                                })
+                        })
+                    };
+                    if result.is_err() {
+                        defmt::error!("Response to GetCertificates failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::GetCertificates(Some(certificates_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send GetCertificates");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::RollBackCalibration(index) => {
+            let result_cb = Box::new({
+                let conn = conn.clone();
+                move |success: bool| {
+                    if notify_data(DataOpcode::RollBackCalibration(success), &conn).is_err() {
+                        defmt::error!("Response to RollBackCalibration failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::RollBackCalibration(
+                    index.into(),
+                    Some(result_cb),
+                ))
+                .is_err()
+            {
+                defmt::error!("Failed to send RollBackCalibration");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::CharacterizeHysteresis => {
+            let report_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<weight::HysteresisReport, weight::Rejected>| {
+                    let response = match result {
+                        Ok(report) => DataOpcode::Hysteresis(report),
+                        Err(rejected) => DataOpcode::Rejected(0xA7, rejected),
+                    };
+                    if notify_data(response, &conn).is_err() {
+                        defmt::error!("Response to CharacterizeHysteresis failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::CharacterizeHysteresis(Some(report_cb)))
+                .is_err()
+            {
+                defmt::error!("Failed to send CharacterizeHysteresis");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::LearnCreep(known_weight, minutes) => {
+            let result_cb = Box::new({
+                let conn = conn.clone();
+                move |result: Result<
+                    Result<&[weight::CreepParams], weight::CreepRejection>,
+                    weight::Rejected,
+                >| {
+                    let result = match result {
+                        Ok(Ok(params)) => {
+                            params.iter().enumerate().try_for_each(|(cell, params)| {
+                                notify_data(
+                                    DataOpcode::Creep {
+                                        cell: cell as u8,
+                                        result: Ok(*params),
+                                    },
+                                    &conn,
+                                )
+                            })
+                        }
+                        Ok(Err(rejection)) => notify_data(
+                            DataOpcode::Creep {
+                                cell: 0,
+                                result: Err(rejection),
+                            },
+                            &conn,
+                        ),
+                        Err(rejected) => notify_data(DataOpcode::Rejected(0xA8, rejected), &conn),
+                    };
+                    if result.is_err() {
+                        defmt::error!("Response to LearnCreep failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            let duration = minutes.map_or(weight::creep::DEFAULT_LEARNING_TIME, |minutes| {
+                Duration::from_secs(60 * u64::from(minutes))
+            });
+            if measure_ch
+                .try_send(weight::Command::LearnCreep(
+                    known_weight,
+                    duration,
+                    Some(result_cb),
+                ))
+                .is_err()
+            {
+                defmt::error!("Failed to send LearnCreep");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::ClearCreepCompensation => {
+            if measure_ch
+                .try_send(weight::Command::ClearCreepCompensation)
+                .is_err()
+            {
+                defmt::error!("Failed to send ClearCreepCompensation");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetUnits(code, gravity) => {
+            let Some(unit) = weight::Unit::from_code(code) else {
+                defmt::error!("Unknown unit: {=u8}", code);
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            };
+            let units = weight::Units {
+                unit,
+                gravity: gravity.unwrap_or(weight::units().gravity),
+            };
+            if measure_ch
+                .try_send(weight::Command::SetUnits(units))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetUnits");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetReferenceWeight(reference_weight) => {
+            let result_cb = Box::new({
+                let conn = conn.clone();
+                move |saved: bool| {
+                    if notify_data(DataOpcode::SetReferenceWeight(saved), &conn).is_err() {
+                        defmt::error!("Response to SetReferenceWeight failed");
+                        error_log::record(ErrorCode::NotifyFailed);
+                    }
+                }
+            });
+            if measure_ch
+                .try_send(weight::Command::SetReferenceWeight(
+                    reference_weight,
+                    Some(result_cb),
+                ))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetReferenceWeight");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetReconnectGrace(grace_s) => {
+            if measure_ch
+                .try_send(weight::Command::SetReconnectGrace(grace_s))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetReconnectGrace");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetOversampling(ratio) => {
+            if measure_ch
+                .try_send(weight::Command::SetOversampling(ratio))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetOversampling");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SetBroadcast(enabled, interval_ms) => {
+            if measure_ch
+                .try_send(weight::Command::SetBroadcast(enabled, interval_ms))
+                .is_err()
+            {
+                defmt::error!("Failed to send SetBroadcast");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::RestoreFactoryCalibration => {
+            if measure_ch
+                .try_send(weight::Command::RestoreFactoryCalibration)
+                .is_err()
+            {
+                defmt::error!("Failed to send RestoreFactoryCalibration");
+                error_log::record(ErrorCode::CommandQueueFull);
+            }
+        }
+        ControlOpcode::SelectCell(cell) => {
+            if measure_ch
+                .try_send(weight::Command::SelectCell(cell.into()))
+                .is_err()
+            {
+                defmt::error!("Failed to send SelectCell");
+                error_log::record(ErrorCode::CommandQueueFull);
             }
         }
         _ => (),
     }
 }
 
-/// Run gatt server until there is a disconnect
-pub(crate) async fn run(conn: &Connection, measure_ch: &MeasureChannel) {
+/// Run gatt server for `peer` until it disconnects
+pub(crate) async fn run(
+    conn: &Connection,
+    peer: PeerId,
+    measure_ch: &MeasureChannel,
+) -> Disconnect {
     let server = Server::get();
+    defmt::info!("Peer {=u32} connected", peer);
+    request_data_length_update(conn);
+    let subscriptions = Subscriptions::default();
+    #[cfg(feature = "ble-console")]
+    let console_input = ConsoleInput::new();
+    // The battery is only sampled at startup, so the level doesn't change while connected
+    let battery_level = battery_voltage::level_pct();
+    if server.battery.battery_level_set(&battery_level).is_err() {
+        defmt::error!("Failed to set battery level");
+    }
 
-    nrf_softdevice::ble::gatt_server::run(conn, server, |e| match e {
+    let serve = nrf_softdevice::ble::gatt_server::run(conn, server, |e| match e {
         ServerEvent::Progressor(e) => match e {
             ProgressorServiceEvent::ControlWrite(value) => {
                 if battery_voltage::is_low() {
                     defmt::error!("Low battery warning");
+                    error_log::record(ErrorCode::LowBattery);
                     if notify_data(DataOpcode::LowPowerWarning, conn).is_err() {
                         defmt::error!("Failed to notify low power warning");
+                        error_log::record(ErrorCode::NotifyFailed);
                     };
-                    if conn.disconnect().is_err() {
-                        defmt::error!("Failed to disconnect");
-                    }
+                    shut_down();
+                }
+                if value.is_query() || peers::take_control(peer) {
+                    on_control_message(value, conn, measure_ch);
+                } else {
+                    defmt::warn!("Peer {=u32} is only observing, ignoring {}", peer, value);
+                    error_log::record(ErrorCode::InvalidCommand);
                 }
-                on_control_message(value, conn, measure_ch);
             }
             ProgressorServiceEvent::DataCccdWrite { notifications } => {
                 defmt::debug!("DataCccdWrite: {}", notifications);
+                subscriptions.data.set(notifications);
+            }
+        },
+        ServerEvent::Hangman(e) => match e {
+            HangmanServiceEvent::CellsCccdWrite { notifications } => {
+                defmt::debug!("CellsCccdWrite: {}", notifications);
+                subscriptions.cells.set(notifications);
+            }
+            HangmanServiceEvent::StreamCccdWrite { notifications } => {
+                defmt::debug!("StreamCccdWrite: {}", notifications);
+                subscriptions.stream.set(notifications);
+            }
+        },
+        #[cfg(feature = "ble-console")]
+        ServerEvent::Uart(e) => match e {
+            NordicUartServiceEvent::RxWrite(data) => {
+                let data = data.as_bytes();
+                if !matches!(console_input.try_write(data), Ok(n) if n == data.len()) {
+                    defmt::warn!("Console input overflow");
+                }
+            }
+            NordicUartServiceEvent::TxCccdWrite { notifications } => {
+                defmt::debug!("TxCccdWrite: {}", notifications);
+                subscriptions.console.set(notifications);
+            }
+        },
+        // Read-only, so there are no events
+        ServerEvent::DeviceInformation(e) => match e {},
+        #[cfg(feature = "weight-scale")]
+        ServerEvent::WeightScale(e) => match e {
+            WeightScaleServiceEvent::WeightMeasurementCccdWrite { indications } => {
+                defmt::debug!("WeightMeasurementCccdWrite: {}", indications);
+                // Weighing takes over the measurement, so it's up to the controller
+                if !peers::take_control(peer) {
+                    defmt::warn!("Peer {=u32} is only observing, not weighing", peer);
+                } else if indications {
+                    start_weighing(conn, measure_ch);
+                } else if measure_ch.try_send(weight::Command::StopSampling).is_err() {
+                    defmt::error!("Failed to send StopSampling");
+                    error_log::record(ErrorCode::CommandQueueFull);
+                }
+            }
+        },
+        ServerEvent::Battery(e) => match e {
+            BatteryServiceEvent::BatteryLevelCccdWrite { notifications } => {
+                defmt::debug!("BatteryLevelCccdWrite: {}", notifications);
+                // Send the level right away, since it won't change later on
+                if notifications
+                    && server
+                        .battery
+                        .battery_level_notify(conn, &battery_level)
+                        .is_err()
+                {
+                    defmt::error!("Failed to notify battery level");
+                    error_log::record(ErrorCode::NotifyFailed);
+                }
             }
         },
-    })
-    .await;
+    });
+    let forward = forward_events(conn, &subscriptions);
+    #[cfg(feature = "ble-console")]
+    let forward = select(
+        forward,
+        console(conn, peer, &console_input, &subscriptions, measure_ch),
+    );
+    select(serve, forward).await;
+    defmt::info!("Peer {=u32} disconnected, {}", peer, peers::stats());
+    if peers::disconnect(peer) {
+        // Nobody else can stop the measurement
+        if measure_ch.try_send(weight::Command::StopSampling).is_err() {
+            defmt::error!("Failed to send StopSampling");
+            error_log::record(ErrorCode::CommandQueueFull);
+        }
+    }
+    if SHUTDOWN_REQUESTED.load(Ordering::Relaxed) {
+        Disconnect::Shutdown
+    } else {
+        Disconnect::Dropped
+    }
 }
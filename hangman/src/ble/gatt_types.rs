@@ -12,14 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::peers::QueueStats;
+use crate::error_log;
+use crate::nonvolatile::CalibrationRecord;
+use crate::weight::{
+    creep, noise, self_test, Branch, CalibrationPoint, CalibrationRejection, CreepParams,
+    CreepRejection, HysteresisReport, Rejected, MAX_CELLS,
+};
 use arrayvec::ArrayVec;
 use bytemuck_derive::{Pod, Zeroable};
 use defmt::Format;
+use hangman_utils::{control, stream};
 use nrf_softdevice::ble::GattValue;
 
 /// Sized to hold the largest possible data payload
-pub(crate) const DATA_PAYLOAD_SIZE: usize = 12;
-pub(crate) type CalibrationCurve = [u8; 12];
+pub(crate) const DATA_PAYLOAD_SIZE: usize = 28;
+/// Size of the calibration curve in the Progressor API. Only the size comes from the Progressor API,
+/// which doesn't document what's in it. See `DataOpcode::CalibrationCurve` for Hangman's layout.
+const CALIBRATION_CURVE_SIZE: usize = 12;
+/// Bytes of a calibration certificate per response, after the count, load cell, and offset
+pub(crate) const CERTIFICATE_CHUNK_SIZE: usize = DATA_PAYLOAD_SIZE - 3;
+/// Largest console write or notification. Fits a whole command line, without taking much room in
+/// the SoftDevice's attribute table.
+#[cfg(feature = "ble-console")]
+pub(crate) const CONSOLE_DATA_SIZE: usize = 64;
 
 /// Convert an integer into an array of bytes with any zeros on the MSB side trimmed
 fn to_le_bytes_without_trailing_zeros<T: Into<u64>>(input: T) -> ArrayVec<u8, 8> {
@@ -44,7 +60,61 @@ pub(crate) enum DataOpcode {
     LowPowerWarning,
     AppVersion(&'static [u8]),
     ProgressorId(u64),
-    CalibrationCurve(CalibrationCurve),
+    /// Calibration constants `m` and `b`
+    ///
+    /// Hangman-specific layout of the Progressor's opaque 12-byte calibration curve: `m` as an
+    /// `f32`, `b` as an `i32`, and 4 zero bytes. A Progressor's curve can't be read this way, nor
+    /// can this one be written to a Progressor.
+    CalibrationCurve(f32, i32),
+    /// One response per entry in the error log, or a single response without an entry if the log
+    /// is empty. Each response starts with the result of the most recent self-test.
+    ErrorInfo {
+        fault: Option<self_test::Fault>,
+        index: u8,
+        count: u8,
+        entry: Option<error_log::Entry>,
+    },
+    /// Response to `AddCalibrationPoint`: `None` if the weight didn't settle in time
+    CalibrationPoint(Option<CalibrationPoint>),
+    /// Response to `SaveCalibration`: `None` if the calibration was saved
+    SaveCalibration(Option<CalibrationRejection>),
+    /// One response per entry in the calibration history, or a single response without a record if
+    /// the history is empty
+    CalibrationHistoryEntry {
+        index: u8,
+        count: u8,
+        record: Option<CalibrationRecord>,
+    },
+    /// Response to `RollBackCalibration`: whether the requested entry was restored
+    RollBackCalibration(bool),
+    /// Response to `SetReferenceWeight`: whether the weight was in range and saved
+    SetReferenceWeight(bool),
+    SelfTest(self_test::Report),
+    /// First response to a noise characterization
+    NoiseSummary(noise::Report),
+    /// Second response to a noise characterization
+    AllanDeviation([f32; noise::ALLAN_TAUS.len()]),
+    Hysteresis(HysteresisReport),
+    /// One response per load cell with its learned creep parameters, or a single response if
+    /// learning failed
+    Creep {
+        cell: u8,
+        result: Result<CreepParams, CreepRejection>,
+    },
+    /// Part of the encoded calibration certificate of a load cell, starting at `offset`, along
+    /// with the number of certificates being sent. If there are none, a single response with just
+    /// the count is sent.
+    CertificateChunk {
+        count: u8,
+        cell: u8,
+        offset: u8,
+        len: u8,
+        data: [u8; CERTIFICATE_CHUNK_SIZE],
+    },
+    NotificationStats(QueueStats),
+    /// Response to a control opcode that was rejected without running, in place of its usual
+    /// response
+    Rejected(u8, Rejected),
 }
 
 impl DataOpcode {
@@ -53,9 +123,24 @@ impl DataOpcode {
             DataOpcode::BatteryVoltage(..)
             | DataOpcode::AppVersion(..)
             | DataOpcode::ProgressorId(..)
-            | DataOpcode::CalibrationCurve(..) => 0x00,
+            | DataOpcode::CalibrationCurve(..)
+            | DataOpcode::ErrorInfo { .. }
+            | DataOpcode::CalibrationPoint(..)
+            | DataOpcode::SaveCalibration(..)
+            | DataOpcode::CalibrationHistoryEntry { .. }
+            | DataOpcode::RollBackCalibration(..)
+            | DataOpcode::SetReferenceWeight(..)
+            | DataOpcode::SelfTest(..)
+            | DataOpcode::NoiseSummary(..)
+            | DataOpcode::AllanDeviation(..)
+            | DataOpcode::Hysteresis(..)
+            | DataOpcode::Creep { .. }
+            | DataOpcode::CertificateChunk { .. }
+            | DataOpcode::NotificationStats(..) => 0x00,
             DataOpcode::Weight(..) => 0x01,
             DataOpcode::LowPowerWarning => 0x04,
+            // Hangman extension to the Progressor API
+            DataOpcode::Rejected(..) => 0x80,
         }
     }
 
@@ -66,7 +151,33 @@ impl DataOpcode {
             DataOpcode::ProgressorId(id) => to_le_bytes_without_trailing_zeros(*id).len() as u8,
             DataOpcode::LowPowerWarning => 0,
             DataOpcode::AppVersion(version) => version.len() as u8,
-            DataOpcode::CalibrationCurve(curve) => curve.len() as u8,
+            DataOpcode::CalibrationCurve(..) => CALIBRATION_CURVE_SIZE as u8,
+            DataOpcode::ErrorInfo { entry, .. } => match entry {
+                Some(_) => 16,
+                None => 3,
+            },
+            DataOpcode::SaveCalibration(..)
+            | DataOpcode::RollBackCalibration(..)
+            | DataOpcode::SetReferenceWeight(..) => 1,
+            DataOpcode::CalibrationPoint(..) => 17,
+            DataOpcode::CalibrationHistoryEntry { record, .. } => match record {
+                Some(_) => 28,
+                None => 2,
+            },
+            DataOpcode::SelfTest(..) => 9,
+            DataOpcode::NoiseSummary(..) => 24,
+            DataOpcode::AllanDeviation(deviations) => (4 * deviations.len()) as u8,
+            DataOpcode::Hysteresis(..) => 16,
+            DataOpcode::Creep { result, .. } => match result {
+                Ok(_) => 10,
+                Err(_) => 1,
+            },
+            DataOpcode::CertificateChunk { count, len, .. } => match count {
+                0 => 1,
+                _ => 3 + len,
+            },
+            DataOpcode::NotificationStats(..) => 16,
+            DataOpcode::Rejected(..) => 2,
         }
     }
 
@@ -88,7 +199,115 @@ impl DataOpcode {
             DataOpcode::AppVersion(version) => {
                 value[0..version.len()].copy_from_slice(version);
             }
-            DataOpcode::CalibrationCurve(curve) => value = *curve,
+            DataOpcode::CalibrationCurve(m, b) => {
+                // The remaining bytes are reserved and left as zero
+                value[0..4].copy_from_slice(&m.to_le_bytes());
+                value[4..8].copy_from_slice(&b.to_le_bytes());
+            }
+            DataOpcode::ErrorInfo {
+                fault,
+                index,
+                count,
+                entry,
+            } => {
+                value[0] = fault.map_or(0, self_test::Fault::code);
+                value[1] = *index;
+                value[2] = *count;
+                if let Some(entry) = entry {
+                    value[3] = entry.code;
+                    value[4..8].copy_from_slice(&entry.count.to_le_bytes());
+                    value[8..12].copy_from_slice(&entry.boot_count.to_le_bytes());
+                    value[12..16].copy_from_slice(&entry.uptime_s.to_le_bytes());
+                }
+            }
+            DataOpcode::CalibrationPoint(point) => match point {
+                Some(point) => {
+                    value[1..5].copy_from_slice(&point.reading.to_le_bytes());
+                    value[5..9].copy_from_slice(&point.std_dev.to_le_bytes());
+                    value[9..13].copy_from_slice(&point.std_dev_kg.to_le_bytes());
+                    value[13..17].copy_from_slice(&point.n_samples.to_le_bytes());
+                }
+                // Timed out
+                None => value[0] = 1,
+            },
+            DataOpcode::SaveCalibration(rejection) => {
+                value[0] = rejection.map_or(0, CalibrationRejection::code);
+            }
+            DataOpcode::CalibrationHistoryEntry {
+                index,
+                count,
+                record,
+            } => {
+                value[0] = *index;
+                value[1] = *count;
+                if let Some(record) = record {
+                    value[2] = record.cell;
+                    value[3] = record.source().map_or(0, |source| source as u8);
+                    value[4..8].copy_from_slice(&{ record.boot_count }.to_le_bytes());
+                    value[8..12].copy_from_slice(&{ record.m }.to_le_bytes());
+                    value[12..16].copy_from_slice(&{ record.b }.to_le_bytes());
+                    value[16..20].copy_from_slice(&{ record.zero_reading }.to_le_bytes());
+                    value[20..24].copy_from_slice(&{ record.reference_reading }.to_le_bytes());
+                    value[24..28].copy_from_slice(&{ record.reference_weight }.to_le_bytes());
+                }
+            }
+            DataOpcode::RollBackCalibration(success) | DataOpcode::SetReferenceWeight(success) => {
+                value[0] = u8::from(!success);
+            }
+            DataOpcode::SelfTest(report) => {
+                value[0] = report.fault.map_or(0, self_test::Fault::code);
+                value[1..5].copy_from_slice(&report.mean.to_le_bytes());
+                value[5..9].copy_from_slice(&report.peak_to_peak.to_le_bytes());
+            }
+            DataOpcode::NoiseSummary(report) => {
+                value[0..4].copy_from_slice(&report.n_samples.to_le_bytes());
+                value[4..8].copy_from_slice(&report.mean.to_le_bytes());
+                value[8..12].copy_from_slice(&report.rms.to_le_bytes());
+                value[12..16].copy_from_slice(&report.peak_to_peak.to_le_bytes());
+                value[16..20].copy_from_slice(&report.effective_bits.to_le_bytes());
+                value[20..24].copy_from_slice(&report.noise_free_bits.to_le_bytes());
+            }
+            DataOpcode::AllanDeviation(deviations) => {
+                for (chunk, deviation) in value.chunks_exact_mut(4).zip(deviations) {
+                    chunk.copy_from_slice(&deviation.to_le_bytes());
+                }
+            }
+            DataOpcode::Hysteresis(report) => {
+                value[0..4].copy_from_slice(&report.zero_error.to_le_bytes());
+                value[4..8].copy_from_slice(&report.reference_error.to_le_bytes());
+                value[8..12].copy_from_slice(&report.reference.to_le_bytes());
+                value[12..16].copy_from_slice(&report.reference_error_pct().to_le_bytes());
+            }
+            DataOpcode::Creep { cell, result } => match result {
+                Ok(params) => {
+                    value[1] = *cell;
+                    value[2..6].copy_from_slice(&params.magnitude.to_le_bytes());
+                    value[6..10].copy_from_slice(&params.tau_s.to_le_bytes());
+                }
+                Err(rejection) => value[0] = rejection.code(),
+            },
+            DataOpcode::CertificateChunk {
+                count,
+                cell,
+                offset,
+                len,
+                data,
+            } => {
+                value[0] = *count;
+                value[1] = *cell;
+                value[2] = *offset;
+                value[3..3 + usize::from(*len)].copy_from_slice(&data[..usize::from(*len)]);
+            }
+            DataOpcode::NotificationStats(stats) => {
+                value[0..4].copy_from_slice(&stats.sent.to_le_bytes());
+                value[4..8].copy_from_slice(&stats.retries.to_le_bytes());
+                value[8..12].copy_from_slice(&stats.dropped.to_le_bytes());
+                value[12..16].copy_from_slice(&stats.failed.to_le_bytes());
+            }
+            DataOpcode::Rejected(opcode, rejected) => {
+                value[0] = *opcode;
+                value[1] = rejected.code();
+            }
         };
         value
     }
@@ -141,6 +360,117 @@ impl GattValue for DataPoint {
     }
 }
 
+/// Sized to hold a timestamp plus one weight per load cell
+const CELLS_POINT_MAX_SIZE: usize = 4 + 4 * MAX_CELLS;
+
+/// Tared weight of each load cell
+///
+/// Format: time since the start of the measurement in microseconds as a `u32`, followed by the
+/// weight of each load cell as an `f32`. All values are little-endian.
+#[derive(Copy, Clone)]
+pub(crate) struct CellsPoint {
+    length: u8,
+    value: [u8; CELLS_POINT_MAX_SIZE],
+}
+
+impl CellsPoint {
+    pub(crate) fn new(timestamp_us: u32, weights: &[f32]) -> Self {
+        assert!(weights.len() <= MAX_CELLS);
+        let mut value = [0; CELLS_POINT_MAX_SIZE];
+        value[0..4].copy_from_slice(&timestamp_us.to_le_bytes());
+        for (chunk, weight) in value[4..].chunks_exact_mut(4).zip(weights) {
+            chunk.copy_from_slice(&weight.to_le_bytes());
+        }
+        Self {
+            length: (4 + 4 * weights.len()) as u8,
+            value,
+        }
+    }
+}
+
+impl GattValue for CellsPoint {
+    /// Minimum = timestamp and one weight
+    const MIN_SIZE: usize = 8;
+    const MAX_SIZE: usize = CELLS_POINT_MAX_SIZE;
+
+    fn from_gatt(_data: &[u8]) -> Self {
+        unimplemented!("CellsPoint is only used for outgoing data");
+    }
+
+    fn to_gatt(&self) -> &[u8] {
+        &self.value[..self.length.into()]
+    }
+}
+
+/// Several timestamped weights, in the format of `hangman_utils::stream`
+pub(crate) struct StreamPacket {
+    length: u8,
+    value: [u8; stream::MAX_PACKET_SIZE],
+}
+
+impl StreamPacket {
+    pub(crate) fn new(packet: &[u8]) -> Self {
+        let mut value = [0; stream::MAX_PACKET_SIZE];
+        value[..packet.len()].copy_from_slice(packet);
+        Self {
+            length: packet.len() as u8,
+            value,
+        }
+    }
+}
+
+impl GattValue for StreamPacket {
+    /// Minimum = header and one sample
+    const MIN_SIZE: usize = stream::HEADER_SIZE + stream::SAMPLE_SIZE;
+    const MAX_SIZE: usize = stream::MAX_PACKET_SIZE;
+
+    fn from_gatt(_data: &[u8]) -> Self {
+        unimplemented!("StreamPacket is only used for outgoing data");
+    }
+
+    fn to_gatt(&self) -> &[u8] {
+        &self.value[..self.length.into()]
+    }
+}
+
+/// Text to or from the console over the Nordic UART Service. Longer text is split over several
+/// writes or notifications.
+#[cfg(feature = "ble-console")]
+pub(crate) struct ConsoleData {
+    length: u8,
+    value: [u8; CONSOLE_DATA_SIZE],
+}
+
+#[cfg(feature = "ble-console")]
+impl ConsoleData {
+    pub(crate) fn new(data: &[u8]) -> Self {
+        let mut value = [0; CONSOLE_DATA_SIZE];
+        value[..data.len()].copy_from_slice(data);
+        Self {
+            length: data.len() as u8,
+            value,
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.value[..self.length.into()]
+    }
+}
+
+#[cfg(feature = "ble-console")]
+impl GattValue for ConsoleData {
+    const MIN_SIZE: usize = 0;
+    const MAX_SIZE: usize = CONSOLE_DATA_SIZE;
+
+    fn from_gatt(data: &[u8]) -> Self {
+        Self::new(&data[..data.len().min(CONSOLE_DATA_SIZE)])
+    }
+
+    fn to_gatt(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
 #[derive(Copy, Clone)]
 pub(crate) enum ControlOpcode {
     Tare,
@@ -148,15 +478,58 @@ pub(crate) enum ControlOpcode {
     StopMeasurement,
     StartPeakRfdMeasurement,
     StartPeakRfdMeasurementSeries,
-    AddCalibrationPoint(f32),
+    /// Known weight and whether it was approached from below or, for the Hangman-specific opcode,
+    /// from above
+    AddCalibrationPoint(f32, Branch),
     SaveCalibration,
     GetCalibrationCurve,
+    /// Calibration constants `m` and `b`, in the same Hangman-specific format as the response to
+    /// `GetCalibrationCurve`
+    SetCalibrationCurve(f32, i32),
     GetAppVersion,
     GetErrorInfo,
     ClearErrorInfo,
     Shutdown,
     SampleBattery,
     GetProgressorID,
+    /// Hangman-specific: run the load cell self-test
+    RunSelfTest,
+    /// Hangman-specific: select the load cell to calibrate
+    SelectCell(u8),
+    /// Hangman-specific: characterize ADC noise over the given number of samples, or a default
+    /// number if `None`
+    CharacterizeNoise(Option<u32>),
+    /// Hangman-specific: list previous calibrations
+    GetCalibrationHistory,
+    /// Hangman-specific: restore an entry of the calibration history. 0 is the most recent.
+    RollBackCalibration(u8),
+    /// Hangman-specific: restore the default calibration of the selected load cell
+    RestoreFactoryCalibration,
+    /// Hangman-specific: compare the unloading calibration points against the loading calibration
+    CharacterizeHysteresis,
+    /// Hangman-specific: learn creep from a known weight in kg, hung for the given number of
+    /// minutes or a default time if `None`
+    LearnCreep(f32, Option<u8>),
+    /// Hangman-specific: disable creep compensation
+    ClearCreepCompensation,
+    /// Hangman-specific: set the reference weight in kg for the on-device calibration mode
+    SetReferenceWeight(f32),
+    /// Hangman-specific: set the unit code of outputs that aren't part of the Progressor API, and
+    /// optionally the local gravity in m/s²
+    SetUnits(u8, Option<f32>),
+    /// Hangman-specific: export the calibration certificates of all load cells
+    GetCertificates,
+    /// Hangman-specific: select whether to broadcast the weight at boot instead of waiting for a
+    /// connection, and optionally the broadcast update interval in ms
+    SetBroadcast(bool, Option<u16>),
+    /// Hangman-specific: set how long to advertise for the peer to reconnect after a disconnect,
+    /// in seconds
+    SetReconnectGrace(u16),
+    /// Hangman-specific: get the counters of notifications sent, retried, dropped and failed
+    GetNotificationStats,
+    /// Hangman-specific: set the number of ADC conversions summed into each reading, or go back to
+    /// the board's default if `None`
+    SetOversampling(Option<u8>),
     Unknown(u8),
     Invalid,
 }
@@ -165,6 +538,21 @@ impl ControlOpcode {
     pub(crate) fn is_known_opcode(&self) -> bool {
         !matches!(self, Self::Unknown(_) | Self::Invalid)
     }
+
+    /// Whether the message only asks for information, so that observers may send it too
+    pub(crate) fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Self::GetCalibrationCurve
+                | Self::GetAppVersion
+                | Self::GetErrorInfo
+                | Self::SampleBattery
+                | Self::GetProgressorID
+                | Self::GetCalibrationHistory
+                | Self::GetCertificates
+                | Self::GetNotificationStats
+        )
+    }
 }
 
 impl Format for ControlOpcode {
@@ -177,26 +565,128 @@ impl Format for ControlOpcode {
             ControlOpcode::StartPeakRfdMeasurementSeries => {
                 defmt::write!(fmt, "StartPeakRfdMeasurementSeries");
             }
-            ControlOpcode::AddCalibrationPoint(val) => {
-                defmt::write!(fmt, "AddCalibrationPoint {=f32}", val);
+            ControlOpcode::AddCalibrationPoint(val, branch) => {
+                defmt::write!(fmt, "AddCalibrationPoint {=f32} ({})", val, branch);
             }
             ControlOpcode::SaveCalibration => defmt::write!(fmt, "SaveCalibration"),
             ControlOpcode::GetCalibrationCurve => defmt::write!(fmt, "GetCalibrationCurve"),
+            ControlOpcode::SetCalibrationCurve(m, b) => {
+                defmt::write!(fmt, "SetCalibrationCurve m = {=f32} b = {=i32}", m, b);
+            }
             ControlOpcode::GetAppVersion => defmt::write!(fmt, "GetAppVersion"),
             ControlOpcode::GetErrorInfo => defmt::write!(fmt, "GetErrorInfo"),
             ControlOpcode::ClearErrorInfo => defmt::write!(fmt, "ClearErrorInfo"),
             ControlOpcode::Shutdown => defmt::write!(fmt, "Shutdown"),
             ControlOpcode::SampleBattery => defmt::write!(fmt, "SampleBattery"),
             ControlOpcode::GetProgressorID => defmt::write!(fmt, "GetProgressorID"),
+            ControlOpcode::RunSelfTest => defmt::write!(fmt, "RunSelfTest"),
+            ControlOpcode::SelectCell(cell) => defmt::write!(fmt, "SelectCell {=u8}", cell),
+            ControlOpcode::CharacterizeNoise(n_samples) => {
+                defmt::write!(fmt, "CharacterizeNoise {}", n_samples);
+            }
+            ControlOpcode::GetCalibrationHistory => defmt::write!(fmt, "GetCalibrationHistory"),
+            ControlOpcode::RollBackCalibration(index) => {
+                defmt::write!(fmt, "RollBackCalibration {=u8}", index);
+            }
+            ControlOpcode::RestoreFactoryCalibration => {
+                defmt::write!(fmt, "RestoreFactoryCalibration");
+            }
+            ControlOpcode::CharacterizeHysteresis => defmt::write!(fmt, "CharacterizeHysteresis"),
+            ControlOpcode::LearnCreep(weight, minutes) => {
+                defmt::write!(fmt, "LearnCreep {=f32} {}", weight, minutes);
+            }
+            ControlOpcode::ClearCreepCompensation => defmt::write!(fmt, "ClearCreepCompensation"),
+            ControlOpcode::SetReferenceWeight(weight) => {
+                defmt::write!(fmt, "SetReferenceWeight {=f32}", weight);
+            }
+            ControlOpcode::SetUnits(unit, gravity) => {
+                defmt::write!(fmt, "SetUnits {=u8} {}", unit, gravity);
+            }
+            ControlOpcode::GetCertificates => defmt::write!(fmt, "GetCertificates"),
+            ControlOpcode::SetBroadcast(enabled, interval_ms) => {
+                defmt::write!(fmt, "SetBroadcast {=bool} {}", enabled, interval_ms);
+            }
+            ControlOpcode::SetReconnectGrace(grace_s) => {
+                defmt::write!(fmt, "SetReconnectGrace {=u16}", grace_s);
+            }
+            ControlOpcode::GetNotificationStats => defmt::write!(fmt, "GetNotificationStats"),
+            ControlOpcode::SetOversampling(ratio) => {
+                defmt::write!(fmt, "SetOversampling {}", ratio)
+            }
             ControlOpcode::Unknown(opcode) => defmt::write!(fmt, "Unknown (0x{=u8:X})", opcode),
             ControlOpcode::Invalid => defmt::write!(fmt, "Invalid"),
         }
     }
 }
 
+impl ControlOpcode {
+    /// Payload of a message, after the opcode and the optional length byte, or `None` if its length
+    /// isn't one of `lengths`. See `hangman_utils::control::payload`.
+    fn parse_payload<'a>(data: &'a [u8], lengths: &[usize]) -> Option<&'a [u8]> {
+        let payload = control::payload(data, lengths);
+        if payload.is_none() {
+            defmt::error!("Invalid payload {=[u8]:X}", data);
+        }
+        payload
+    }
+
+    fn parse_calibration_point(data: &[u8], branch: Branch) -> Self {
+        // Allow length to be omitted
+        let float_bytes = match data.len() {
+            5 => &data[1..5],
+            6 => &data[2..6],
+            _ => {
+                defmt::error!("Invalid payload {=[u8]:X}", data);
+                return Self::Invalid;
+            }
+        };
+        Self::AddCalibrationPoint(f32::from_le_bytes(float_bytes.try_into().unwrap()), branch)
+    }
+}
+
+/// Longest string in the Device Information Service
+const DEVICE_STRING_MAX_SIZE: usize = 32;
+
+/// UTF-8 string characteristic of the Device Information Service, truncated to
+/// `DEVICE_STRING_MAX_SIZE` bytes
+#[derive(Copy, Clone)]
+pub(crate) struct DeviceString {
+    length: u8,
+    value: [u8; DEVICE_STRING_MAX_SIZE],
+}
+
+impl DeviceString {
+    pub(crate) fn new(string: &str) -> Self {
+        let mut length = string.len().min(DEVICE_STRING_MAX_SIZE);
+        while !string.is_char_boundary(length) {
+            length -= 1;
+        }
+        let mut value = [0; DEVICE_STRING_MAX_SIZE];
+        value[..length].copy_from_slice(&string.as_bytes()[..length]);
+        Self {
+            length: length as u8,
+            value,
+        }
+    }
+}
+
+impl GattValue for DeviceString {
+    const MIN_SIZE: usize = 0;
+    const MAX_SIZE: usize = DEVICE_STRING_MAX_SIZE;
+
+    fn from_gatt(_data: &[u8]) -> Self {
+        unimplemented!("DeviceString is only used for outgoing data");
+    }
+
+    fn to_gatt(&self) -> &[u8] {
+        &self.value[..self.length.into()]
+    }
+}
+
 impl GattValue for ControlOpcode {
     const MIN_SIZE: usize = 1;
-    const MAX_SIZE: usize = 6;
+    /// Opcode, length, and calibration curve
+    const MAX_SIZE: usize = 2 + CALIBRATION_CURVE_SIZE;
 
     fn from_gatt(data: &[u8]) -> Self {
         if data.len() < Self::MIN_SIZE || data.len() > Self::MAX_SIZE {
@@ -210,18 +700,7 @@ impl GattValue for ControlOpcode {
             0x66 => Self::StopMeasurement,
             0x67 => Self::StartPeakRfdMeasurement,
             0x68 => Self::StartPeakRfdMeasurementSeries,
-            0x69 => {
-                // Allow length to be omitted
-                let float_bytes = match data.len() {
-                    5 => &data[1..5],
-                    6 => &data[2..6],
-                    _ => {
-                        defmt::error!("Invalid payload {=[u8]:X}", data);
-                        return Self::Invalid;
-                    }
-                };
-                Self::AddCalibrationPoint(f32::from_le_bytes(float_bytes.try_into().unwrap()))
-            }
+            0x69 => Self::parse_calibration_point(data, Branch::Loading),
             0x6A => Self::SaveCalibration,
             0x6B => Self::GetAppVersion,
             0x6C => Self::GetErrorInfo,
@@ -229,7 +708,98 @@ impl GattValue for ControlOpcode {
             0x6E => Self::Shutdown,
             0x6F => Self::SampleBattery,
             0x70 => Self::GetProgressorID,
+            0x71 => {
+                // Allow length to be omitted
+                let curve = match data.len() {
+                    13 => &data[1..13],
+                    14 => &data[2..14],
+                    _ => {
+                        defmt::error!("Invalid payload {=[u8]:X}", data);
+                        return Self::Invalid;
+                    }
+                };
+                Self::SetCalibrationCurve(
+                    f32::from_le_bytes(curve[0..4].try_into().unwrap()),
+                    i32::from_le_bytes(curve[4..8].try_into().unwrap()),
+                )
+            }
             0x72 => Self::GetCalibrationCurve,
+            // Opcodes from 0xA0 onwards are Hangman extensions to the Progressor API
+            0xA0 => Self::RunSelfTest,
+            0xA1 => match Self::parse_payload(data, control::SELECT_CELL) {
+                Some([cell]) => Self::SelectCell(*cell),
+                _ => Self::Invalid,
+            },
+            0xA2 => match Self::parse_payload(data, control::CHARACTERIZE_NOISE) {
+                Some([]) => Self::CharacterizeNoise(None),
+                Some(payload) => {
+                    let n_samples = u32::from_le_bytes(payload.try_into().unwrap());
+                    if n_samples == 0 || n_samples as usize > noise::max_n_samples() {
+                        defmt::error!("Number of noise samples out of range: {=u32}", n_samples);
+                        return Self::Invalid;
+                    }
+                    Self::CharacterizeNoise(Some(n_samples))
+                }
+                None => Self::Invalid,
+            },
+            0xA3 => Self::GetCalibrationHistory,
+            0xA4 => match Self::parse_payload(data, control::ROLL_BACK_CALIBRATION) {
+                Some([index]) => Self::RollBackCalibration(*index),
+                _ => Self::Invalid,
+            },
+            0xA5 => Self::RestoreFactoryCalibration,
+            0xA6 => Self::parse_calibration_point(data, Branch::Unloading),
+            0xA7 => Self::CharacterizeHysteresis,
+            0xA8 => {
+                let Some(payload) = Self::parse_payload(data, control::LEARN_CREEP) else {
+                    return Self::Invalid;
+                };
+                let minutes = payload.get(4).copied();
+                if let Some(minutes) =
+                    minutes.filter(|m| !(1..=creep::MAX_LEARNING_MINUTES).contains(m))
+                {
+                    defmt::error!("Creep learning time out of range: {=u8} min", minutes);
+                    return Self::Invalid;
+                }
+                Self::LearnCreep(
+                    f32::from_le_bytes(payload[..4].try_into().unwrap()),
+                    minutes,
+                )
+            }
+            0xA9 => Self::ClearCreepCompensation,
+            0xAA => match Self::parse_payload(data, control::SET_REFERENCE_WEIGHT) {
+                Some(payload) => {
+                    Self::SetReferenceWeight(f32::from_le_bytes(payload.try_into().unwrap()))
+                }
+                None => Self::Invalid,
+            },
+            0xAB => match Self::parse_payload(data, control::SET_UNITS) {
+                Some([unit]) => Self::SetUnits(*unit, None),
+                Some([unit, gravity @ ..]) => {
+                    Self::SetUnits(*unit, Some(f32::from_le_bytes(gravity.try_into().unwrap())))
+                }
+                _ => Self::Invalid,
+            },
+            0xAC => Self::GetCertificates,
+            0xAD => match Self::parse_payload(data, control::SET_BROADCAST) {
+                Some([enabled]) => Self::SetBroadcast(*enabled != 0, None),
+                Some([enabled, interval @ ..]) => Self::SetBroadcast(
+                    *enabled != 0,
+                    Some(u16::from_le_bytes(interval.try_into().unwrap())),
+                ),
+                _ => Self::Invalid,
+            },
+            0xAE => match Self::parse_payload(data, control::SET_RECONNECT_GRACE) {
+                Some(payload) => {
+                    Self::SetReconnectGrace(u16::from_le_bytes(payload.try_into().unwrap()))
+                }
+                None => Self::Invalid,
+            },
+            0xAF => Self::GetNotificationStats,
+            0xB0 => match Self::parse_payload(data, control::SET_OVERSAMPLING) {
+                Some([ratio]) => Self::SetOversampling((*ratio != 0).then_some(*ratio)),
+                _ => Self::Invalid,
+            },
             _ => Self::Unknown(opcode),
         }
     }
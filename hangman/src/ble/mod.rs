@@ -13,8 +13,10 @@
 // limitations under the License.
 
 mod advertising;
+pub mod broadcast;
 mod gatt_server;
 mod gatt_types;
+mod peers;
 mod task;
 
 use nrf_softdevice::Softdevice;
@@ -30,6 +32,28 @@ type MeasureChannel = Sender<'static, NoopRawMutex, weight::Command, MEASURE_COM
 /// something of this form.
 const ADVERTISED_NAME: &[u8] = env!("ADVERTISED_NAME").as_bytes();
 
+/// Maximum number of peers connected at once
+const MAX_CONNECTIONS: usize = 2;
+
+/// Largest ATT MTU, fitting the largest streaming packet plus the 3 byte ATT header
+const ATT_MTU: u16 = hangman_utils::stream::MAX_PACKET_SIZE as u16 + 3;
+
+/// Size of the SoftDevice's GATT attribute table, which also holds the characteristic values.
+///
+/// Budgeted for the largest feature set (`weight-scale` and `ble-console`): about 640 bytes of
+/// values (the 244 byte streaming packet, 6 x 32 byte device information strings, 2 x 64 byte
+/// console buffers, progressor, WSS and battery values), about 160 bytes of service and
+/// characteristic declarations and ~16 bytes of bookkeeping for each of the ~45 attributes, plus
+/// some headroom. Must be a multiple of 4. Changing this changes the RAM start in `memory.x`.
+const ATTR_TAB_SIZE: u32 = 2048;
+
+/// Board-specific fields of the Device Information Service. The rest are common to all boards.
+pub struct DeviceInfo {
+    /// Model number, e.g. the name of the binary
+    pub model: &'static str,
+    pub hardware_revision: &'static str,
+}
+
 fn softdevice_config() -> nrf_softdevice::Config {
     use nrf_softdevice::raw;
     let advertised_name_len: u16 = ADVERTISED_NAME.len() as u16;
@@ -41,22 +65,21 @@ fn softdevice_config() -> nrf_softdevice::Config {
             accuracy: raw::NRF_CLOCK_LF_ACCURACY_500_PPM as u8,
         }),
         conn_gap: Some(raw::ble_gap_conn_cfg_t {
-            conn_count: 1,
-            event_length: raw::BLE_GAP_EVENT_LENGTH_DEFAULT as u16,
+            conn_count: MAX_CONNECTIONS as u8,
+            // Long enough for a data length extended packet in each direction
+            event_length: 6,
         }),
         conn_gatt: Some(raw::ble_gatt_conn_cfg_t {
-            // Set to something small-ish since individual GATT transactions are small (guessing
-            // ~10 bytes). Might want to bump this up if we add DFU support. Don't really know what
-            // I'm doing here.
-            att_mtu: 48,
+            // Large enough for a packet of the streaming characteristic to fit into a single
+            // notification. Peers that ask for less get less.
+            att_mtu: ATT_MTU,
         }),
         gatts_attr_tab_size: Some(raw::ble_gatts_cfg_attr_tab_size_t {
-            // Using default value of BLE_GATTS_ATTR_TAB_SIZE_DEFAULT
-            attr_tab_size: 1408,
+            attr_tab_size: ATTR_TAB_SIZE,
         }),
         gap_role_count: Some(raw::ble_gap_cfg_role_count_t {
             adv_set_count: 1,
-            periph_role_count: 1,
+            periph_role_count: MAX_CONNECTIONS as u8,
         }),
         gap_device_name: Some(raw::ble_gap_cfg_device_name_t {
             p_value: ADVERTISED_NAME.as_ptr().cast_mut(),
@@ -76,8 +99,8 @@ fn softdevice_config() -> nrf_softdevice::Config {
 /// To keep the Softdevice machinery happy, the returned Softdevice should be "run" (e.g. via
 /// `run`, `run_with_callback`, etc.) on its own task and given a chance to run as early before
 /// running any other initialization code.
-pub fn init_softdevice() -> &'static mut Softdevice {
+pub fn init_softdevice(device_info: DeviceInfo) -> &'static mut Softdevice {
     let sd = Softdevice::enable(&softdevice_config());
-    gatt_server::init(sd).unwrap();
+    gatt_server::init(sd, &device_info).unwrap();
     sd
 }
@@ -0,0 +1,159 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bookkeeping of connected peers
+//!
+//! Up to `MAX_CONNECTIONS` peers can be connected at once, e.g. an athlete's phone and a coach's
+//! tablet. All of them receive the measurements, but only one of them, the controller, can send
+//! commands that change the state of the scale. The others are observers. The first peer to connect
+//! is the controller. Once it disconnects, the next peer to send a command takes over.
+//!
+//! Events are queued for each peer, so that a peer whose radio link stalls briefly, e.g. while the
+//! SoftDevice has no free TX buffers, catches up without missing samples. If the queue overflows,
+//! the oldest events are dropped. Counters of what happened to notifications are kept for
+//! diagnosing flaky links.
+
+use super::MAX_CONNECTIONS;
+use crate::weight;
+use arrayvec::ArrayVec;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use defmt::Format;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber, WaitResult};
+
+pub(crate) type PeerId = u32;
+
+const NO_CONTROLLER: PeerId = 0;
+/// Number of events buffered for each peer, 400 ms of samples at the highest (80 Hz) sample rate.
+/// That outlasts a notification stuck retrying for `gatt_server::NOTIFY_RETRY_TIMEOUT` (20 samples)
+/// with some room to spare, so one slow notification doesn't drop samples.
+const EVENT_QUEUE_LEN: usize = 32;
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(NO_CONTROLLER + 1);
+static CONTROLLER: AtomicU32 = AtomicU32::new(NO_CONTROLLER);
+static CONNECTED: AtomicUsize = AtomicUsize::new(0);
+static EVENTS: PubSubChannel<CriticalSectionRawMutex, Event, EVENT_QUEUE_LEN, MAX_CONNECTIONS, 0> =
+    PubSubChannel::new();
+
+static SENT: AtomicU32 = AtomicU32::new(0);
+static RETRIES: AtomicU32 = AtomicU32::new(0);
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+static FAILED: AtomicU32 = AtomicU32::new(0);
+
+/// Counters of notifications since boot, summed over all peers
+#[derive(Copy, Clone, Format)]
+pub(crate) struct QueueStats {
+    /// Notifications sent
+    pub sent: u32,
+    /// Attempts to send a notification while the SoftDevice had no free TX buffers
+    pub retries: u32,
+    /// Events dropped because a peer's queue overflowed
+    pub dropped: u32,
+    /// Notifications given up on, after retrying or because of another error
+    pub failed: u32,
+}
+
+/// Event sent to every connected peer
+#[derive(Clone)]
+pub(crate) enum Event {
+    /// Tared weight in kg, followed by the tared weight of each load cell
+    Weight {
+        timestamp_us: u32,
+        weight: f32,
+        cells: ArrayVec<f32, { weight::MAX_CELLS }>,
+    },
+    /// Disconnect to go to sleep
+    Shutdown,
+}
+
+pub(crate) type EventSubscriber =
+    Subscriber<'static, CriticalSectionRawMutex, Event, EVENT_QUEUE_LEN, MAX_CONNECTIONS, 0>;
+
+/// Register a newly connected peer, which becomes the controller if there is none. Must be
+/// called before advertising for the next one.
+pub(crate) fn connect() -> PeerId {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    CONNECTED.fetch_add(1, Ordering::Relaxed);
+    take_control(id);
+    id
+}
+
+/// Unregister a disconnected peer. Returns whether it was the controller.
+pub(crate) fn disconnect(id: PeerId) -> bool {
+    CONNECTED.fetch_sub(1, Ordering::Relaxed);
+    CONTROLLER
+        .compare_exchange(id, NO_CONTROLLER, Ordering::Relaxed, Ordering::Relaxed)
+        .is_ok()
+}
+
+/// Number of connected peers
+pub(crate) fn count() -> usize {
+    CONNECTED.load(Ordering::Relaxed)
+}
+
+/// Whether `id` is the controller, taking over control if there is none
+pub(crate) fn take_control(id: PeerId) -> bool {
+    match CONTROLLER.compare_exchange(NO_CONTROLLER, id, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => {
+            defmt::info!("Peer {=u32} is in control", id);
+            true
+        }
+        Err(controller) => controller == id,
+    }
+}
+
+/// Send an event to every connected peer. Peers that fall behind miss the oldest events.
+pub(crate) fn publish(event: Event) {
+    EVENTS.immediate_publisher().publish_immediate(event);
+}
+
+pub(crate) fn subscribe() -> EventSubscriber {
+    EVENTS
+        .subscriber()
+        .expect("One subscriber per connection at most")
+}
+
+/// Wait for the next event, skipping over any that were missed
+pub(crate) async fn next_event(subscriber: &mut EventSubscriber) -> Event {
+    loop {
+        match subscriber.next_message().await {
+            WaitResult::Message(event) => return event,
+            WaitResult::Lagged(n) => {
+                defmt::warn!("Missed {=u64} events", n);
+                DROPPED.fetch_add(n as u32, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+pub(crate) fn record_sent() {
+    SENT.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_retry() {
+    RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_failed() {
+    FAILED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn stats() -> QueueStats {
+    QueueStats {
+        sent: SENT.load(Ordering::Relaxed),
+        retries: RETRIES.load(Ordering::Relaxed),
+        dropped: DROPPED.load(Ordering::Relaxed),
+        failed: FAILED.load(Ordering::Relaxed),
+    }
+}
@@ -12,13 +12,37 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{advertising, MeasureChannel};
+extern crate alloc;
+
+use super::gatt_server::Disconnect;
+use super::peers::PeerId;
+use super::{advertising, broadcast, peers, MeasureChannel, MAX_CONNECTIONS};
 use crate::button::Button;
+use crate::error_log::{self, ErrorCode};
+use crate::nonvolatile::Settings;
 use crate::{battery_voltage, weight};
+use alloc::boxed::Box;
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Timer};
 use nrf_softdevice::ble::{peripheral::AdvertiseError, Connection};
 use nrf_softdevice::Softdevice;
 
+static SETTINGS: Signal<CriticalSectionRawMutex, Settings> = Signal::new();
+/// How the last connection ended
+static DISCONNECTED: Signal<CriticalSectionRawMutex, Disconnect> = Signal::new();
+
+async fn settings(measure_ch: &MeasureChannel) -> Settings {
+    measure_ch
+        .send(weight::Command::GetSettings(Some(Box::new(
+            |settings: Settings| SETTINGS.signal(settings),
+        ))))
+        .await;
+    SETTINGS.wait().await
+}
+
 async fn system_off(measure_ch: MeasureChannel, wakeup_button: Button) -> ! {
     // We shouldn't be sampling at this point, but just in case, stop sampling here.
     // 1. We want the ADC to be powered down while we are asleep
@@ -26,6 +50,15 @@ async fn system_off(measure_ch: MeasureChannel, wakeup_button: Button) -> ! {
     //    event
     if measure_ch.try_send(weight::Command::StopSampling).is_err() {
         defmt::error!("Failed to send StopSampling");
+        error_log::record(ErrorCode::CommandQueueFull);
+    }
+    // Keep the errors of this session, including any that led to going to sleep. Erasing Flash at
+    // brownout voltage could lose the calibration stored in the same page, so the errors of a
+    // session that ends on a dead battery are lost instead.
+    if battery_voltage::is_critically_low() {
+        defmt::warn!("Not saving the error log with a critically low battery");
+    } else if measure_ch.try_send(weight::Command::SaveErrorLog).is_err() {
+        defmt::error!("Failed to send SaveErrorLog");
     }
     Timer::after(Duration::from_millis(1000)).await;
     // We won't return from this
@@ -33,37 +66,103 @@ async fn system_off(measure_ch: MeasureChannel, wakeup_button: Button) -> ! {
     unsafe { crate::sleep::system_off(wakeup_button).await }
 }
 
+/// Runs the BLE side of the scale until it goes to sleep
+///
+/// The scale broadcasts its weight if selected in the settings, or waits for a connection
+/// otherwise. `toggle_broadcast` selects the other mode for this session. It keeps advertising
+/// while there's room for more peers. After the last peer disconnects, it advertises for the
+/// reconnect grace period from the settings unless a peer asked to shut down.
 #[embassy_executor::task]
-pub async fn task(sd: &'static Softdevice, measure_ch: MeasureChannel, wakeup_button: Button) {
+pub async fn task(
+    sd: &'static Softdevice,
+    measure_ch: MeasureChannel,
+    mut wakeup_button: Button,
+    toggle_broadcast: bool,
+) {
     defmt::debug!("Starting BLE task");
     // Check for low battery voltage at startup
     if battery_voltage::is_critically_low() {
         defmt::error!("🔋💀 Battery voltage critically low!");
+        error_log::record(ErrorCode::CriticallyLowBattery);
         system_off(measure_ch, wakeup_button).await;
     }
 
     const ADVERTISED_NAME_STR: Result<&str, core::str::Utf8Error> =
         core::str::from_utf8(super::ADVERTISED_NAME);
+    let settings = settings(&measure_ch).await;
+    if settings.broadcast() != toggle_broadcast {
+        defmt::info!("Broadcasting as {=str}", ADVERTISED_NAME_STR.unwrap());
+        let interval = Duration::from_millis(settings.broadcast_interval_ms().into());
+        broadcast::run(sd, &measure_ch, &mut wakeup_button, interval).await;
+        system_off(measure_ch, wakeup_button).await;
+    }
+
     defmt::info!("Advertising as {=str}", ADVERTISED_NAME_STR.unwrap());
-    let conn: Connection = match advertising::start(sd).await {
-        Ok(conn) => conn,
-        Err(AdvertiseError::Timeout) => {
-            defmt::warn!("Advertising timeout");
-            system_off(measure_ch, wakeup_button).await
-        }
-        Err(AdvertiseError::NoFreeConn) => {
-            defmt::error!("No free connection");
-            system_off(measure_ch, wakeup_button).await
+    let spawner = Spawner::for_current_executor().await;
+    // Reconnect grace period, while advertising after the last peer disconnected
+    let mut grace_s = None;
+    loop {
+        let disconnect = if peers::count() < MAX_CONNECTIONS {
+            let advertise = async {
+                match grace_s.take() {
+                    Some(grace_s) => advertising::restart(sd, grace_s).await,
+                    None => advertising::start(sd).await,
+                }
+            };
+            match select(advertise, DISCONNECTED.wait()).await {
+                Either::First(Ok(conn)) => {
+                    let peer = peers::connect();
+                    if spawner
+                        .spawn(connection_task(conn, peer, measure_ch))
+                        .is_err()
+                    {
+                        defmt::error!("No free connection task");
+                        error_log::record(ErrorCode::AdvertisingFailed);
+                        peers::disconnect(peer);
+                    }
+                    continue;
+                }
+                Either::First(Err(err)) => {
+                    match err {
+                        AdvertiseError::Timeout => defmt::warn!("Advertising timeout"),
+                        AdvertiseError::NoFreeConn => {
+                            defmt::error!("No free connection");
+                            error_log::record(ErrorCode::AdvertisingFailed);
+                        }
+                        AdvertiseError::Raw(err) => {
+                            defmt::error!("Advertising error: {=u32}", err as u32);
+                            error_log::record(ErrorCode::AdvertisingFailed);
+                        }
+                    }
+                    if peers::count() == 0 {
+                        break;
+                    }
+                    // Stop advertising until one of the connected peers disconnects
+                    DISCONNECTED.wait().await
+                }
+                Either::Second(disconnect) => disconnect,
+            }
+        } else {
+            DISCONNECTED.wait().await
+        };
+        if peers::count() > 0 {
+            continue;
         }
-        Err(AdvertiseError::Raw(err)) => {
-            defmt::error!("Advertising error: {=u32}", err as u32);
-            system_off(measure_ch, wakeup_button).await
+        let grace = settings(&measure_ch).await.reconnect_grace_s;
+        if disconnect == Disconnect::Shutdown || grace == 0 {
+            break;
         }
-    };
-    defmt::info!("Peer connected");
-    super::gatt_server::run(&conn, &measure_ch).await;
-    defmt::info!("Disconnected");
-    // Make sure we stop measuring on disconnect
-    measure_ch.send(weight::Command::StopSampling).await;
+        defmt::info!("Waiting {=u16} s for a peer to reconnect", grace);
+        grace_s = Some(grace);
+    }
     system_off(measure_ch, wakeup_button).await;
 }
+
+/// Serve one connected peer until it disconnects
+#[embassy_executor::task(pool_size = MAX_CONNECTIONS)]
+async fn connection_task(conn: Connection, peer: PeerId, measure_ch: MeasureChannel) {
+    let disconnect = super::gatt_server::run(&conn, peer, &measure_ch).await;
+    // Free the connection before advertising for another one
+    drop(conn);
+    DISCONNECTED.signal(disconnect);
+}
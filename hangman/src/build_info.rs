@@ -0,0 +1,56 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Identification of the firmware build, embedded by `build.rs`
+
+/// Version reported by the Progressor API and the Device Information Service
+pub const VERSION: &str = env!("DEVICE_VERSION_NUMBER");
+/// Numeric device ID, reported as the Progressor ID and recorded in calibration certificates.
+/// A `DEVICE_ID` that isn't a decimal number fails the build rather than the first command using it.
+pub const DEVICE_ID: u64 = parse_device_id(env!("DEVICE_ID"));
+/// Abbreviated hash of the git commit, or "unknown" if built outside of a git checkout
+pub const GIT_COMMIT: &str = env!("GIT_COMMIT");
+/// Whether tracked files had uncommitted changes
+pub const GIT_DIRTY: bool = !env!("GIT_DIRTY").is_empty();
+/// Cargo profile, e.g. "release"
+pub const PROFILE: &str = env!("BUILD_PROFILE");
+/// Commit, dirty flag, and profile, e.g. "0123456789ab-dirty release"
+pub const DESCRIPTION: &str = concat!(
+    env!("GIT_COMMIT"),
+    env!("GIT_DIRTY"),
+    " ",
+    env!("BUILD_PROFILE")
+);
+
+const fn parse_device_id(id: &str) -> u64 {
+    let digits = id.as_bytes();
+    assert!(!digits.is_empty(), "DEVICE_ID must not be empty");
+    let mut value: u64 = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        assert!(
+            digits[i].is_ascii_digit(),
+            "DEVICE_ID must be a decimal number"
+        );
+        let Some(shifted) = value.checked_mul(10) else {
+            panic!("DEVICE_ID must fit in a u64");
+        };
+        let Some(sum) = shifted.checked_add((digits[i] - b'0') as u64) else {
+            panic!("DEVICE_ID must fit in a u64");
+        };
+        value = sum;
+        i += 1;
+    }
+    value
+}
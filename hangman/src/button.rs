@@ -59,6 +59,21 @@ impl Button {
         }
     }
 
+    pub fn is_pressed(&self) -> bool {
+        match self.polarity {
+            Polarity::ActiveLow => self.input.is_low(),
+            Polarity::ActiveHigh => self.input.is_high(),
+        }
+    }
+
+    /// Returns immediately if the button isn't pressed
+    pub async fn wait_for_release(&mut self) {
+        match self.polarity {
+            Polarity::ActiveLow => self.input.wait_for_high().await,
+            Polarity::ActiveHigh => self.input.wait_for_low().await,
+        }
+    }
+
     unsafe fn steal_port(&mut self) -> &'static RegisterBlock {
         match self.port {
             Port::Port0 => unsafe { &(*pac::P0::ptr()) },
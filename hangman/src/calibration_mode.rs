@@ -0,0 +1,141 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-device calibration, entered by holding the button while the scale boots
+//!
+//! Runs the same two-point calibration as the BLE opcodes, using the reference weight from the
+//! settings and the status LED to prompt for each step:
+//!
+//! 1. The LED blinks once every couple of seconds: remove all weight and press the button.
+//! 2. The LED blinks twice every couple of seconds: hang the reference weight and press the button.
+//! 3. The LED stays on for a few seconds once the calibration is saved. Otherwise, it flashes
+//!    rapidly, as many times as the rejection code, or `TIMEOUT_FLASHES` times if a step timed out.
+//!
+//! The LED is on while each point is captured.
+
+extern crate alloc;
+
+use crate::button::Button;
+use crate::led::Led;
+use crate::nonvolatile::Settings;
+use crate::weight::{self, Branch, CalibrationPoint, CalibrationRejection, Rejected};
+use crate::MeasureCommandSender;
+use alloc::boxed::Box;
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Timer};
+
+/// How long the button must be held at boot to enter calibration mode
+const HOLD_TIME: Duration = Duration::from_secs(3);
+/// How long to wait for each button press
+const STEP_TIMEOUT: Duration = Duration::from_secs(120);
+const PROMPT_BLINK_PERIOD: Duration = Duration::from_millis(300);
+const PROMPT_PAUSE: Duration = Duration::from_secs(2);
+const FLASH_PERIOD: Duration = Duration::from_millis(150);
+/// Number of flashes if a step timed out. Larger than any rejection code.
+const TIMEOUT_FLASHES: usize = 10;
+const SUCCESS_TIME: Duration = Duration::from_secs(3);
+
+static SETTINGS: Signal<CriticalSectionRawMutex, Settings> = Signal::new();
+static POINT: Signal<CriticalSectionRawMutex, Result<Option<CalibrationPoint>, Rejected>> =
+    Signal::new();
+static SAVED: Signal<CriticalSectionRawMutex, Result<(), CalibrationRejection>> = Signal::new();
+
+/// Whether the button is held for `HOLD_TIME`. Should be called right after boot.
+///
+/// The LED is on while the button is held.
+pub async fn requested(button: &mut Button, led: &mut Led) -> bool {
+    if !button.is_pressed() {
+        return false;
+    }
+    led.on();
+    let held = with_timeout(HOLD_TIME, button.wait_for_release())
+        .await
+        .is_err();
+    led.off();
+    if held {
+        // Don't count the release as the first button press
+        button.wait_for_release().await;
+    }
+    held
+}
+
+/// Blink `n` times every couple of seconds until the button is pressed
+async fn prompt(button: &mut Button, led: &mut Led, n: usize) {
+    let blink = async {
+        loop {
+            led.blink(n, PROMPT_BLINK_PERIOD).await;
+            Timer::after(PROMPT_PAUSE).await;
+        }
+    };
+    select(button.wait_for_press(), blink).await;
+    led.off();
+}
+
+/// Walk through a two-point calibration of the selected load cell
+pub async fn run(button: &mut Button, led: &mut Led, measure_ch: MeasureCommandSender) {
+    measure_ch
+        .send(weight::Command::GetSettings(Some(Box::new(
+            |settings: Settings| SETTINGS.signal(settings),
+        ))))
+        .await;
+    let reference_weight = SETTINGS.wait().await.reference_weight;
+    defmt::info!(
+        "Entered calibration mode with a reference weight of {=f32} kg",
+        reference_weight
+    );
+
+    for (step, known_weight) in [(1, 0.0), (2, reference_weight)] {
+        if with_timeout(STEP_TIMEOUT, prompt(button, led, step))
+            .await
+            .is_err()
+        {
+            defmt::error!("Timed out waiting for calibration step {=usize}", step);
+            led.blink(TIMEOUT_FLASHES, FLASH_PERIOD).await;
+            return;
+        }
+        led.on();
+        measure_ch
+            .send(weight::Command::AddCalibrationPoint(
+                known_weight,
+                Branch::Loading,
+                Some(Box::new(
+                    |point: Result<Option<CalibrationPoint>, Rejected>| POINT.signal(point),
+                )),
+            ))
+            .await;
+        let point = POINT.wait().await;
+        led.off();
+        if !matches!(point, Ok(Some(_))) {
+            led.blink(TIMEOUT_FLASHES, FLASH_PERIOD).await;
+            return;
+        }
+    }
+
+    measure_ch
+        .send(weight::Command::SaveCalibration(Some(Box::new(
+            |result: Result<(), CalibrationRejection>| SAVED.signal(result),
+        ))))
+        .await;
+    match SAVED.wait().await {
+        Ok(()) => {
+            defmt::info!("Calibration mode finished");
+            led.on();
+            Timer::after(SUCCESS_TIME).await;
+            led.off();
+        }
+        Err(rejection) => led.blink(usize::from(rejection.code()), FLASH_PERIOD).await,
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text commands, independent of the transport they arrive over
+
+extern crate alloc;
+
+use crate::weight::{self, noise, Certificate, MAX_CELLS};
+use crate::MeasureCommandSender;
+use alloc::boxed::Box;
+use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::{self, Write};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+const HELP: &str = "\
+certificate        export the calibration certificates, to decode with hangman-cli\r
+help               show this message\r
+noise [n_samples]  characterize ADC noise. The scale must be at rest.\r
+units [unit [g]]   show or set the units (kg, lb, N, kgf) and local gravity in m/s^2\r
+";
+
+/// Longest command line accepted
+const MAX_LINE_LENGTH: usize = 64;
+/// Longest response to a single command
+const MAX_RESPONSE_LENGTH: usize = 1024;
+pub(crate) const PROMPT: &str = "> ";
+
+static NOISE_REPORT: Signal<CriticalSectionRawMutex, Result<noise::Report, weight::Rejected>> =
+    Signal::new();
+static CERTIFICATES: Signal<CriticalSectionRawMutex, ArrayVec<Certificate, MAX_CELLS>> =
+    Signal::new();
+
+/// Command line being typed, with backspace as the only editing
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    line: ArrayString<MAX_LINE_LENGTH>,
+}
+
+impl LineBuffer {
+    /// Add a byte of input. Returns the line once it's complete and not empty.
+    pub(crate) fn push(&mut self, byte: u8) -> Option<ArrayString<MAX_LINE_LENGTH>> {
+        match byte {
+            b'\r' | b'\n' if !self.line.is_empty() => return Some(core::mem::take(&mut self.line)),
+            // Backspace and delete
+            0x08 | 0x7F => {
+                self.line.pop();
+            }
+            byte if byte.is_ascii() && !byte.is_ascii_control() => {
+                if self.line.try_push(char::from(byte)).is_err() {
+                    defmt::warn!("Console line too long");
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+}
+
+/// Run one line of input and return the response, on a new line and followed by the prompt
+pub(crate) async fn respond(
+    line: &str,
+    measure_ch: &MeasureCommandSender,
+) -> ArrayString<MAX_RESPONSE_LENGTH> {
+    let mut response = ArrayString::new();
+    response.push_str("\r\n");
+    if execute(line, measure_ch, &mut response).await.is_err() {
+        defmt::warn!("Console response truncated");
+    }
+    let _ = response.try_push_str(PROMPT);
+    response
+}
+
+/// Run one line of input and write the response to `out`
+pub(crate) async fn execute<W: Write>(
+    line: &str,
+    measure_ch: &MeasureCommandSender,
+    out: &mut W,
+) -> fmt::Result {
+    let mut args = line.split_whitespace();
+    let Some(command) = args.next() else {
+        return Ok(());
+    };
+    match command {
+        "certificate" => {
+            CERTIFICATES.reset();
+            let cb = Box::new(|certificates: &[Certificate]| {
+                CERTIFICATES.signal(certificates.iter().copied().collect());
+            });
+            measure_ch
+                .send(weight::Command::GetCertificates(Some(cb)))
+                .await;
+            let certificates = CERTIFICATES.wait().await;
+            if certificates.is_empty() {
+                return out.write_str("No calibration certificates\r\n");
+            }
+            for certificate in &certificates {
+                write_certificate(certificate, out)?;
+            }
+            Ok(())
+        }
+        "help" => out.write_str(HELP),
+        "noise" => {
+            let n_samples = match args.next().map(str::parse) {
+                None => noise::default_n_samples(),
+                Some(Ok(n_samples)) if (1..=noise::max_n_samples()).contains(&n_samples) => {
+                    n_samples
+                }
+                Some(_) => {
+                    return writeln!(
+                        out,
+                        "The number of samples must be between 1 and {}\r",
+                        noise::max_n_samples()
+                    )
+                }
+            };
+            writeln!(out, "Collecting {n_samples} samples...\r")?;
+            NOISE_REPORT.reset();
+            let cb = Box::new(|result: Result<noise::Report, weight::Rejected>| {
+                NOISE_REPORT.signal(result);
+            });
+            measure_ch
+                .send(weight::Command::CharacterizeNoise(n_samples, Some(cb)))
+                .await;
+            match NOISE_REPORT.wait().await {
+                Ok(report) => write_noise_report(&report, out),
+                Err(weight::Rejected::Busy) => out.write_str("Stop measuring first\r\n"),
+            }
+        }
+        "units" => {
+            let mut units = weight::units();
+            let Some(unit) = args.next() else {
+                return write_units(&units, out);
+            };
+            let Some(unit) = weight::Unit::from_symbol(unit) else {
+                return writeln!(out, "Unknown unit: {unit}. Try kg, lb, N, or kgf.\r");
+            };
+            units.unit = unit;
+            if let Some(gravity) = args.next() {
+                match gravity.parse() {
+                    Ok(gravity) => units.gravity = gravity,
+                    Err(_) => return out.write_str("Invalid gravity\r\n"),
+                }
+            }
+            if !units.is_valid() {
+                return writeln!(
+                    out,
+                    "Gravity must be between {} and {} m/s^2\r",
+                    hangman_utils::units::GRAVITY_RANGE.start(),
+                    hangman_utils::units::GRAVITY_RANGE.end()
+                );
+            }
+            measure_ch.send(weight::Command::SetUnits(units)).await;
+            write_units(&units, out)
+        }
+        _ => writeln!(out, "Unknown command: {command}. Try 'help'.\r"),
+    }
+}
+
+fn write_certificate<W: Write>(certificate: &Certificate, out: &mut W) -> fmt::Result {
+    writeln!(
+        out,
+        "load cell {}, calibrated at boot {}:\r",
+        certificate.cell, certificate.boot_count
+    )?;
+    for byte in certificate.encode() {
+        write!(out, "{byte:02X}")?;
+    }
+    out.write_str("\r\n")
+}
+
+fn write_units<W: Write>(units: &weight::Units, out: &mut W) -> fmt::Result {
+    writeln!(
+        out,
+        "units: {}, gravity: {} m/s^2\r",
+        units.unit.symbol(),
+        units.gravity
+    )
+}
+
+fn write_noise_report<W: Write>(report: &noise::Report, out: &mut W) -> fmt::Result {
+    writeln!(out, "samples:         {}\r", report.n_samples)?;
+    writeln!(out, "mean:            {:.1} counts\r", report.mean)?;
+    writeln!(out, "RMS noise:       {:.2} counts\r", report.rms)?;
+    writeln!(out, "peak-to-peak:    {} counts\r", report.peak_to_peak)?;
+    writeln!(out, "effective bits:  {:.2}\r", report.effective_bits)?;
+    writeln!(out, "noise-free bits: {:.2}\r", report.noise_free_bits)?;
+    writeln!(
+        out,
+        "oversampling:    {}x, up to +{:.2} noise-free bits\r",
+        weight::oversampling_ratio(),
+        weight::oversampling_gain_bits()
+    )?;
+    writeln!(out, "Allan deviation:\r")?;
+    for (i, deviation) in report.allan_deviation.iter().enumerate() {
+        if deviation.is_nan() {
+            break;
+        }
+        writeln!(
+            out,
+            "  tau {:>8.4} s: {:.2} counts\r",
+            noise::tau_s(i),
+            deviation
+        )?;
+    }
+    Ok(())
+}
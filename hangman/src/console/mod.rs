@@ -12,12 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Text console, over USB serial with the `console` feature or over the Nordic UART Service with
+//! the `ble-console` feature
+
+#[cfg(feature = "console")]
 pub mod board;
+pub(crate) mod command;
+#[cfg(feature = "console")]
 pub mod task;
 
+#[cfg(feature = "console")]
 use embassy_nrf::{
     peripherals,
     usb::{vbus_detect::SoftwareVbusDetect, Driver},
 };
 
+#[cfg(feature = "console")]
 pub type UsbDriver = Driver<'static, peripherals::USBD, &'static SoftwareVbusDetect>;
@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::UsbDriver;
+use super::{command, UsbDriver};
+use crate::MeasureCommandSender;
 use defmt_rtt as _;
 use embassy_usb::class::cdc_acm::CdcAcmClass;
 use embassy_usb::driver::EndpointError;
@@ -29,12 +30,38 @@ impl From<EndpointError> for Disconnected {
     }
 }
 
-async fn echo(class: &mut CdcAcmClass<'static, UsbDriver>) -> Result<(), Disconnected> {
+async fn write_all(
+    class: &mut CdcAcmClass<'static, UsbDriver>,
+    data: &[u8],
+) -> Result<(), Disconnected> {
+    let max_packet_size = usize::from(class.max_packet_size());
+    for chunk in data.chunks(max_packet_size) {
+        class.write_packet(chunk).await?;
+    }
+    // A full-size packet needs to be followed by a short one to be delivered right away
+    if data.len() % max_packet_size == 0 {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+async fn console(
+    class: &mut CdcAcmClass<'static, UsbDriver>,
+    measure_ch: &MeasureCommandSender,
+) -> Result<(), Disconnected> {
     let mut buf = [0; 64];
+    let mut input = command::LineBuffer::default();
+    write_all(class, command::PROMPT.as_bytes()).await?;
     loop {
         let n = class.read_packet(&mut buf).await?;
-        let data = &buf[..n];
-        class.write_packet(data).await?;
+        // Echo input back to the terminal
+        write_all(class, &buf[..n]).await?;
+        for &byte in &buf[..n] {
+            if let Some(line) = input.push(byte) {
+                let response = command::respond(&line, measure_ch).await;
+                write_all(class, response.as_bytes()).await?;
+            }
+        }
     }
 }
 
@@ -44,13 +71,17 @@ pub async fn usb_task(mut device: UsbDevice<'static, UsbDriver>) {
     device.run().await;
 }
 
+/// Run the text console over USB serial
 #[embassy_executor::task]
-pub async fn echo_task(mut class: CdcAcmClass<'static, UsbDriver>) {
+pub async fn console_task(
+    mut class: CdcAcmClass<'static, UsbDriver>,
+    measure_ch: MeasureCommandSender,
+) {
     loop {
         defmt::debug!("Waiting for USB");
         class.wait_connection().await;
         defmt::debug!("USB connected");
-        let _ = echo(&mut class).await;
+        let _ = console(&mut class, &measure_ch).await;
         defmt::debug!("USB disconnected");
     }
 }
@@ -0,0 +1,81 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log of errors, reported over BLE by `GetErrorInfo`
+//!
+//! Errors are recorded in RAM from any task. The measurement task restores the log stored in `Nvm`
+//! at boot and writes it back on `weight::Command::SaveErrorLog`, which is sent before going to
+//! sleep unless the battery is critically low. See `hangman_utils::error_log` for the format.
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_time::Instant;
+pub use hangman_utils::error_log::{Entry, ErrorLog};
+
+static LOG: Mutex<CriticalSectionRawMutex, RefCell<ErrorLog>> =
+    Mutex::new(RefCell::new(ErrorLog::new()));
+/// Boot count that errors are attributed to, known once the log has been restored
+static BOOT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Copy, Clone, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// A notification couldn't be sent to the peer
+    NotifyFailed = 0x01,
+    /// A command couldn't be queued for the measurement task
+    CommandQueueFull = 0x02,
+    /// A control message with an invalid payload, or that can't be handled in the current state
+    InvalidCommand = 0x03,
+    /// A calibration or creep learning procedure was rejected
+    CalibrationRejected = 0x04,
+    /// The weight didn't settle in time while capturing a calibration point
+    NotSettled = 0x05,
+    /// The self-test found a fault. The fault itself is reported separately.
+    SelfTestFailed = 0x06,
+    LowBattery = 0x07,
+    /// The battery was too low to start at all
+    CriticallyLowBattery = 0x08,
+    AdvertisingFailed = 0x09,
+    /// Stored data, e.g. a calibration certificate, was corrupt
+    InvalidNvm = 0x0A,
+}
+
+impl ErrorCode {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Record an occurrence of an error
+pub fn record(code: ErrorCode) {
+    let boot_count = BOOT_COUNT.load(Ordering::Relaxed);
+    let uptime_s = Instant::now().as_secs() as u32;
+    LOG.lock(|log| log.borrow_mut().record(code.code(), boot_count, uptime_s));
+}
+
+/// Copy of the log as recorded so far
+pub fn snapshot() -> ErrorLog {
+    LOG.lock(|log| *log.borrow())
+}
+
+pub fn clear() {
+    LOG.lock(|log| log.borrow_mut().clear());
+}
+
+/// Merge the errors recorded so far into the log stored by previous boots
+pub(crate) fn restore(stored: &ErrorLog, boot_count: u32) {
+    BOOT_COUNT.store(boot_count, Ordering::Relaxed);
+    LOG.lock(|log| log.borrow_mut().restore(stored, boot_count));
+}
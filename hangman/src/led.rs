@@ -0,0 +1,62 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::button::Polarity;
+use embassy_nrf::gpio::{AnyPin, Level, Output, OutputDrive};
+use embassy_time::{Duration, Timer};
+
+/// Status LED
+pub struct Led {
+    output: Output<'static, AnyPin>,
+    polarity: Polarity,
+}
+
+impl Led {
+    /// The LED starts off
+    pub fn new(pin: AnyPin, polarity: Polarity) -> Self {
+        let off = match polarity {
+            Polarity::ActiveLow => Level::High,
+            Polarity::ActiveHigh => Level::Low,
+        };
+        Self {
+            output: Output::new(pin, off, OutputDrive::Standard),
+            polarity,
+        }
+    }
+
+    pub fn set(&mut self, on: bool) {
+        match (&self.polarity, on) {
+            (Polarity::ActiveLow, true) | (Polarity::ActiveHigh, false) => self.output.set_low(),
+            (Polarity::ActiveLow, false) | (Polarity::ActiveHigh, true) => self.output.set_high(),
+        }
+    }
+
+    pub fn on(&mut self) {
+        self.set(true);
+    }
+
+    pub fn off(&mut self) {
+        self.set(false);
+    }
+
+    /// Blink `n` times, spending `period` per blink, half of it on
+    pub async fn blink(&mut self, n: usize, period: Duration) {
+        for _ in 0..n {
+            self.on();
+            Timer::after(period / 2).await;
+            self.off();
+            Timer::after(period / 2).await;
+        }
+    }
+}
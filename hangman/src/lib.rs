@@ -18,9 +18,13 @@
 
 pub mod battery_voltage;
 pub mod ble;
+pub mod build_info;
 pub mod button;
-#[cfg(feature = "console")]
+pub mod calibration_mode;
+#[cfg(any(feature = "console", feature = "ble-console"))]
 pub mod console;
+pub mod error_log;
+pub mod led;
 pub mod nonvolatile;
 pub mod sleep;
 pub mod util;
@@ -30,7 +34,7 @@ use blocking_hal::Delay as SysTickDelay;
 pub use embassy_nrf::pac;
 use embassy_sync::{
     blocking_mutex::raw::NoopRawMutex,
-    channel::{Channel, Receiver},
+    channel::{Channel, Receiver, Sender},
     mutex::Mutex,
 };
 
@@ -55,6 +59,8 @@ pub type MeasureCommandChannel =
 pub const MEASURE_COMMAND_CHANNEL_SIZE: usize = 5;
 pub type MeasureCommandReceiver =
     Receiver<'static, NoopRawMutex, weight::Command, MEASURE_COMMAND_CHANNEL_SIZE>;
+pub type MeasureCommandSender =
+    Sender<'static, NoopRawMutex, weight::Command, MEASURE_COMMAND_CHANNEL_SIZE>;
 
 /// Re-implementation of static_cell::make_static, which is broken on latest nightly
 /// https://github.com/embassy-rs/static-cell/issues/16
@@ -22,12 +22,17 @@
 //! impossible to write to them with the Softdevice enabled. Instead, we just reserve one 4kB page
 //! of Flash.
 
+use crate::error_log::{self, ErrorCode, ErrorLog};
+use crate::weight::{Certificate, CreepParams, Unit, Units, MAX_CELLS};
 use aligned::{Aligned, A32};
 use as_slice::AsMutSlice;
+use bytemuck::Zeroable as _;
 use bytemuck_derive::{Pod, Zeroable};
+use core::ops::RangeInclusive;
 use crc::{Crc, CRC_32_ISCSI};
 use embedded_storage::nor_flash::ReadNorFlash;
 use embedded_storage_async::nor_flash::NorFlash;
+use hangman_utils::certificate;
 use nrf_softdevice::{Flash, Softdevice};
 
 /// Address of start of Flash page
@@ -42,25 +47,281 @@ const MAX_ADDR: u32 = 0xE0000;
 const MAX_ADDR: u32 = 0x40000;
 const CHECKSUM_ADDR: u32 = MAX_ADDR - 4;
 
+/// Version of the layout of `Cache`. Must be incremented whenever fields are added, removed, or
+/// changed, along with migration code in `Nvm::new` for the previous layout. Otherwise, the
+/// mismatch causes all stored values to be reset.
+const LAYOUT_VERSION: u32 = 1;
+
 /// Data stored in Flash
 ///
-/// The struct is stored as is, but aligned via `AlignedCache`. Care must be taken if new fields
-/// are added, as the checksum mechanism will cause all stored values to be reset without migration
-/// code.
+/// The struct is stored as is, but aligned via `AlignedCache`.
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C, packed)]
 struct Cache {
-    calibration_m: f32,
-    calibration_b: i32,
+    /// `LAYOUT_VERSION` at the time of writing
+    version: u32,
+    /// Calibration constants for each load cell
+    calibration: [Calibration; MAX_CELLS],
+    /// Calibration constants for each load cell while the load is decreasing. `m` is zero if
+    /// hysteresis compensation is disabled.
+    unloading_calibration: [Calibration; MAX_CELLS],
+    /// Creep model for each load cell. `tau_s` is zero if creep compensation is disabled.
+    creep: [Creep; MAX_CELLS],
+    settings: Settings,
+    /// Number of times the firmware has started and then saved something
+    boot_count: u32,
+    /// Index into `calibration_history` of the most recent entry
+    history_head: u32,
+    /// Ring buffer of calibrations, both current and previous, for all load cells
+    calibration_history: [CalibrationRecord; CALIBRATION_HISTORY_LEN],
+    /// Certificate of the most recent calibration with reference weights for each load cell, in the
+    /// format of `hangman_utils::certificate`. All zeros if there is none.
+    certificates: [[u8; certificate::SIZE]; MAX_CELLS],
+    /// Errors of all boots so far, in the format of `hangman_utils::error_log`
+    error_log: [u8; hangman_utils::error_log::SIZE],
 }
 // Ensure that we only read into and write from 4-byte aligned buffers
 type AlignedCache = Aligned<A32, Cache>;
 
+/// Layout written by firmware from before `LAYOUT_VERSION`, with a single load cell and no version
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C, packed)]
+struct UnversionedCache {
+    calibration_m: f32,
+    calibration_b: i32,
+}
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C, packed)]
+struct Calibration {
+    m: f32,
+    b: i32,
+}
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C, packed)]
+struct Creep {
+    magnitude: f32,
+    tau_s: f32,
+}
+
+/// User-adjustable settings
+#[derive(Copy, Clone, Pod, Zeroable, defmt::Format)]
+#[repr(C)]
+pub struct Settings {
+    /// Reference weight in kg used by the on-device calibration mode
+    pub reference_weight: f32,
+    /// Local gravitational acceleration in m/s²
+    gravity: f32,
+    /// `Unit` discriminant
+    unit: u8,
+    /// Whether to broadcast the weight rather than wait for a connection at boot
+    broadcast: u8,
+    /// Interval between broadcast weight updates in ms, or 0 for the default
+    broadcast_interval_ms: u16,
+    /// How long to advertise for the peer to reconnect after a disconnect, or 0 to go to sleep
+    /// right away
+    pub reconnect_grace_s: u16,
+    /// Number of conversions summed into each reading, or 0 for the board's default
+    oversampling_ratio: u8,
+    _reserved: [u8; 1],
+}
+
+/// Broadcast update interval if none was set
+const DEFAULT_BROADCAST_INTERVAL_MS: u16 = 200;
+/// Valid broadcast update intervals
+pub const BROADCAST_INTERVAL_MS: RangeInclusive<u16> = 100..=10_000;
+/// Longest reconnect grace period
+pub const MAX_RECONNECT_GRACE_S: u16 = 10 * 60;
+
+impl Settings {
+    pub fn units(&self) -> Units {
+        Units {
+            unit: Unit::from_code(self.unit).unwrap_or_default(),
+            gravity: self.gravity,
+        }
+    }
+
+    pub fn set_units(&mut self, units: Units) {
+        self.unit = units.unit.code();
+        self.gravity = units.gravity;
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.broadcast != 0
+    }
+
+    pub fn broadcast_interval_ms(&self) -> u16 {
+        match self.broadcast_interval_ms {
+            0 => DEFAULT_BROADCAST_INTERVAL_MS,
+            interval_ms => interval_ms,
+        }
+    }
+
+    /// Oversampling ratio, if it overrides the board's default
+    pub fn oversampling_ratio(&self) -> Option<usize> {
+        (self.oversampling_ratio != 0).then_some(self.oversampling_ratio.into())
+    }
+
+    pub fn set_oversampling_ratio(&mut self, ratio: Option<usize>) {
+        self.oversampling_ratio = ratio.map_or(0, |ratio| ratio as u8);
+    }
+
+    /// Select broadcast mode, keeping the current interval if `interval_ms` is `None`
+    pub fn set_broadcast(&mut self, enabled: bool, interval_ms: Option<u16>) {
+        self.broadcast = enabled.into();
+        if let Some(interval_ms) = interval_ms {
+            self.broadcast_interval_ms = interval_ms;
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let mut settings = Self {
+            reference_weight: 20.0,
+            gravity: 0.0,
+            unit: 0,
+            broadcast: 0,
+            broadcast_interval_ms: 0,
+            reconnect_grace_s: 60,
+            oversampling_ratio: 0,
+            _reserved: [0; 1],
+        };
+        settings.set_units(Units::DEFAULT);
+        settings
+    }
+}
+
+/// Number of calibrations kept in `Nvm`
+pub const CALIBRATION_HISTORY_LEN: usize = 8;
+
+/// How a calibration came to be
+#[derive(Copy, Clone, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum CalibrationSource {
+    /// Two-point calibration with reference weights
+    Calibrated = 1,
+    /// Copy of an earlier entry in the history
+    RolledBack = 2,
+    /// `DEFAULT_CALIBRATION_M` and `DEFAULT_CALIBRATION_B`
+    Factory = 3,
+    /// Constants written directly, e.g. copied from another scale
+    Cloned = 4,
+}
+
+/// Entry in the calibration history
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct CalibrationRecord {
+    /// `CalibrationSource` discriminant, or zero for an unused entry
+    source: u8,
+    pub cell: u8,
+    _reserved: [u8; 2],
+    /// Boot count at the time of the calibration
+    pub boot_count: u32,
+    pub m: f32,
+    pub b: i32,
+    /// Reading with nothing hanging from the scale. Zero if not calibrated with reference weights.
+    pub zero_reading: i32,
+    /// Reading with the reference weight. Zero if not calibrated with reference weights.
+    pub reference_reading: i32,
+    /// Reference weight in kg. Zero if not calibrated with reference weights.
+    pub reference_weight: f32,
+}
+
+impl CalibrationRecord {
+    pub fn new(source: CalibrationSource, cell: usize, boot_count: u32, m: f32, b: i32) -> Self {
+        Self {
+            source: source as u8,
+            cell: cell as u8,
+            _reserved: [0; 2],
+            boot_count,
+            m,
+            b,
+            zero_reading: 0,
+            reference_reading: 0,
+            reference_weight: 0.0,
+        }
+    }
+
+    /// Record the calibration points that `m` and `b` were derived from
+    pub fn with_points(
+        mut self,
+        zero_reading: i32,
+        reference_reading: i32,
+        reference_weight: f32,
+    ) -> Self {
+        self.zero_reading = zero_reading;
+        self.reference_reading = reference_reading;
+        self.reference_weight = reference_weight;
+        self
+    }
+
+    /// Copy of this entry to restore it at `boot_count`
+    pub fn rolled_back(self, boot_count: u32) -> Self {
+        Self {
+            source: CalibrationSource::RolledBack as u8,
+            boot_count,
+            ..self
+        }
+    }
+
+    pub fn source(&self) -> Option<CalibrationSource> {
+        match self.source {
+            1 => Some(CalibrationSource::Calibrated),
+            2 => Some(CalibrationSource::RolledBack),
+            3 => Some(CalibrationSource::Factory),
+            4 => Some(CalibrationSource::Cloned),
+            _ => None,
+        }
+    }
+}
+
+impl defmt::Format for CalibrationRecord {
+    fn format(&self, fmt: defmt::Formatter) {
+        // Copy fields out of the packed struct to avoid unaligned references
+        let Self {
+            cell,
+            boot_count,
+            m,
+            b,
+            zero_reading,
+            reference_reading,
+            reference_weight,
+            ..
+        } = *self;
+        defmt::write!(
+            fmt,
+            "{} cell {=u8} at boot {=u32}: m = {=f32} b = {=i32} ({=i32} -> 0 kg, {=i32} -> {=f32} kg)",
+            self.source(),
+            cell,
+            boot_count,
+            m,
+            b,
+            zero_reading,
+            reference_reading,
+            reference_weight
+        );
+    }
+}
+
 impl Default for Cache {
     fn default() -> Self {
         Self {
-            calibration_m: crate::weight::DEFAULT_CALIBRATION_M,
-            calibration_b: crate::weight::DEFAULT_CALIBRATION_B,
+            version: LAYOUT_VERSION,
+            calibration: [Calibration {
+                m: crate::weight::DEFAULT_CALIBRATION_M,
+                b: crate::weight::DEFAULT_CALIBRATION_B,
+            }; MAX_CELLS],
+            unloading_calibration: [Calibration { m: 0.0, b: 0 }; MAX_CELLS],
+            creep: [Creep::zeroed(); MAX_CELLS],
+            settings: Settings::default(),
+            boot_count: 0,
+            history_head: 0,
+            calibration_history: [CalibrationRecord::zeroed(); CALIBRATION_HISTORY_LEN],
+            certificates: [[0; certificate::SIZE]; MAX_CELLS],
+            error_log: [0; hangman_utils::error_log::SIZE],
         }
     }
 }
@@ -92,31 +353,155 @@ impl Nvm {
         new.flash
             .read(CHECKSUM_ADDR, stored_checksum.as_mut_slice())
             .unwrap();
-        let load_defaults = *stored_checksum != checksum(bytemuck::bytes_of(&*new.cache));
+        let valid = *stored_checksum == checksum(bytemuck::bytes_of(&*new.cache));
+        let version = new.cache.version;
 
-        if load_defaults {
+        if valid && version == LAYOUT_VERSION {
+            return new;
+        }
+        if let Some(cache) = Self::migrate(&new.cache, *stored_checksum) {
+            defmt::info!("Migrating NVM to layout version {=u32}", LAYOUT_VERSION);
+            *new.cache = cache;
+        } else {
             defmt::info!("Checksum mismatch. Rewriting NVM defaults.");
             new.cache = AlignedCache::default();
         }
+        // Write the current layout, so that older ones don't need to be recognized again
+        new.dirty = true;
         new
     }
 
-    pub fn write_cal_m(&mut self, val: f32) {
-        self.cache.calibration_m = val;
+    /// Convert the contents of a Flash page written in an older layout, if it's one that we know
+    fn migrate(stored: &Cache, stored_checksum: [u8; 4]) -> Option<Cache> {
+        let bytes = bytemuck::bytes_of(stored);
+        let unversioned: UnversionedCache =
+            bytemuck::pod_read_unaligned(&bytes[..core::mem::size_of::<UnversionedCache>()]);
+        if stored_checksum != checksum(bytemuck::bytes_of(&unversioned)) {
+            return None;
+        }
+        let mut cache = Cache::default();
+        cache.calibration[0] = Calibration {
+            m: unversioned.calibration_m,
+            b: unversioned.calibration_b,
+        };
+        Some(cache)
+    }
+
+    pub fn write_cal_m(&mut self, cell: usize, val: f32) {
+        self.cache.calibration[cell].m = val;
         self.dirty = true;
     }
 
-    pub fn read_cal_m(&self) -> f32 {
-        self.cache.calibration_m
+    pub fn read_cal_m(&self, cell: usize) -> f32 {
+        self.cache.calibration[cell].m
     }
 
-    pub fn write_cal_b(&mut self, val: i32) {
-        self.cache.calibration_b = val;
+    pub fn write_cal_b(&mut self, cell: usize, val: i32) {
+        self.cache.calibration[cell].b = val;
         self.dirty = true;
     }
 
-    pub fn read_cal_b(&self) -> i32 {
-        self.cache.calibration_b
+    pub fn read_cal_b(&self, cell: usize) -> i32 {
+        self.cache.calibration[cell].b
+    }
+
+    /// Calibration constants `m` and `b` of the unloading branch, if hysteresis compensation is
+    /// enabled
+    pub fn read_unloading_cal(&self, cell: usize) -> Option<(f32, i32)> {
+        let Calibration { m, b } = self.cache.unloading_calibration[cell];
+        (m != 0.0).then_some((m, b))
+    }
+
+    pub fn write_unloading_cal(&mut self, cell: usize, cal: Option<(f32, i32)>) {
+        let (m, b) = cal.unwrap_or((0.0, 0));
+        self.cache.unloading_calibration[cell] = Calibration { m, b };
+        self.dirty = true;
+    }
+
+    /// Creep model parameters, if creep compensation is enabled
+    pub fn read_creep(&self, cell: usize) -> Option<CreepParams> {
+        let Creep { magnitude, tau_s } = self.cache.creep[cell];
+        (tau_s != 0.0).then_some(CreepParams { magnitude, tau_s })
+    }
+
+    pub fn write_creep(&mut self, cell: usize, params: Option<CreepParams>) {
+        self.cache.creep[cell] = params.map_or(Creep::zeroed(), |params| Creep {
+            magnitude: params.magnitude,
+            tau_s: params.tau_s,
+        });
+        self.dirty = true;
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.cache.settings
+    }
+
+    pub fn write_settings(&mut self, settings: Settings) {
+        self.cache.settings = settings;
+        self.dirty = true;
+    }
+
+    pub fn boot_count(&self) -> u32 {
+        self.cache.boot_count
+    }
+
+    /// Count this boot
+    ///
+    /// Only the cache is updated. The count is saved along with the next change that is flushed,
+    /// so that booting doesn't erase the Flash page, which could lose the calibration if the
+    /// battery browns out mid-erase. Boots that don't save anything aren't counted.
+    pub fn record_boot(&mut self) {
+        self.cache.boot_count = self.cache.boot_count.wrapping_add(1);
+    }
+
+    /// Add a calibration to the history, overwriting the oldest entry if the history is full
+    pub fn push_calibration(&mut self, record: CalibrationRecord) {
+        let head = (self.cache.history_head as usize + 1) % CALIBRATION_HISTORY_LEN;
+        self.cache.calibration_history[head] = record;
+        self.cache.history_head = head as u32;
+        self.dirty = true;
+    }
+
+    /// Calibrations from the most recent to the oldest
+    pub fn calibration_history(&self) -> impl Iterator<Item = CalibrationRecord> + '_ {
+        let head = self.cache.history_head as usize;
+        (0..CALIBRATION_HISTORY_LEN)
+            .map(move |i| {
+                self.cache.calibration_history
+                    [(head + CALIBRATION_HISTORY_LEN - i) % CALIBRATION_HISTORY_LEN]
+            })
+            .take_while(|record| record.source().is_some())
+    }
+
+    /// Certificate of the most recent calibration with reference weights
+    pub fn certificate(&self, cell: usize) -> Option<Certificate> {
+        match Certificate::decode(&self.cache.certificates[cell]) {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                defmt::error!("Invalid certificate for load cell {=usize}: {}", cell, e);
+                error_log::record(ErrorCode::InvalidNvm);
+                None
+            }
+        }
+    }
+
+    pub fn write_certificate(&mut self, certificate: &Certificate) {
+        self.cache.certificates[usize::from(certificate.cell)] = certificate.encode();
+        self.dirty = true;
+    }
+
+    pub fn error_log(&self) -> ErrorLog {
+        ErrorLog::decode(&self.cache.error_log)
+    }
+
+    /// Store `log`. Only marks the cache as dirty if the log changed, so that saving it before
+    /// every sleep doesn't wear out the Flash.
+    pub fn write_error_log(&mut self, log: &ErrorLog) {
+        let encoded = log.encode();
+        if self.cache.error_log != encoded {
+            self.cache.error_log = encoded;
+            self.dirty = true;
+        }
     }
 
     pub async fn flush(&mut self) {
@@ -138,5 +523,6 @@ impl Nvm {
             .write(CHECKSUM_ADDR, &*aligned_checksum)
             .await
             .expect("Write to succeed");
+        self.dirty = false;
     }
 }
@@ -41,6 +41,9 @@ pub struct Ads1230<'d> {
 }
 
 impl<'d> Ads1230<'d> {
+    /// Largest positive reading. Readings are 20-bit signed integers.
+    pub const MAX_READING: i32 = (1 << 19) - 1;
+
     pub fn new(
         data: Input<'d, AnyPin>,
         mut clock: Output<'d, AnyPin>,
@@ -12,26 +12,60 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{RawReading, Sample, SampleProducerMut};
+use super::oversample::Oversampled;
+use super::{RawReading, Sample, SampleProducerMut, CAPACITY_KG};
+use hangman_utils::hysteresis::{Branch, Trend};
+use hangman_utils::two_point_cal::Limits;
+
+/// Change in load needed to switch between the loading and unloading calibrations
+const TREND_DEADBAND_KG: f32 = 0.5;
+
+/// Bounds for plausible calibrations of an ADC whose largest reading is `full_scale`
+///
+/// The range is deliberately generous. A load cell at capacity should span at least 1% of the
+/// ADC's range, and the ADC shouldn't saturate below a twentieth of the capacity.
+pub(crate) fn limits(full_scale: RawReading) -> Limits {
+    let full_scale = full_scale as f32;
+    Limits {
+        capacity: CAPACITY_KG,
+        min_m: CAPACITY_KG / 20.0 / full_scale,
+        max_m: CAPACITY_KG * 100.0 / full_scale,
+    }
+}
 
 pub struct Calibrator<T> {
     sampler: T,
     m: f32,
     b: RawReading,
+    /// Calibration while the load is decreasing, for load cells with significant hysteresis
+    unloading: Option<(f32, RawReading)>,
+    trend: Trend,
 }
 
 impl<T> Calibrator<T> {
-    pub fn new(sampler: T, m: f32, b: RawReading) -> Self {
-        Self { sampler, m, b }
+    pub fn new(sampler: T, m: f32, b: RawReading, unloading: Option<(f32, RawReading)>) -> Self {
+        Self {
+            sampler,
+            m,
+            b,
+            unloading,
+            trend: Trend::new(TREND_DEADBAND_KG),
+        }
     }
 
-    pub fn set_calibration(&mut self, m: f32, b: RawReading) {
+    pub fn set_calibration(&mut self, m: f32, b: RawReading, unloading: Option<(f32, RawReading)>) {
         self.m = m;
         self.b = b;
+        self.unloading = unloading;
+        self.trend.reset();
     }
 
-    fn calibrate(&self, raw_value: RawReading) -> f32 {
-        let value = (raw_value - self.b) as f32 * self.m;
+    fn calibrate(&mut self, raw_value: Oversampled) -> f32 {
+        let loading = raw_value.counts_from(self.b) * self.m;
+        let value = match (self.unloading, self.trend.update(loading)) {
+            (Some((m, b)), Branch::Unloading) => raw_value.counts_from(b) * m,
+            _ => loading,
+        };
         defmt::trace!("Calibrated = {=f32}", value);
         value
     }
@@ -39,7 +73,7 @@ impl<T> Calibrator<T> {
 
 impl<T> SampleProducerMut for Calibrator<T>
 where
-    T: SampleProducerMut<Output = RawReading>,
+    T: SampleProducerMut<Output = Oversampled>,
 {
     type Output = f32;
 
@@ -57,7 +91,7 @@ where
 
 impl<T> SampleProducerMut for &mut Calibrator<T>
 where
-    T: SampleProducerMut<Output = RawReading>,
+    T: SampleProducerMut<Output = Oversampled>,
 {
     type Output = f32;
 
@@ -0,0 +1,72 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Optional creep compensation of tared readings
+//!
+//! See `hangman_utils::creep` for the model.
+
+use super::{CreepParams, Sample, SampleProducerMut};
+use embassy_time::Duration;
+use hangman_utils::creep::Compensator;
+
+/// How long to hang the known weight when learning creep, unless told otherwise. Should be at least
+/// as long as the longest hold.
+pub const DEFAULT_LEARNING_TIME: Duration = Duration::from_secs(180);
+/// Longest learning time that can be requested, in minutes. Learning can't be interrupted, and the
+/// scale can't be tared or stopped until it's done.
+pub const MAX_LEARNING_MINUTES: u8 = 10;
+
+pub struct CreepCompensator<T> {
+    sampler: T,
+    compensator: Compensator,
+}
+
+impl<T> CreepCompensator<T> {
+    /// `params` is `None` to pass readings through unchanged
+    pub fn new(sampler: T, params: Option<CreepParams>) -> Self {
+        Self {
+            sampler,
+            compensator: Compensator::new(params),
+        }
+    }
+
+    pub fn sampler_mut(&mut self) -> &mut T {
+        &mut self.sampler
+    }
+
+    pub fn set_params(&mut self, params: Option<CreepParams>) {
+        self.compensator.set_params(params);
+    }
+
+    /// Forget about the current load, e.g. after taring
+    pub fn reset(&mut self) {
+        self.compensator.reset();
+    }
+}
+
+impl<T> SampleProducerMut for CreepCompensator<T>
+where
+    T: SampleProducerMut<Output = f32>,
+{
+    type Output = f32;
+
+    async fn sample(&mut self) -> Sample<Self::Output> {
+        let mut sample = self.sampler.sample().await;
+        sample.value = self
+            .compensator
+            .compensate(sample.timestamp.as_micros(), sample.value);
+        defmt::trace!("Creep compensated = {=f32}", sample.value);
+        sample
+    }
+}
@@ -31,6 +31,9 @@ pub struct Hx711<'d> {
 }
 
 impl<'d> Hx711<'d> {
+    /// Largest positive reading. Readings are 24-bit signed integers.
+    pub const MAX_READING: i32 = (1 << 23) - 1;
+
     pub fn new(
         data: Input<'d, AnyPin>,
         mut clock: Output<'d, AnyPin>,
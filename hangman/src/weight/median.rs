@@ -34,6 +34,11 @@ where
             accumulator: median::stack::Filter::new(),
         }
     }
+
+    /// Forget past readings, e.g. because they're no longer comparable with new ones
+    pub(crate) fn reset(&mut self) {
+        self.accumulator = median::stack::Filter::new();
+    }
 }
 
 impl<T> SampleProducerMut for Median<T>
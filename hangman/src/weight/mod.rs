@@ -15,41 +15,119 @@
 pub mod ads1230;
 pub mod average;
 mod calibrate;
+pub mod creep;
 pub mod hx711;
 pub mod median;
-mod random;
+pub mod noise;
+mod oversample;
+pub mod self_test;
+#[cfg(feature = "sim")]
+mod sim;
 mod tare;
 mod task;
 
 extern crate alloc;
 
-use crate::nonvolatile::Nvm;
+use crate::nonvolatile::{CalibrationRecord, Nvm, Settings};
 pub use ads1230::Ads1230;
 use alloc::boxed::Box;
+use core::cell::Cell;
 use core::ops::DerefMut;
-use embassy_sync::blocking_mutex::raw::RawMutex;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use embassy_sync::blocking_mutex::{
+    self,
+    raw::{CriticalSectionRawMutex, RawMutex},
+};
 use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Instant};
+pub use hangman_utils::certificate::Certificate;
+pub use hangman_utils::creep::{Params as CreepParams, Rejection as CreepRejection};
+pub use hangman_utils::hysteresis::{Branch, Report as HysteresisReport};
+pub use hangman_utils::two_point_cal::Rejection as CalibrationRejection;
+pub use hangman_utils::units::{Unit, Units};
 pub use hx711::Hx711;
 use once_cell::sync::OnceCell;
 pub use task::task_function;
 
 static SAMPLING_INTERVAL_HZ: OnceCell<usize> = OnceCell::new();
+static DEFAULT_OVERSAMPLING_RATIO: OnceCell<usize> = OnceCell::new();
+/// Oversampling ratio in use, the board's default unless overridden by the settings
+static OVERSAMPLING_RATIO: AtomicUsize = AtomicUsize::new(1);
+static UNITS: blocking_mutex::Mutex<CriticalSectionRawMutex, Cell<Units>> =
+    blocking_mutex::Mutex::new(Cell::new(Units::DEFAULT));
 // Temporary defaults for test load cell
 // TODO: provide better defaults for Hangman P1_0
 pub const DEFAULT_CALIBRATION_M: f32 = 4.675_038e-6;
 pub const DEFAULT_CALIBRATION_B: i32 = -100598;
+/// Capacity of the crane scale
+pub const CAPACITY_KG: f32 = 150.0;
+/// Maximum number of load cells, each with its own ADC, that can be combined into one scale
+pub const MAX_CELLS: usize = 2;
 
 type RawReading = i32;
 pub type OnRawMeasurementCb = dyn FnMut(Duration, RawReading);
 pub type OnCalibratedMeasurementCb = dyn FnMut(Duration, f32);
 pub type OnTaredMeasurementCb = dyn FnMut(Duration, f32);
+/// Called with the summed tared weight followed by the tared weight of each load cell
+pub type OnTaredCellsMeasurementCb = dyn FnMut(Duration, f32, &[f32]);
+
+/// Why a command was rejected without running
+#[derive(Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Rejected {
+    /// The command needs the scale to be idle, but it's measuring
+    Busy = 0x01,
+}
+
+impl Rejected {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Called with the outcome of `SaveCalibration`
+pub type OnSaveCalibrationCb = dyn FnOnce(Result<(), CalibrationRejection>);
+/// Averaged reading captured for a calibration point
+#[derive(Copy, Clone, defmt::Format)]
+pub struct CalibrationPoint {
+    pub reading: RawReading,
+    /// Standard deviation of the averaged readings, in counts
+    pub std_dev: f32,
+    /// Standard deviation of the averaged readings, converted to kg with the current calibration
+    pub std_dev_kg: f32,
+    /// Number of readings averaged
+    pub n_samples: u32,
+}
+
+/// Called with the captured calibration point, or `None` if the weight didn't settle in time
+pub type OnCalibrationPointCb = dyn FnOnce(Result<Option<CalibrationPoint>, Rejected>);
+/// Called with the calibration constants `m` and `b`
+pub type OnCalibrationCb = dyn FnOnce(f32, RawReading);
+/// Called with the calibration history, from the most recent entry to the oldest
+pub type OnCalibrationHistoryCb = dyn FnOnce(&[CalibrationRecord]);
+/// Called with the calibration certificates of the load cells that have one
+pub type OnCertificatesCb = dyn FnOnce(&[Certificate]);
+/// Called with whether the requested entry existed and was restored
+pub type OnRollBackCalibrationCb = dyn FnOnce(bool);
+/// Called with whether the reference weight was in range and saved
+pub type OnSetReferenceWeightCb = dyn FnOnce(bool);
+pub type OnSelfTestCb = dyn FnOnce(Result<self_test::Report, Rejected>);
+pub type OnNoiseCb = dyn FnOnce(Result<noise::Report, Rejected>);
+pub type OnHysteresisCb = dyn FnOnce(Result<HysteresisReport, Rejected>);
+pub type OnSettingsCb = dyn FnOnce(Settings);
+/// Called with the learned creep parameters of each load cell
+pub type OnCreepCb = dyn FnOnce(Result<Result<&[CreepParams], CreepRejection>, Rejected>);
 
 pub enum SampleType {
+    /// Individual conversions, at the ADC's sampling rate
     Raw(Option<Box<OnRawMeasurementCb>>),
+    /// Oversampled and median filtered, rounded to whole counts
     FilteredRaw(Option<Box<OnRawMeasurementCb>>),
+    /// In the configured `units`
     Calibrated(Option<Box<OnCalibratedMeasurementCb>>),
+    /// Sum of all load cells
     Tared(Option<Box<OnTaredMeasurementCb>>),
+    /// Sum of all load cells as well as each individual load cell
+    TaredCells(Option<Box<OnTaredCellsMeasurementCb>>),
 }
 
 pub enum Command {
@@ -57,8 +135,53 @@ pub enum Command {
     StartSampling(SampleType),
     StopSampling,
     Tare,
-    AddCalibrationPoint(f32),
-    SaveCalibration,
+    /// Capture a calibration point for the given weight once the readings are stable. Points on
+    /// the unloading branch are approached from a heavier weight.
+    AddCalibrationPoint(f32, Branch, Option<Box<OnCalibrationPointCb>>),
+    /// Validate and persist the calibration points added so far
+    SaveCalibration(Option<Box<OnSaveCalibrationCb>>),
+    /// Report the calibration constants of the selected load cell
+    GetCalibration(Option<Box<OnCalibrationCb>>),
+    /// Validate and persist the given calibration constants `m` and `b` for the selected load cell,
+    /// e.g. to clone the calibration of another scale
+    SetCalibration(f32, RawReading, Option<Box<OnSaveCalibrationCb>>),
+    GetCalibrationHistory(Option<Box<OnCalibrationHistoryCb>>),
+    /// Report the calibration certificates of all load cells
+    GetCertificates(Option<Box<OnCertificatesCb>>),
+    /// Restore the calibration at the given index of the history, where 0 is the most recent
+    RollBackCalibration(usize, Option<Box<OnRollBackCalibrationCb>>),
+    /// Restore the default calibration of the selected load cell
+    RestoreFactoryCalibration,
+    /// Select the load cell used for calibration and for the `Raw`, `FilteredRaw`, and `Calibrated`
+    /// sample types. Discards any calibration points that haven't been saved.
+    SelectCell(usize),
+    /// Check the load cell for faults. Must be run while the scale is at rest.
+    SelfTest(Option<Box<OnSelfTestCb>>),
+    /// Collect the given number of raw readings from the selected load cell and characterize
+    /// their noise. Must be run while the scale is at rest.
+    CharacterizeNoise(usize, Option<Box<OnNoiseCb>>),
+    /// Compare the unloading calibration points added so far against the loading calibration
+    CharacterizeHysteresis(Option<Box<OnHysteresisCb>>),
+    /// Learn the creep of every load cell and enable creep compensation. Must be started at rest,
+    /// after which the given known weight is hung for the given time.
+    LearnCreep(f32, Duration, Option<Box<OnCreepCb>>),
+    /// Disable creep compensation for every load cell
+    ClearCreepCompensation,
+    GetSettings(Option<Box<OnSettingsCb>>),
+    /// Set the reference weight used by the on-device calibration mode
+    SetReferenceWeight(f32, Option<Box<OnSetReferenceWeightCb>>),
+    /// Set the units of the `Calibrated` sample type and of other outputs that aren't part of the
+    /// Progressor API
+    SetUnits(Units),
+    /// Select whether to broadcast the weight at boot, and optionally the update interval in ms
+    SetBroadcast(bool, Option<u16>),
+    /// Set how long to wait for the peer to reconnect after a disconnect, in seconds
+    SetReconnectGrace(u16),
+    /// Set the number of conversions summed into each reading, or go back to the board's default if
+    /// `None`. Only while idle.
+    SetOversampling(Option<u8>),
+    /// Store the errors recorded by `error_log` in `Nvm`, e.g. before going to sleep
+    SaveErrorLog,
 }
 
 impl defmt::Format for Command {
@@ -74,35 +197,152 @@ impl defmt::Format for Command {
             Command::StartSampling(SampleType::Tared(_)) => {
                 defmt::write!(fmt, "StartSampling (Tared)");
             }
+            Command::StartSampling(SampleType::TaredCells(_)) => {
+                defmt::write!(fmt, "StartSampling (TaredCells)");
+            }
             Command::StopSampling => defmt::write!(fmt, "StopSampling"),
             Command::Tare => defmt::write!(fmt, "Tare"),
-            Command::AddCalibrationPoint(known_weight) => {
-                defmt::write!(fmt, "AddCalibrationPoint: {=f32}", known_weight);
+            Command::AddCalibrationPoint(known_weight, branch, _) => {
+                defmt::write!(
+                    fmt,
+                    "AddCalibrationPoint: {=f32} ({})",
+                    known_weight,
+                    branch
+                );
             }
-            Command::SaveCalibration => defmt::write!(fmt, "SaveCalibration"),
+            Command::SaveCalibration(_) => defmt::write!(fmt, "SaveCalibration"),
+            Command::GetCalibration(_) => defmt::write!(fmt, "GetCalibration"),
+            Command::SetCalibration(m, b, _) => {
+                defmt::write!(fmt, "SetCalibration: m = {=f32} b = {=i32}", m, b);
+            }
+            Command::GetCalibrationHistory(_) => defmt::write!(fmt, "GetCalibrationHistory"),
+            Command::GetCertificates(_) => defmt::write!(fmt, "GetCertificates"),
+            Command::RollBackCalibration(index, _) => {
+                defmt::write!(fmt, "RollBackCalibration: {=usize}", index);
+            }
+            Command::RestoreFactoryCalibration => defmt::write!(fmt, "RestoreFactoryCalibration"),
+            Command::SelectCell(cell) => defmt::write!(fmt, "SelectCell: {=usize}", cell),
+            Command::SelfTest(_) => defmt::write!(fmt, "SelfTest"),
+            Command::CharacterizeNoise(n_samples, _) => {
+                defmt::write!(fmt, "CharacterizeNoise: {=usize}", n_samples);
+            }
+            Command::CharacterizeHysteresis(_) => defmt::write!(fmt, "CharacterizeHysteresis"),
+            Command::LearnCreep(known_weight, duration, _) => {
+                defmt::write!(
+                    fmt,
+                    "LearnCreep: {=f32} kg for {=u64} s",
+                    known_weight,
+                    duration.as_secs()
+                );
+            }
+            Command::ClearCreepCompensation => defmt::write!(fmt, "ClearCreepCompensation"),
+            Command::GetSettings(_) => defmt::write!(fmt, "GetSettings"),
+            Command::SetReferenceWeight(weight, _) => {
+                defmt::write!(fmt, "SetReferenceWeight: {=f32}", weight);
+            }
+            Command::SetUnits(units) => defmt::write!(fmt, "SetUnits: {}", units),
+            Command::SetBroadcast(enabled, interval_ms) => {
+                defmt::write!(fmt, "SetBroadcast: {=bool} {}", enabled, interval_ms);
+            }
+            Command::SetReconnectGrace(grace_s) => {
+                defmt::write!(fmt, "SetReconnectGrace: {=u16} s", grace_s);
+            }
+            Command::SetOversampling(ratio) => defmt::write!(fmt, "SetOversampling: {}", ratio),
+            Command::SaveErrorLog => defmt::write!(fmt, "SaveErrorLog"),
         }
     }
 }
 
 pub struct Config {
+    /// Conversion rate of the ADC
     pub sampling_interval_hz: usize,
+    /// Number of conversions summed into each reading, unless overridden by the settings. 1
+    /// disables oversampling.
+    pub oversampling_ratio: usize,
 }
 
 pub fn init(config: Config) {
+    let max_ratio =
+        hangman_utils::noise::max_oversampling_ratio(config.sampling_interval_hz as u32) as usize;
+    assert!(
+        config.oversampling_ratio > 0 && config.oversampling_ratio <= max_ratio,
+        "Oversampling ratio must be between 1 and half the sampling rate"
+    );
     SAMPLING_INTERVAL_HZ
         .set(config.sampling_interval_hz)
         .expect("weight::init to be called only once");
+    DEFAULT_OVERSAMPLING_RATIO
+        .set(config.oversampling_ratio)
+        .expect("weight::init to be called only once");
+    set_oversampling_ratio(None);
 }
 
+/// Conversion rate of the ADC
 pub fn sampling_interval_hz() -> usize {
     *SAMPLING_INTERVAL_HZ
         .get()
         .expect("weight::init to have been called")
 }
 
-async fn write_calibration(nvm: &mut Nvm, cal_m: f32, cal_b: RawReading) {
-    nvm.write_cal_m(cal_m);
-    nvm.write_cal_b(cal_b);
+pub fn oversampling_ratio() -> usize {
+    OVERSAMPLING_RATIO.load(Ordering::Relaxed)
+}
+
+/// Whether `ratio` can be used as the oversampling ratio, leaving at least one reading per half
+/// second
+pub fn is_valid_oversampling_ratio(ratio: usize) -> bool {
+    let max = hangman_utils::noise::max_oversampling_ratio(sampling_interval_hz() as u32);
+    ratio > 0 && ratio <= max as usize
+}
+
+/// Use `ratio` from now on, or the board's default if `None`
+fn set_oversampling_ratio(ratio: Option<usize>) {
+    let ratio = ratio.unwrap_or_else(|| {
+        *DEFAULT_OVERSAMPLING_RATIO
+            .get()
+            .expect("weight::init to have been called")
+    });
+    OVERSAMPLING_RATIO.store(ratio, Ordering::Relaxed);
+    if ratio > 1 {
+        defmt::info!(
+            "Oversampling {=usize}x: {=usize} Hz output, up to {=f32} extra noise-free bits",
+            ratio,
+            output_rate_hz(),
+            oversampling_gain_bits()
+        );
+    }
+}
+
+/// Units of outputs that aren't part of the Progressor API, which always uses kg
+pub fn units() -> Units {
+    UNITS.lock(Cell::get)
+}
+
+fn set_units(units: Units) {
+    UNITS.lock(|cell| cell.set(units));
+}
+
+/// Rate of readings after oversampling, i.e. of every sample type except `Raw`
+pub fn output_rate_hz() -> usize {
+    sampling_interval_hz() / oversampling_ratio()
+}
+
+/// Number of readings in half a second at the output rate, at least one
+pub(crate) fn half_second_samples() -> usize {
+    (output_rate_hz() / 2).max(1)
+}
+
+/// Theoretical gain in noise-free bits from oversampling
+pub fn oversampling_gain_bits() -> f32 {
+    hangman_utils::noise::oversampling_gain_bits(oversampling_ratio() as u32)
+}
+
+/// Make `record` the current calibration of its load cell and add it to the history
+async fn write_calibration(nvm: &mut Nvm, record: CalibrationRecord) {
+    let cell = usize::from(record.cell);
+    nvm.write_cal_m(cell, record.m);
+    nvm.write_cal_b(cell, record.b);
+    nvm.push_calibration(record);
     nvm.flush().await;
 }
 
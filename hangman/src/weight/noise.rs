@@ -0,0 +1,61 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Noise characterization of the raw ADC readings
+//!
+//! See `hangman_utils::noise` for the statistics.
+
+use super::{RawReading, Sample, SampleProducerMut};
+use hangman_utils::noise::Analyzer;
+pub use hangman_utils::noise::{Report, ALLAN_TAUS};
+
+/// Number of samples to collect if the requester doesn't specify one
+pub fn default_n_samples() -> usize {
+    // 30 seconds, enough for the longest averaging time at 80 Hz
+    super::sampling_interval_hz() * 30
+}
+
+/// Largest number of samples that can be requested. The measurement task can't do anything else,
+/// e.g. tare or stop measuring, while collecting them.
+pub fn max_n_samples() -> usize {
+    // 5 minutes
+    super::sampling_interval_hz() * 300
+}
+
+/// Averaging time of the Allan deviation at `index` in `Report::allan_deviation`, in seconds
+pub fn tau_s(index: usize) -> f32 {
+    ALLAN_TAUS[index] as f32 / super::sampling_interval_hz() as f32
+}
+
+/// Collect raw readings at rest and compute their noise statistics
+///
+/// The caller is responsible for powering down the ADC afterwards.
+pub(crate) async fn run<T>(mut adc: T, n_samples: usize, full_scale: RawReading) -> Report
+where
+    T: SampleProducerMut<Output = RawReading>,
+{
+    // 0.5 second
+    let warmup = super::sampling_interval_hz() / 2;
+    for _ in 0..warmup {
+        let _ = adc.sample().await;
+    }
+    let mut analyzer = Analyzer::new();
+    for _ in 0..n_samples {
+        let Sample { value, .. } = adc.sample().await;
+        analyzer.add_sample(value);
+    }
+    let report = analyzer.finish(full_scale);
+    defmt::info!("Noise characterization: {}", report);
+    report
+}
@@ -0,0 +1,90 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Oversampling: sum consecutive conversions to gain resolution at a lower output rate
+
+use super::average::Accumulator;
+use super::{RawReading, Sample, SampleProducer};
+
+type Sum = <RawReading as Accumulator>::Sum;
+
+/// Sum of `n` consecutive raw readings
+///
+/// Keeping the sum instead of the average preserves the fractional counts gained by oversampling.
+/// Fields are ordered so that comparisons between readings with the same `n` compare the sums.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct Oversampled {
+    pub sum: Sum,
+    pub n: u32,
+}
+
+impl Oversampled {
+    /// Average reading, rounded to the nearest count
+    pub fn mean(&self) -> RawReading {
+        let n = Sum::from(self.n.max(1));
+        let rounding = if self.sum < 0 { -n / 2 } else { n / 2 };
+        ((self.sum + rounding) / n) as RawReading
+    }
+
+    /// Average reading, in fractional counts
+    pub fn counts(&self) -> f64 {
+        self.sum as f64 / f64::from(self.n.max(1))
+    }
+
+    /// Distance of the average reading from `zero`, in fractional counts
+    pub fn counts_from(&self, zero: RawReading) -> f32 {
+        let n = self.n.max(1);
+        (self.sum - Sum::from(zero) * Sum::from(n)) as f32 / n as f32
+    }
+}
+
+impl defmt::Format for Oversampled {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "{=i64}/{=u32}", self.sum, self.n);
+    }
+}
+
+/// Sums conversions according to `oversampling_ratio`, which can change between readings
+pub(crate) struct Oversampler<T> {
+    source: T,
+}
+
+impl<T> Oversampler<T> {
+    pub(crate) fn new(source: T) -> Self {
+        Self { source }
+    }
+}
+
+impl<T> SampleProducer for Oversampler<T>
+where
+    T: SampleProducer<Output = RawReading>,
+{
+    type Output = Oversampled;
+
+    /// Sum the next `ratio` conversions. The timestamp is that of the last conversion.
+    async fn sample(&self) -> Sample<Self::Output> {
+        let ratio = super::oversampling_ratio() as u32;
+        let mut sum: Sum = 0;
+        let mut timestamp = None;
+        for _ in 0..ratio {
+            let sample = self.source.sample().await;
+            sum += Sum::from(sample.value);
+            timestamp = Some(sample.timestamp);
+        }
+        Sample {
+            timestamp: timestamp.unwrap(),
+            value: Oversampled { sum, n: ratio },
+        }
+    }
+}
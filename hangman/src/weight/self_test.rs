@@ -0,0 +1,106 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load cell self-test, run at boot and on demand
+//!
+//! See `hangman_utils::self_test` for the checks themselves.
+
+use super::{RawReading, Sample, SampleProducerMut, CAPACITY_KG};
+use core::sync::atomic::{AtomicU8, Ordering};
+use embassy_time::{with_timeout, Duration};
+use hangman_utils::self_test::{Analyzer, Limits};
+pub use hangman_utils::self_test::{Fault, Report};
+
+/// Fault code of the most recent self-test, or zero if it passed
+static LAST_FAULT: AtomicU8 = AtomicU8::new(0);
+
+/// Peak-to-peak noise at rest above which we consider the load cell faulty
+const MAX_NOISE_KG: f32 = 1.0;
+/// The first reading can take a while, e.g. if the ADS1230 is running an offset calibration
+const FIRST_READING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Result of the most recent self-test
+pub fn last_fault() -> Option<Fault> {
+    Fault::from_code(LAST_FAULT.load(Ordering::Relaxed))
+}
+
+pub(crate) fn set_last_fault(fault: Option<Fault>) {
+    LAST_FAULT.store(fault.map_or(0, Fault::code), Ordering::Relaxed);
+}
+
+/// Derive self-test limits from the ADC's range and the current calibration
+pub(crate) fn limits(full_scale: RawReading, cal_m: f32, cal_b: RawReading) -> Limits {
+    let kg_to_counts = |kg: f32| -> u32 {
+        let counts = kg / cal_m;
+        if counts.is_finite() {
+            (counts as i64).unsigned_abs().min(full_scale as u64) as u32
+        } else {
+            full_scale as u32
+        }
+    };
+    Limits {
+        full_scale,
+        expected_interval_us: 1_000_000 / super::sampling_interval_hz() as u32,
+        interval_tolerance_pct: 25,
+        zero: cal_b,
+        zero_window: hangman_utils::self_test::zero_window(kg_to_counts(CAPACITY_KG)),
+        max_peak_to_peak: kg_to_counts(MAX_NOISE_KG),
+        // 0.05% of full scale
+        short_window: full_scale / 2000,
+    }
+}
+
+/// Take raw readings at rest and check them for signs of a faulty load cell
+///
+/// The caller is responsible for powering down the ADC and for recording the result with
+/// `set_last_fault` afterwards.
+pub(crate) async fn run<T>(mut adc: T, limits: Limits) -> Report
+where
+    T: SampleProducerMut<Output = RawReading>,
+{
+    // 0.5 second
+    let warmup = super::sampling_interval_hz() / 2;
+    // 0.5 second
+    let n_samples = super::sampling_interval_hz() / 2;
+    let sample_timeout = Duration::from_micros(u64::from(limits.expected_interval_us) * 10);
+
+    let mut analyzer = Analyzer::new();
+    let mut timed_out = false;
+    for i in 0..(warmup + n_samples) {
+        let timeout = if i == 0 {
+            FIRST_READING_TIMEOUT
+        } else {
+            sample_timeout
+        };
+        let Ok(Sample { timestamp, value }) = with_timeout(timeout, adc.sample()).await else {
+            defmt::error!("Timed out waiting for ADC reading");
+            timed_out = true;
+            break;
+        };
+        if i >= warmup {
+            analyzer.add_sample(timestamp.as_micros(), value);
+        }
+    }
+
+    let mut report = analyzer.finish(&limits);
+    // Readings stopped partway through
+    if timed_out && report.fault.is_none() {
+        report.fault = Some(Fault::DataRate);
+    }
+    match report.fault {
+        None => defmt::info!("Self-test passed: {}", report),
+        Some(fault) => defmt::error!("Self-test failed with {}: {}", fault, report),
+    }
+    report
+}
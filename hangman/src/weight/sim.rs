@@ -0,0 +1,245 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simulated ADC that plays back scripted force profiles. Enabled via the `sim` feature.
+//!
+//! The simulation is configured at build time via environment variables:
+//!
+//! * `SIM_PROFILE`: one of `step` (default), `ramp`, `sine`, or `trace`
+//! * `SIM_TRACE`: path to a recorded trace for the `trace` profile: a text file with one weight in kg
+//!   per line. If a line has multiple comma-separated columns, the last one is used.
+//! * `SIM_TRACE_RATE_HZ`: sample rate of the recorded trace. Defaults to 80.
+//! * `SIM_NOISE_KG`: standard deviation of Gaussian noise added to every reading. Defaults to 0.05.
+//! * `SIM_DRIFT_KG_PER_MIN`: linear drift since boot. Defaults to 0.
+//!
+//! Every profile starts over whenever the ADC is powered up, i.e. at the start of every
+//! measurement, and begins with a couple of seconds at rest so that taring works as expected.
+
+use super::{RawReading, Sample, SampleProducerMut, DEFAULT_CALIBRATION_B, DEFAULT_CALIBRATION_M};
+use core::num::NonZeroU32;
+use embassy_time::{Duration, Instant, Ticker};
+use hangman_utils::sim::{self, Profile, Segment};
+use nrf_softdevice::Softdevice;
+use once_cell::sync::OnceCell;
+use rand::{Rng, RngCore};
+
+/// Heaviest weight of the built-in profiles, leaving room for noise below the full scale of about
+/// 39.7 kg at the default calibration
+const MAX_PROFILE_KG: f32 = 35.0;
+
+const STEP: &[Segment] = &[
+    Segment::Hold {
+        kg: 0.0,
+        duration_s: 3.0,
+    },
+    Segment::Hold {
+        kg: 20.0,
+        duration_s: 7.0,
+    },
+    Segment::Hold {
+        kg: 0.0,
+        duration_s: 3.0,
+    },
+    Segment::Hold {
+        kg: MAX_PROFILE_KG,
+        duration_s: 7.0,
+    },
+];
+
+const RAMP: &[Segment] = &[
+    Segment::Hold {
+        kg: 0.0,
+        duration_s: 3.0,
+    },
+    Segment::Ramp {
+        from_kg: 0.0,
+        to_kg: MAX_PROFILE_KG,
+        duration_s: 2.0,
+    },
+    Segment::Hold {
+        kg: MAX_PROFILE_KG,
+        duration_s: 5.0,
+    },
+    Segment::Ramp {
+        from_kg: MAX_PROFILE_KG,
+        to_kg: 0.0,
+        duration_s: 1.0,
+    },
+];
+
+const SINE: &[Segment] = &[
+    Segment::Hold {
+        kg: 0.0,
+        duration_s: 3.0,
+    },
+    Segment::Sine {
+        offset_kg: MAX_PROFILE_KG / 2.0,
+        amplitude_kg: MAX_PROFILE_KG / 2.0,
+        period_s: 4.0,
+        duration_s: 20.0,
+    },
+];
+
+/// Recorded trace, converted to little-endian `f32`s by the build script
+static TRACE_SAMPLES: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sim_trace.bin"));
+
+/// Parse a number from a build-time environment variable
+fn parse_env(name: &str, value: Option<&str>, default: f32) -> f32 {
+    value.map_or(default, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| defmt::panic!("Invalid {=str}: {=str}", name, value))
+    })
+}
+
+fn profile() -> Profile<'static> {
+    match option_env!("SIM_PROFILE").unwrap_or("step") {
+        "step" => Profile::new(STEP),
+        "ramp" => Profile::new(RAMP),
+        "sine" => Profile::new(SINE),
+        "trace" => {
+            let sample_rate_hz =
+                parse_env("SIM_TRACE_RATE_HZ", option_env!("SIM_TRACE_RATE_HZ"), 80.0);
+            static TRACE: OnceCell<[Segment; 2]> = OnceCell::new();
+            Profile::new(TRACE.get_or_init(|| {
+                [
+                    Segment::Hold {
+                        kg: 0.0,
+                        duration_s: 3.0,
+                    },
+                    Segment::Trace {
+                        samples: TRACE_SAMPLES,
+                        sample_rate_hz,
+                    },
+                ]
+            }))
+        }
+        other => defmt::panic!("Unknown SIM_PROFILE: {=str}", other),
+    }
+}
+
+struct SoftDeviceRng(&'static Softdevice);
+
+impl RngCore for SoftDeviceRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0; 4];
+        nrf_softdevice::random_bytes(self.0, &mut buf).unwrap();
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0; 8];
+        nrf_softdevice::random_bytes(self.0, &mut buf).unwrap();
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        nrf_softdevice::random_bytes(self.0, dest).unwrap();
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        nrf_softdevice::random_bytes(self.0, dest).map_err(|_| NonZeroU32::new(1).unwrap().into())
+    }
+}
+
+/// Stand-in for a real ADC
+///
+/// Weights are converted to raw readings using the active calibration of the load cell, so that the
+/// reported weight follows the profile after a calibration too.
+pub struct SimulatedAdc {
+    rng: SoftDeviceRng,
+    profile: Profile<'static>,
+    /// Fraction of the profile's weight seen by this ADC, for scales with multiple load cells
+    share: f32,
+    noise_kg: f32,
+    drift_kg_per_min: f32,
+    /// Calibration constants `m` and `b` to convert weights into readings
+    cal_m: f32,
+    cal_b: RawReading,
+    /// Time that the ADC was powered up and a ticker for the ADC's sample rate
+    state: Option<(Instant, Ticker)>,
+}
+
+impl SimulatedAdc {
+    /// Same range as the HX711
+    pub const MAX_READING: i32 = (1 << 23) - 1;
+
+    pub fn new(sd: &'static Softdevice, share: f32) -> Self {
+        Self {
+            rng: SoftDeviceRng(sd),
+            profile: profile(),
+            share,
+            noise_kg: parse_env("SIM_NOISE_KG", option_env!("SIM_NOISE_KG"), 0.05),
+            drift_kg_per_min: parse_env(
+                "SIM_DRIFT_KG_PER_MIN",
+                option_env!("SIM_DRIFT_KG_PER_MIN"),
+                0.0,
+            ),
+            cal_m: DEFAULT_CALIBRATION_M,
+            cal_b: DEFAULT_CALIBRATION_B,
+            state: None,
+        }
+    }
+
+    /// Convert weights with new calibration constants, e.g. once the load cell was calibrated
+    pub fn set_calibration(&mut self, m: f32, b: RawReading) {
+        self.cal_m = m;
+        self.cal_b = b;
+    }
+
+    fn is_powered(&self) -> bool {
+        self.state.is_some()
+    }
+
+    pub fn power_down(&mut self) {
+        self.state = None;
+    }
+
+    pub async fn power_up(&mut self) {
+        let ticker = Ticker::every(Duration::from_hz(super::sampling_interval_hz() as u64));
+        self.state = Some((Instant::now(), ticker));
+    }
+
+    fn noise(&mut self) -> f32 {
+        // Map to (0, 1] to keep the logarithm in the Box-Muller transform finite
+        let u1 = 1.0 - self.rng.gen::<f32>();
+        let u2 = self.rng.gen::<f32>();
+        self.noise_kg * sim::standard_normal(u1, u2)
+    }
+}
+
+impl SampleProducerMut for SimulatedAdc {
+    type Output = RawReading;
+
+    async fn sample(&mut self) -> Sample<Self::Output> {
+        if !self.is_powered() {
+            self.power_up().await;
+        }
+        let (power_up_time, ticker) = self.state.as_mut().unwrap();
+        ticker.next().await;
+        let power_up_time = *power_up_time;
+
+        let timestamp = Instant::now();
+        let t_s = (timestamp - power_up_time).as_micros() as f32 / 1e6;
+        let minutes_since_boot = timestamp.as_millis() as f32 / 60_000.0;
+        let kg = self.share * self.profile.weight_at(t_s)
+            + self.drift_kg_per_min * minutes_since_boot
+            + self.noise();
+        let value = ((kg / self.cal_m) as RawReading)
+            .saturating_add(self.cal_b)
+            .clamp(-Self::MAX_READING - 1, Self::MAX_READING);
+        defmt::trace!("Simulated {=f32} kg = {=i32}", kg, value);
+        Sample { timestamp, value }
+    }
+}
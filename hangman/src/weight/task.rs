@@ -12,124 +12,766 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::calibrate::Calibrator;
+extern crate alloc;
+
+use super::calibrate::{self, Calibrator};
+use super::creep::CreepCompensator;
+use super::oversample::Oversampler;
 use super::tare::Tarer;
 #[cfg(feature = "nrf52832")]
 use super::Ads1230;
 #[cfg(feature = "nrf52840")]
 use super::Hx711;
-use super::{average, median::Median, Command, RawReading, Sample, SampleProducerMut, SampleType};
-use crate::{make_static, nonvolatile::Nvm, MeasureCommandReceiver};
+use super::{
+    average, median::Median, noise, self_test, Branch, CalibrationPoint, Certificate, Command,
+    CreepParams, CreepRejection, RawReading, Rejected, Sample, SampleProducerMut, SampleType,
+    MAX_CELLS,
+};
+use crate::error_log::{self, ErrorCode};
+use crate::nonvolatile::{
+    CalibrationRecord, CalibrationSource, Nvm, BROADCAST_INTERVAL_MS, CALIBRATION_HISTORY_LEN,
+    MAX_RECONNECT_GRACE_S,
+};
+use crate::{make_static, MeasureCommandReceiver};
+use alloc::boxed::Box;
+use arrayvec::ArrayVec;
+use embassy_futures::join::join;
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Instant, Timer};
 use hangman_utils::two_point_cal::{self, CalPoint, TwoPoint};
+use hangman_utils::{creep, hysteresis, stability};
 use nrf_softdevice::Softdevice;
 
 const THREAD_SLEEP_DELAY: Duration = Duration::from_millis(100);
+/// Readings must vary less than this to be captured as a calibration point
+const STABLE_STD_DEV_KG: f32 = 0.1;
+const CALIBRATION_POINT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Time to wait for the known weight to be hung when learning creep
+const CREEP_LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest difference between the learned load and the known weight, relative to the known weight
+const CREEP_MAX_WEIGHT_ERROR: f32 = 0.1;
 
 #[cfg(feature = "nrf52832")]
-type Adc = Ads1230<'static>;
+type HardwareAdc = Ads1230<'static>;
 #[cfg(feature = "nrf52840")]
-type Adc = Hx711<'static>;
+type HardwareAdc = Hx711<'static>;
+
+#[cfg(not(feature = "sim"))]
+type Adc = HardwareAdc;
+#[cfg(feature = "sim")]
+type Adc = super::sim::SimulatedAdc;
 
 type SharedAdc = Mutex<NoopRawMutex, Adc>;
-type SharedFilteredAdc = Mutex<NoopRawMutex, Median<&'static SharedAdc>>;
+type SharedFilteredAdc = Mutex<NoopRawMutex, Median<Oversampler<&'static SharedAdc>>>;
 type SharedCalibrator = Mutex<NoopRawMutex, Calibrator<&'static SharedFilteredAdc>>;
+type TaredCell = CreepCompensator<Tarer<&'static SharedCalibrator>>;
 
 enum MeasurementState {
     Idle,
     Active(SampleType, Instant),
 }
 
-struct MeasurementContext {
-    state: MeasurementState,
+/// Measurement pipeline for a single load cell
+struct Cell {
     adc: &'static SharedAdc,
     median: &'static SharedFilteredAdc,
     calibrator: &'static SharedCalibrator,
-    tarer: Tarer<&'static SharedCalibrator>,
+}
+
+struct MeasurementContext {
+    sd: &'static Softdevice,
+    state: MeasurementState,
+    cells: &'static [Cell],
+    /// Tare offsets and creep compensation are tracked per cell. Indices match `cells`.
+    tarers: ArrayVec<TaredCell, MAX_CELLS>,
+    /// Index of the cell used for calibration and single-cell sample types
+    selected_cell: usize,
     nvm: Nvm,
     factory_cal: TwoPoint<RawReading>,
+    /// Calibration points captured while unloading, for hysteresis compensation
+    unloading_cal: TwoPoint<RawReading>,
+    /// Standard deviations in counts of the zero and reference points of `factory_cal` and
+    /// `unloading_cal`, for the calibration certificate
+    point_std_devs: [[f32; 2]; 2],
+}
+
+impl MeasurementContext {
+    fn selected(&self) -> &'static Cell {
+        let cells = self.cells;
+        &cells[self.selected_cell]
+    }
+
+    fn calibrators(&self) -> ArrayVec<&'static SharedCalibrator, MAX_CELLS> {
+        self.cells.iter().map(|cell| cell.calibrator).collect()
+    }
+
+    async fn power_up(&self) {
+        for cell in self.cells {
+            cell.adc.lock().await.power_up().await;
+        }
+    }
+
+    async fn power_down(&self) {
+        for cell in self.cells {
+            cell.adc.lock().await.power_down();
+        }
+    }
+}
+
+/// Take one reading from each of the given producers, keeping the readings aligned in time
+///
+/// Returns the timestamp of the latest reading along with the readings in the same order as
+/// `producers`.
+async fn sample_all<T>(producers: &mut [T]) -> (Instant, ArrayVec<T::Output, MAX_CELLS>)
+where
+    T: SampleProducerMut,
+{
+    let mut values = ArrayVec::new();
+    match producers {
+        [only] => {
+            let Sample { timestamp, value } = only.sample().await;
+            values.push(value);
+            (timestamp, values)
+        }
+        [first, second] => {
+            let (mut a, mut b) = join(first.sample(), second.sample()).await;
+            // Both ADCs free-run at the same nominal rate, so each sample is the next conversion
+            // from its ADC. If the two are more than half a sample period apart, the earlier one is
+            // stale. Replace it with its ADC's following conversion, which is closer to the other.
+            let half_period = Duration::from_hz(super::output_rate_hz() as u64) / 2;
+            if b.timestamp > a.timestamp + half_period {
+                a = first.sample().await;
+            } else if a.timestamp > b.timestamp + half_period {
+                b = second.sample().await;
+            }
+            let timestamp = a.timestamp.max(b.timestamp);
+            values.push(a.value);
+            values.push(b.value);
+            (timestamp, values)
+        }
+        _ => defmt::unreachable!("Between 1 and MAX_CELLS load cells are supported"),
+    }
+}
+
+/// Learn the creep of every load cell from a known weight hung for `duration`
+///
+/// The scale must be at rest when this starts. Readings are relative to the weight at rest, so
+/// that the tare offset doesn't matter.
+async fn learn_creep(
+    context: &MeasurementContext,
+    weight: f32,
+    duration: Duration,
+) -> Result<ArrayVec<CreepParams, MAX_CELLS>, CreepRejection> {
+    let mut calibrators = context.calibrators();
+    let n_samples = super::half_second_samples();
+    for _ in 0..n_samples {
+        let _ = sample_all(&mut calibrators).await;
+    }
+    let mut baseline: ArrayVec<f32, MAX_CELLS> = calibrators.iter().map(|_| 0.0).collect();
+    for _ in 0..n_samples {
+        let (_, values) = sample_all(&mut calibrators).await;
+        for (baseline, value) in baseline.iter_mut().zip(values) {
+            *baseline += value / n_samples as f32;
+        }
+    }
+    let load = |values: &[f32]| -> f32 {
+        values
+            .iter()
+            .zip(&baseline)
+            .map(|(value, baseline)| value - baseline)
+            .sum()
+    };
+
+    defmt::info!("Waiting for {=f32} kg to be hung", weight);
+    let threshold = weight / 2.0;
+    let deadline = Instant::now() + CREEP_LOAD_TIMEOUT;
+    let onset = loop {
+        let (timestamp, values) = sample_all(&mut calibrators).await;
+        if load(&values) >= threshold {
+            break timestamp;
+        }
+        if timestamp > deadline {
+            return Err(CreepRejection::NoLoad);
+        }
+    };
+
+    defmt::info!("Learning creep for {=u64} s", duration.as_secs());
+    let duration_s = duration.as_micros() as f32 / 1e6;
+    let mut learners: ArrayVec<creep::Learner, MAX_CELLS> = calibrators
+        .iter()
+        .map(|_| creep::Learner::new(duration_s))
+        .collect();
+    loop {
+        let (timestamp, values) = sample_all(&mut calibrators).await;
+        let elapsed = timestamp.checked_duration_since(onset).unwrap_or_default();
+        if elapsed >= duration {
+            break;
+        }
+        if load(&values) < threshold {
+            return Err(CreepRejection::Unloaded);
+        }
+        let t_s = elapsed.as_micros() as f32 / 1e6;
+        for ((learner, value), baseline) in learners.iter_mut().zip(values).zip(&baseline) {
+            learner.add_sample(t_s, value - baseline);
+        }
+    }
+
+    let fits = learners
+        .iter()
+        .map(creep::Learner::finish)
+        .collect::<Result<ArrayVec<creep::Fit, MAX_CELLS>, _>>()?;
+    let learned_weight: f32 = fits.iter().map(|fit| fit.load).sum();
+    defmt::info!(
+        "Learned creep of {=f32} kg: {}",
+        learned_weight,
+        fits.as_slice()
+    );
+    if (learned_weight - weight).abs() > CREEP_MAX_WEIGHT_ERROR * weight {
+        return Err(CreepRejection::WeightMismatch);
+    }
+    Ok(fits.iter().map(|fit| fit.params).collect())
 }
 
-async fn handle_command(cmd: Command, context: &mut MeasurementContext, adc: &SharedAdc) {
+/// Run the self-test on every load cell
+///
+/// Returns the first failing report, or the last report if every load cell passes.
+async fn run_self_test(context: &MeasurementContext) -> self_test::Report {
+    let mut result: Option<self_test::Report> = None;
+    for (i, cell) in context.cells.iter().enumerate() {
+        let limits = self_test::limits(
+            Adc::MAX_READING,
+            context.nvm.read_cal_m(i),
+            context.nvm.read_cal_b(i),
+        );
+        defmt::info!("Running self-test on load cell {=usize}", i);
+        let report = self_test::run(cell.adc, limits).await;
+        cell.adc.lock().await.power_down();
+        match result {
+            Some(self_test::Report { fault: Some(_), .. }) => (),
+            _ => result = Some(report),
+        }
+    }
+    let result = result.expect("At least one load cell");
+    self_test::set_last_fault(result.fault);
+    if result.fault.is_some() {
+        error_log::record(ErrorCode::SelfTestFailed);
+    }
+    result
+}
+
+/// Average the selected load cell's filtered readings once they're stable
+///
+/// Returns `None` if the readings don't settle within `CALIBRATION_POINT_TIMEOUT`.
+async fn capture_calibration_point(context: &MeasurementContext) -> Option<CalibrationPoint> {
+    let cell = context.selected();
+    let cal_m = context.nvm.read_cal_m(context.selected_cell).abs();
+    let settings = stability::Settings {
+        // 0.5 second
+        block_len: super::half_second_samples() as u32,
+        // 1 second in total
+        n_blocks: 2,
+        // Based on the current calibration, which is good enough to judge stability
+        max_std_dev: f64::from(STABLE_STD_DEV_KG / cal_m),
+    };
+    let max_samples = super::output_rate_hz() as u64 * CALIBRATION_POINT_TIMEOUT.as_secs();
+    let mut capture = stability::Capture::new(settings);
+    for _ in 0..max_samples {
+        let Sample { value, .. } = cell.median.sample().await;
+        if let Some(point) = capture.add_sample(value.counts()) {
+            // Round half away from zero. Casting truncates towards zero.
+            let rounded = if point.mean < 0.0 {
+                point.mean - 0.5
+            } else {
+                point.mean + 0.5
+            };
+            return Some(CalibrationPoint {
+                reading: rounded as RawReading,
+                std_dev: point.std_dev as f32,
+                std_dev_kg: point.std_dev as f32 * cal_m,
+                n_samples: point.n_samples,
+            });
+        }
+    }
+    None
+}
+
+/// Persist and activate a calibration. `unloading` holds the constants of the unloading branch,
+/// or `None` to disable hysteresis compensation.
+async fn apply_calibration(
+    context: &mut MeasurementContext,
+    record: CalibrationRecord,
+    unloading: Option<(f32, RawReading)>,
+) {
+    defmt::info!("New calibration: {} unloading: {}", record, unloading);
+    let cell = usize::from(record.cell);
+    context.nvm.write_unloading_cal(cell, unloading);
+    super::write_calibration(&mut context.nvm, record).await;
+    context.cells[cell]
+        .calibrator
+        .lock()
+        .await
+        .set_calibration(record.m, record.b, unloading);
+    #[cfg(feature = "sim")]
+    context.cells[cell]
+        .adc
+        .lock()
+        .await
+        .set_calibration(record.m, record.b);
+}
+
+/// Certificate for the calibration points of the selected load cell, which were validated and
+/// resulted in `m` and `b`. The unloading points are only included if that branch was saved too.
+fn make_certificate(
+    context: &MeasurementContext,
+    m: f32,
+    b: RawReading,
+    with_unloading: bool,
+) -> Certificate {
+    let temperature = match nrf_softdevice::temperature_celsius(context.sd) {
+        Ok(temperature) => Some(temperature.to_num::<f32>()),
+        Err(_) => {
+            defmt::error!("Failed to read temperature");
+            None
+        }
+    };
+    let mut certificate = Certificate::new(
+        context.selected_cell as u8,
+        context.nvm.boot_count(),
+        crate::build_info::DEVICE_ID,
+        crate::build_info::VERSION,
+        temperature,
+        m,
+        b,
+    );
+    let branches = [
+        (Branch::Loading, &context.factory_cal),
+        (Branch::Unloading, &context.unloading_cal),
+    ];
+    let n_branches = if with_unloading { 2 } else { 1 };
+    for ((branch, cal), std_devs) in branches
+        .into_iter()
+        .zip(context.point_std_devs)
+        .take(n_branches)
+    {
+        if let Some(zero) = cal.zero() {
+            certificate.add_point(branch, 0.0, zero, std_devs[0]);
+        }
+        if let Some(reference) = cal.reference() {
+            certificate.add_point(
+                branch,
+                reference.expected_value,
+                reference.measured_value,
+                std_devs[1],
+            );
+        }
+    }
+    certificate
+}
+
+async fn handle_command(cmd: Command, context: &mut MeasurementContext) {
     match cmd {
         Command::StartSampling(measurement_cb) => {
             if !matches!(context.state, MeasurementState::Idle) {
                 defmt::error!("Can't start sampling while already measuring");
+                error_log::record(ErrorCode::InvalidCommand);
                 return;
             }
-            adc.lock().await.power_up().await;
+            context.power_up().await;
             context.state = MeasurementState::Active(measurement_cb, Instant::now());
         }
         Command::StopSampling => {
-            adc.lock().await.power_down();
+            context.power_down().await;
             context.state = MeasurementState::Idle;
         }
         Command::Tare => {
             if !matches!(context.state, MeasurementState::Idle) {
                 defmt::error!("Can't tare while measuring");
+                error_log::record(ErrorCode::InvalidCommand);
                 return;
             }
 
-            // 0.5 second
-            let warmup = super::sampling_interval_hz() / 2;
-            // 0.5 second
-            let filter_size = super::sampling_interval_hz() / 2;
+            let warmup = super::half_second_samples();
+            let filter_size = super::half_second_samples();
+            let mut calibrators = context.calibrators();
             for _ in 0..warmup {
-                let _ = context.calibrator.sample().await;
+                let _ = sample_all(&mut calibrators).await;
             }
-            let mut filter = average::Window::<f32>::new(filter_size);
+            let mut filters: ArrayVec<average::Window<f32>, MAX_CELLS> = calibrators
+                .iter()
+                .map(|_| average::Window::<f32>::new(filter_size))
+                .collect();
             for _ in 0..(filter_size - 1) {
-                let Sample { value, .. } = context.calibrator.sample().await;
-                assert!(filter.add_sample(value).is_none());
+                let (_, values) = sample_all(&mut calibrators).await;
+                for (filter, value) in filters.iter_mut().zip(values) {
+                    assert!(filter.add_sample(value).is_none());
+                }
+            }
+            let (_, values) = sample_all(&mut calibrators).await;
+            for ((filter, tarer), value) in filters.iter_mut().zip(&mut context.tarers).zip(values)
+            {
+                let average = filter.add_sample(value).unwrap();
+                tarer.sampler_mut().set_offset(average);
+                tarer.reset();
             }
-            let Sample { value, .. } = context.calibrator.sample().await;
-            let average = filter.add_sample(value).unwrap();
-            context.tarer.set_offset(average);
 
-            adc.lock().await.power_down();
+            context.power_down().await;
             context.state = MeasurementState::Idle;
         }
-        Command::AddCalibrationPoint(weight) => {
+        Command::AddCalibrationPoint(weight, branch, cb) => {
             if !matches!(context.state, MeasurementState::Idle) {
-                defmt::error!("Can't run factory cal while measuring");
+                reject_while_measuring("capture a calibration point", cb);
                 return;
             }
 
-            // 1 second
-            let warmup = super::sampling_interval_hz();
-            // 1 second
-            let filter_size = super::sampling_interval_hz();
-            for _ in 0..warmup {
-                let _ = context.calibrator.sample().await;
+            let point = capture_calibration_point(context).await;
+            context.power_down().await;
+            match point {
+                Some(point) => {
+                    defmt::info!(
+                        "Captured {} calibration point for {=f32} kg: {}",
+                        branch,
+                        weight,
+                        point
+                    );
+                    let (cal, std_devs) = match branch {
+                        Branch::Loading => {
+                            (&mut context.factory_cal, &mut context.point_std_devs[0])
+                        }
+                        Branch::Unloading => {
+                            (&mut context.unloading_cal, &mut context.point_std_devs[1])
+                        }
+                    };
+                    cal.add_point(CalPoint {
+                        expected_value: weight,
+                        measured_value: point.reading,
+                    });
+                    std_devs[usize::from(weight != 0.0)] = point.std_dev;
+                }
+                None => {
+                    defmt::error!("Timed out waiting for the weight to settle");
+                    error_log::record(ErrorCode::NotSettled);
+                }
             }
-            let mut filter = average::Window::<RawReading>::new(filter_size);
-            for _ in 0..(filter_size - 1) {
-                let Sample { value, .. } = context.median.sample().await;
-                assert!(filter.add_sample(value).is_none());
-            }
-            let Sample { value, .. } = context.median.sample().await;
-            let reading = filter.add_sample(value).unwrap();
-            context.factory_cal.add_point(CalPoint {
-                expected_value: weight,
-                measured_value: reading,
+            if let Some(cb) = cb {
+                cb(Ok(point));
+            }
+        }
+        Command::SaveCalibration(cb) => {
+            let limits = calibrate::limits(Adc::MAX_READING);
+            // The unloading branch is optional, but must be valid if both of its points exist
+            let unloading = match context.unloading_cal.get_cal_constants() {
+                Some(_) => context
+                    .unloading_cal
+                    .get_validated_cal_constants(&limits)
+                    .map(|two_point_cal::Constants { m, b }| Some((m, b))),
+                None => Ok(None),
+            };
+            let result = unloading.and_then(|unloading| {
+                context
+                    .factory_cal
+                    .get_validated_cal_constants(&limits)
+                    .map(|constants| (constants, unloading))
             });
+            match result {
+                Ok((two_point_cal::Constants { m, b }, unloading)) => {
+                    // Validation guarantees that both points exist
+                    let zero = context.factory_cal.zero().unwrap();
+                    let reference = context.factory_cal.reference().unwrap();
+                    let record = CalibrationRecord::new(
+                        CalibrationSource::Calibrated,
+                        context.selected_cell,
+                        context.nvm.boot_count(),
+                        m,
+                        b,
+                    )
+                    .with_points(
+                        zero,
+                        reference.measured_value,
+                        reference.expected_value,
+                    );
+                    let certificate = make_certificate(context, m, b, unloading.is_some());
+                    defmt::info!("Calibration certificate: {}", certificate);
+                    context.nvm.write_certificate(&certificate);
+                    apply_calibration(context, record, unloading).await;
+                }
+                Err(rejection) => {
+                    defmt::error!("Calibration rejected: {}", rejection);
+                    error_log::record(ErrorCode::CalibrationRejected);
+                }
+            }
+            if let Some(cb) = cb {
+                cb(result.map(|_| ()));
+            }
         }
-        Command::SaveCalibration => {
-            if let Some(two_point_cal::Constants { m, b }) = context.factory_cal.get_cal_constants()
-            {
-                defmt::info!("New calibration: m = {=f32} b = {=i32}", m, b);
-                super::write_calibration(&mut context.nvm, m, b).await;
-                context.calibrator.lock().await.set_calibration(m, b);
+        Command::GetCalibration(cb) => {
+            let cell = context.selected_cell;
+            if let Some(cb) = cb {
+                cb(context.nvm.read_cal_m(cell), context.nvm.read_cal_b(cell));
+            }
+        }
+        Command::SetCalibration(m, b, cb) => {
+            let constants = two_point_cal::Constants { m, b };
+            let result = constants.validate(&calibrate::limits(Adc::MAX_READING));
+            match result {
+                Ok(()) => {
+                    let record = CalibrationRecord::new(
+                        CalibrationSource::Cloned,
+                        context.selected_cell,
+                        context.nvm.boot_count(),
+                        m,
+                        b,
+                    );
+                    apply_calibration(context, record, None).await;
+                }
+                Err(rejection) => {
+                    defmt::error!("Calibration rejected: {}", rejection);
+                    error_log::record(ErrorCode::CalibrationRejected);
+                }
+            }
+            if let Some(cb) = cb {
+                cb(result);
+            }
+        }
+        Command::GetCalibrationHistory(cb) => {
+            let history: ArrayVec<CalibrationRecord, CALIBRATION_HISTORY_LEN> =
+                context.nvm.calibration_history().collect();
+            if let Some(cb) = cb {
+                cb(&history);
+            }
+        }
+        Command::GetCertificates(cb) => {
+            let certificates: ArrayVec<Certificate, MAX_CELLS> = (0..context.cells.len())
+                .filter_map(|cell| context.nvm.certificate(cell))
+                .collect();
+            if let Some(cb) = cb {
+                cb(&certificates);
+            }
+        }
+        Command::RollBackCalibration(index, cb) => {
+            let record = context
+                .nvm
+                .calibration_history()
+                .nth(index)
+                // The history may contain load cells that this scale doesn't have
+                .filter(|record| usize::from(record.cell) < context.cells.len());
+            match record {
+                Some(record) => {
+                    let boot_count = context.nvm.boot_count();
+                    apply_calibration(context, record.rolled_back(boot_count), None).await;
+                }
+                None => {
+                    defmt::error!("No calibration to roll back to at index {=usize}", index);
+                    error_log::record(ErrorCode::InvalidCommand);
+                }
+            }
+            if let Some(cb) = cb {
+                cb(record.is_some());
+            }
+        }
+        Command::RestoreFactoryCalibration => {
+            let record = CalibrationRecord::new(
+                CalibrationSource::Factory,
+                context.selected_cell,
+                context.nvm.boot_count(),
+                super::DEFAULT_CALIBRATION_M,
+                super::DEFAULT_CALIBRATION_B,
+            );
+            apply_calibration(context, record, None).await;
+        }
+        Command::SelectCell(cell) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                defmt::error!("Can't select load cell while measuring");
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            if cell >= context.cells.len() {
+                defmt::error!(
+                    "Load cell {=usize} doesn't exist. Only {=usize} available.",
+                    cell,
+                    context.cells.len()
+                );
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            context.selected_cell = cell;
+            context.factory_cal = TwoPoint::default();
+            context.unloading_cal = TwoPoint::default();
+            context.point_std_devs = Default::default();
+        }
+        Command::SelfTest(cb) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                reject_while_measuring("run the self-test", cb);
+                return;
+            }
+            let report = run_self_test(context).await;
+            if let Some(cb) = cb {
+                cb(Ok(report));
+            }
+        }
+        Command::CharacterizeNoise(n_samples, cb) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                reject_while_measuring("characterize noise", cb);
+                return;
+            }
+            let cell = context.selected();
+            let report = noise::run(cell.adc, n_samples, Adc::MAX_READING).await;
+            cell.adc.lock().await.power_down();
+            if let Some(cb) = cb {
+                cb(Ok(report));
+            }
+        }
+        Command::CharacterizeHysteresis(cb) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                reject_while_measuring("characterize hysteresis", cb);
+                return;
+            }
+            // Prefer the loading points captured alongside the unloading points, if any
+            let cell = context.selected_cell;
+            let loading =
+                context
+                    .factory_cal
+                    .get_cal_constants()
+                    .unwrap_or(two_point_cal::Constants {
+                        m: context.nvm.read_cal_m(cell),
+                        b: context.nvm.read_cal_b(cell),
+                    });
+            let report = hysteresis::characterize(&loading, &context.unloading_cal);
+            defmt::info!(
+                "Hysteresis: {} ({=f32}% of the reference weight)",
+                report,
+                report.reference_error_pct()
+            );
+            if let Some(cb) = cb {
+                cb(Ok(report));
+            }
+        }
+        Command::LearnCreep(weight, duration, cb) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                reject_while_measuring("learn creep", cb);
+                return;
+            }
+            let result = learn_creep(context, weight, duration).await;
+            context.power_down().await;
+            match &result {
+                Ok(params) => {
+                    for (i, (tarer, params)) in context.tarers.iter_mut().zip(params).enumerate() {
+                        context.nvm.write_creep(i, Some(*params));
+                        tarer.set_params(Some(*params));
+                    }
+                    context.nvm.flush().await;
+                }
+                Err(rejection) => {
+                    defmt::error!("Failed to learn creep: {}", rejection);
+                    error_log::record(ErrorCode::CalibrationRejected);
+                }
+            }
+            if let Some(cb) = cb {
+                cb(Ok(result.as_deref().map_err(|&rejection| rejection)));
+            }
+        }
+        Command::ClearCreepCompensation => {
+            for (i, tarer) in context.tarers.iter_mut().enumerate() {
+                context.nvm.write_creep(i, None);
+                tarer.set_params(None);
+            }
+            context.nvm.flush().await;
+        }
+        Command::GetSettings(cb) => {
+            if let Some(cb) = cb {
+                cb(context.nvm.settings());
+            }
+        }
+        Command::SetReferenceWeight(weight, cb) => {
+            let valid = weight > 0.0 && weight <= super::CAPACITY_KG;
+            if valid {
+                let mut settings = context.nvm.settings();
+                settings.reference_weight = weight;
+                context.nvm.write_settings(settings);
+                context.nvm.flush().await;
             } else {
-                defmt::error!("Not enough data points to calibrate");
+                defmt::error!("Reference weight out of range: {=f32}", weight);
+                error_log::record(ErrorCode::InvalidCommand);
+            }
+            if let Some(cb) = cb {
+                cb(valid);
+            }
+        }
+        Command::SetUnits(units) => {
+            if !units.is_valid() {
+                defmt::error!("Gravity out of range: {=f32}", units.gravity);
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            let mut settings = context.nvm.settings();
+            settings.set_units(units);
+            context.nvm.write_settings(settings);
+            context.nvm.flush().await;
+            super::set_units(units);
+        }
+        Command::SetBroadcast(enabled, interval_ms) => {
+            if let Some(interval_ms) = interval_ms.filter(|ms| !BROADCAST_INTERVAL_MS.contains(ms))
+            {
+                defmt::error!("Broadcast interval out of range: {=u16} ms", interval_ms);
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            let mut settings = context.nvm.settings();
+            settings.set_broadcast(enabled, interval_ms);
+            context.nvm.write_settings(settings);
+            context.nvm.flush().await;
+        }
+        Command::SetReconnectGrace(grace_s) => {
+            if grace_s > MAX_RECONNECT_GRACE_S {
+                defmt::error!("Reconnect grace period out of range: {=u16} s", grace_s);
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
             }
+            let mut settings = context.nvm.settings();
+            settings.reconnect_grace_s = grace_s;
+            context.nvm.write_settings(settings);
+            context.nvm.flush().await;
+        }
+        Command::SetOversampling(ratio) => {
+            if !matches!(context.state, MeasurementState::Idle) {
+                defmt::error!("Can't change oversampling while measuring");
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            let ratio = ratio.map(usize::from);
+            if ratio.is_some_and(|ratio| !super::is_valid_oversampling_ratio(ratio)) {
+                defmt::error!("Oversampling ratio out of range: {}", ratio);
+                error_log::record(ErrorCode::InvalidCommand);
+                return;
+            }
+            super::set_oversampling_ratio(ratio);
+            // Buffered sums over the old ratio would be compared with sums over the new one
+            for cell in context.cells {
+                cell.median.lock().await.reset();
+            }
+            let mut settings = context.nvm.settings();
+            settings.set_oversampling_ratio(ratio);
+            context.nvm.write_settings(settings);
+            context.nvm.flush().await;
+        }
+        Command::SaveErrorLog => {
+            context.nvm.write_error_log(&error_log::snapshot());
+            context.nvm.flush().await;
         }
     }
 }
 
+/// Reject a command that can only run while idle, and let the caller know through `cb`
+fn reject_while_measuring<T>(action: &str, cb: Option<Box<dyn FnOnce(Result<T, Rejected>)>>) {
+    defmt::error!("Can't {=str} while measuring", action);
+    error_log::record(ErrorCode::InvalidCommand);
+    if let Some(cb) = cb {
+        cb(Err(Rejected::Busy));
+    }
+}
+
 async fn measure(context: &mut MeasurementContext) {
+    let cell = context.selected();
     let MeasurementState::Active(ref mut sample_type, ref mut start_time) = context.state else {
         return;
     };
@@ -143,63 +785,180 @@ async fn measure(context: &mut MeasurementContext) {
         };
     match sample_type {
         SampleType::Raw(cb) => {
-            let Sample { timestamp, value } = context.adc.sample().await;
+            let Sample { timestamp, value } = cell.adc.sample().await;
             if let Some(cb) = cb {
                 cb(calculate_duration(timestamp), value);
             }
         }
         SampleType::FilteredRaw(cb) => {
-            let Sample { timestamp, value } = context.median.sample().await;
+            let Sample { timestamp, value } = cell.median.sample().await;
             if let Some(cb) = cb {
-                cb(calculate_duration(timestamp), value);
+                cb(calculate_duration(timestamp), value.mean());
             }
         }
         SampleType::Calibrated(cb) => {
-            let Sample { timestamp, value } = context.calibrator.sample().await;
+            let Sample { timestamp, value } = cell.calibrator.sample().await;
             if let Some(cb) = cb {
-                cb(calculate_duration(timestamp), value);
+                cb(calculate_duration(timestamp), super::units().convert(value));
             }
         }
         SampleType::Tared(cb) => {
-            let Sample { timestamp, value } = context.tarer.sample().await;
+            let (timestamp, values) = sample_all(&mut context.tarers).await;
             if let Some(cb) = cb {
-                cb(calculate_duration(timestamp), value);
+                cb(calculate_duration(timestamp), values.iter().sum());
+            }
+        }
+        SampleType::TaredCells(cb) => {
+            let (timestamp, values) = sample_all(&mut context.tarers).await;
+            if let Some(cb) = cb {
+                cb(
+                    calculate_duration(timestamp),
+                    values.iter().sum(),
+                    values.as_slice(),
+                );
             }
         }
     };
 }
 
+#[cfg(not(feature = "sim"))]
+fn into_adcs(
+    adc: HardwareAdc,
+    second_adc: Option<HardwareAdc>,
+    _sd: &'static Softdevice,
+) -> ArrayVec<Adc, MAX_CELLS> {
+    core::iter::once(adc).chain(second_adc).collect()
+}
+
+/// Swap out the real ADCs for simulated ones
+#[cfg(feature = "sim")]
+fn into_adcs(
+    mut adc: HardwareAdc,
+    second_adc: Option<HardwareAdc>,
+    sd: &'static Softdevice,
+) -> ArrayVec<Adc, MAX_CELLS> {
+    defmt::warn!("Using simulated ADC");
+    let n_cells = if second_adc.is_some() { 2 } else { 1 };
+    // Keep the real ADCs powered down and their pins configured for the rest of time
+    adc.power_down();
+    core::mem::forget(adc);
+    if let Some(mut second_adc) = second_adc {
+        second_adc.power_down();
+        core::mem::forget(second_adc);
+    }
+    (0..n_cells)
+        .map(|_| Adc::new(sd, 1.0 / n_cells as f32))
+        .collect()
+}
+
+/// Run the measurement task
+///
+/// `second_adc` is for scales built from two load cells, each with its own ADC. Their tared
+/// readings are summed.
+///
+/// With the `sim` feature enabled, the given ADCs are powered down and replaced with simulated ones.
 #[embassy_executor::task]
-pub async fn task_function(rx: MeasureCommandReceiver, adc: Adc, sd: &'static Softdevice) {
+pub async fn task_function(
+    rx: MeasureCommandReceiver,
+    adc: HardwareAdc,
+    second_adc: Option<HardwareAdc>,
+    sd: &'static Softdevice,
+) {
     defmt::debug!("Starting measurement task");
-    let adc: &SharedAdc = make_static!(SharedAdc, Mutex::new(adc));
-    let median: &'static SharedFilteredAdc =
-        make_static!(SharedFilteredAdc, Mutex::new(Median::new(adc)));
-
-    let nvm = Nvm::new(sd);
-    let cal_m = nvm.read_cal_m();
-    let cal_b = nvm.read_cal_b();
-    defmt::info!("Loaded calibration: m={=f32} b={=i32}", cal_m, cal_b);
-    let calibrator: &SharedCalibrator = make_static!(
-        SharedCalibrator,
-        Mutex::new(Calibrator::new(median, cal_m, cal_b))
+    let adcs: &'static [SharedAdc] = make_static!(
+        ArrayVec<SharedAdc, MAX_CELLS>,
+        into_adcs(adc, second_adc, sd)
+            .into_iter()
+            .map(Mutex::new)
+            .collect()
+    );
+    let medians: &'static [SharedFilteredAdc] = make_static!(
+        ArrayVec<SharedFilteredAdc, MAX_CELLS>,
+        adcs.iter()
+            .map(|adc| Mutex::new(Median::new(Oversampler::new(adc))))
+            .collect()
+    );
+
+    let mut nvm = Nvm::new(sd);
+    nvm.record_boot();
+    defmt::info!("Boot count: {=u32}", nvm.boot_count());
+    error_log::restore(&nvm.error_log(), nvm.boot_count());
+    let units = nvm.settings().units();
+    defmt::info!("Units: {}", units);
+    super::set_units(units);
+    match nvm.settings().oversampling_ratio() {
+        Some(ratio) if super::is_valid_oversampling_ratio(ratio) => {
+            super::set_oversampling_ratio(Some(ratio));
+        }
+        Some(ratio) => defmt::warn!("Ignoring invalid oversampling ratio {=usize}", ratio),
+        None => (),
+    }
+    #[cfg(feature = "sim")]
+    for (i, adc) in adcs.iter().enumerate() {
+        adc.lock()
+            .await
+            .set_calibration(nvm.read_cal_m(i), nvm.read_cal_b(i));
+    }
+    let calibrators: &'static [SharedCalibrator] = make_static!(
+        ArrayVec<SharedCalibrator, MAX_CELLS>,
+        medians
+            .iter()
+            .enumerate()
+            .map(|(i, median)| {
+                let cal_m = nvm.read_cal_m(i);
+                let cal_b = nvm.read_cal_b(i);
+                let unloading = nvm.read_unloading_cal(i);
+                defmt::info!(
+                    "Loaded calibration for load cell {=usize}: m={=f32} b={=i32} unloading: {}",
+                    i,
+                    cal_m,
+                    cal_b,
+                    unloading
+                );
+                Mutex::new(Calibrator::new(median, cal_m, cal_b, unloading))
+            })
+            .collect()
     );
+    let cells: &'static [Cell] = make_static!(
+        ArrayVec<Cell, MAX_CELLS>,
+        adcs.iter()
+            .zip(medians)
+            .zip(calibrators)
+            .map(|((adc, median), calibrator)| Cell {
+                adc,
+                median,
+                calibrator,
+            })
+            .collect()
+    );
+
+    let tarers = calibrators
+        .iter()
+        .enumerate()
+        .map(|(i, calibrator)| {
+            let creep = nvm.read_creep(i);
+            defmt::info!("Creep compensation for load cell {=usize}: {}", i, creep);
+            CreepCompensator::new(Tarer::new(calibrator), creep)
+        })
+        .collect();
 
-    let tarer = Tarer::new(calibrator);
     let mut context = MeasurementContext {
+        sd,
         state: MeasurementState::Idle,
-        adc,
-        median,
-        calibrator,
-        tarer,
+        cells,
+        tarers,
+        selected_cell: 0,
         nvm,
         factory_cal: TwoPoint::default(),
+        unloading_cal: TwoPoint::default(),
+        point_std_devs: Default::default(),
     };
+    run_self_test(&context).await;
 
     loop {
         if let Ok(cmd) = rx.try_receive() {
             defmt::info!("Measure task received command: {}", cmd);
-            handle_command(cmd, &mut context, adc).await;
+            handle_command(cmd, &mut context).await;
         }
         if let MeasurementState::Active(..) = context.state {
             measure(&mut context).await;
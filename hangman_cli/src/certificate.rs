@@ -0,0 +1,190 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export and rendering of calibration certificates
+//!
+//! See `hangman_utils::certificate` for the format.
+
+use crate::protocol::{self, Command};
+use crate::transport::Transport;
+use hangman_utils::certificate::{self, Certificate, DecodeError};
+use hangman_utils::hysteresis::Branch;
+use std::fmt::{self, Write};
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Protocol(protocol::Error),
+    Decode(DecodeError),
+    /// A chunk doesn't fit in the certificate or is out of order
+    UnexpectedChunk,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Protocol(e) => write!(f, "invalid response: {e}"),
+            Error::Decode(e) => write!(f, "invalid certificate: {e:?}"),
+            Error::UnexpectedChunk => write!(f, "unexpected part of a certificate"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<protocol::Error> for Error {
+    fn from(e: protocol::Error) -> Self {
+        Error::Protocol(e)
+    }
+}
+
+/// Decode an encoded certificate, e.g. as printed by the `certificate` console command
+pub fn decode(data: &[u8]) -> Result<Option<Certificate>, Error> {
+    Certificate::decode(data).map_err(Error::Decode)
+}
+
+/// Fetch the calibration certificates of all load cells that have one
+pub fn fetch<T: Transport>(transport: &mut T) -> Result<Vec<Certificate>, Error> {
+    transport.write_control(&Command::GetCertificates.encode())?;
+    let mut certificates = Vec::new();
+    let mut encoded = Vec::with_capacity(certificate::SIZE);
+    loop {
+        let chunk = protocol::parse_certificate_chunk(&transport.read_data()?)?;
+        if chunk.count == 0 {
+            return Ok(certificates);
+        }
+        if usize::from(chunk.offset) != encoded.len()
+            || encoded.len() + chunk.data.len() > certificate::SIZE
+        {
+            return Err(Error::UnexpectedChunk);
+        }
+        encoded.extend_from_slice(&chunk.data);
+        if encoded.len() == certificate::SIZE {
+            // The device only sends cells that have a certificate
+            let certificate = decode(&encoded)?.ok_or(Error::UnexpectedChunk)?;
+            certificates.push(certificate);
+            encoded.clear();
+            if usize::from(chunk.count) == certificates.len() {
+                return Ok(certificates);
+            }
+        }
+    }
+}
+
+/// Human-readable report of a certificate
+pub fn render(certificate: &Certificate) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = write_report(certificate, &mut out);
+    out
+}
+
+fn write_report(certificate: &Certificate, out: &mut String) -> fmt::Result {
+    writeln!(out, "Calibration certificate")?;
+    writeln!(out, "  Device ID:        {}", certificate.device_id)?;
+    writeln!(
+        out,
+        "  Firmware version: {}",
+        certificate.firmware_version()
+    )?;
+    writeln!(out, "  Load cell:        {}", certificate.cell)?;
+    writeln!(out, "  Boot count:       {}", certificate.boot_count)?;
+    match certificate.temperature_c() {
+        Some(temperature) => writeln!(out, "  Temperature:      {temperature:.2} °C")?,
+        None => writeln!(out, "  Temperature:      unknown")?,
+    }
+    writeln!(
+        out,
+        "  Calibration:      weight = {:e} kg × (reading - {})",
+        certificate.m, certificate.b
+    )?;
+    writeln!(out, "  Points:")?;
+    writeln!(
+        out,
+        "    {:<10} {:>14} {:>10} {:>13} {:>14}",
+        "Branch", "Reference (kg)", "Reading", "Std dev (kg)", "Residual (kg)"
+    )?;
+    for point in certificate.points() {
+        let branch = match point.branch {
+            Branch::Loading => "loading",
+            Branch::Unloading => "unloading",
+        };
+        writeln!(
+            out,
+            "    {:<10} {:>14.3} {:>10} {:>13.4} {:>14.4}",
+            branch, point.weight, point.reading, point.std_dev_kg, point.residual_kg
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim::SimulatedDevice;
+    use crate::wizard::{self, Ui};
+
+    struct Silent;
+
+    impl Ui for Silent {
+        fn prompt(&mut self, _instruction: &str) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn message(&mut self, _message: &str) {}
+    }
+
+    #[test]
+    fn fetch_after_calibration() {
+        let mut device = SimulatedDevice::new(2.5e-5, 12_345);
+        assert!(fetch(&mut device).unwrap().is_empty());
+
+        wizard::calibrate(&mut device, &mut Silent, Some(1), 20.0).unwrap();
+        wizard::calibrate(&mut device, &mut Silent, Some(0), 30.0).unwrap();
+        let certificates = fetch(&mut device).unwrap();
+        assert_eq!(certificates.len(), 2);
+        let certificate = certificates.iter().find(|c| c.cell == 1).unwrap();
+        assert_eq!(certificate.points().len(), 2);
+        assert_eq!(certificate.points()[1].weight, 20.0);
+        assert_eq!(certificate.b, 12_345);
+
+        let report = render(certificate);
+        assert!(report.contains("Load cell:        1"), "{report}");
+        assert!(report.contains("loading"), "{report}");
+    }
+
+    #[test]
+    fn unexpected_chunk() {
+        struct Scripted(Vec<Vec<u8>>);
+        impl Transport for Scripted {
+            fn write_control(&mut self, _payload: &[u8]) -> io::Result<()> {
+                Ok(())
+            }
+            fn read_data(&mut self) -> io::Result<Vec<u8>> {
+                Ok(self.0.remove(0))
+            }
+        }
+        // The first chunk is missing
+        let mut transport = Scripted(vec![vec![0x00, 0x04, 1, 0, 25, 0xFF]]);
+        assert!(matches!(fetch(&mut transport), Err(Error::UnexpectedChunk)));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use hangman_cli::certificate;
+use hangman_cli::sim::SimulatedDevice;
+use hangman_cli::transport::{self, ManualTransport, Transport};
+use hangman_cli::wizard::{self, TerminalUi};
+use hangman_utils::units::{Unit, Units};
+use std::io;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: hangman-cli calibrate <weight> [kg|lb] [--cell <n>] [--simulate]
+       hangman-cli certificate [<hex>] [--simulate]
+
+calibrate    Calibrates a load cell with a reference weight.
+certificate  Shows the calibration certificates of all load cells. Pass the hex printed by the
+             `certificate` console command to decode it instead of asking the device.
+
+Payloads are printed for you to write with a BLE app such as nRF Connect, and the app's responses
+are pasted back. With --simulate, a simulated device responds instead.";
+
+enum Command {
+    Calibrate { reference_kg: f32, cell: Option<u8> },
+    Certificate { encoded: Option<Vec<u8>> },
+}
+
+struct Args {
+    command: Command,
+    simulate: bool,
+}
+
+fn parse_calibrate(args: &[String]) -> Result<Command, String> {
+    let mut weight = None;
+    let mut unit = Unit::Kilogram;
+    let mut cell = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cell" => {
+                let value = args.next().ok_or("Missing load cell")?;
+                cell = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid load cell: {value}"))?,
+                );
+            }
+            _ if weight.is_none() => {
+                weight = Some(
+                    arg.parse::<f32>()
+                        .map_err(|_| format!("Invalid weight: {arg}"))?,
+                );
+            }
+            _ => {
+                unit = Unit::from_symbol(arg)
+                    .filter(|unit| matches!(unit, Unit::Kilogram | Unit::Pound))
+                    .ok_or_else(|| format!("Invalid unit: {arg}"))?;
+            }
+        }
+    }
+    let weight = weight.ok_or("Missing weight")?;
+    let units = Units {
+        unit,
+        ..Units::DEFAULT
+    };
+    Ok(Command::Calibrate {
+        reference_kg: units.to_kg(weight),
+        cell,
+    })
+}
+
+fn parse_certificate(args: &[String]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Certificate { encoded: None }),
+        [hex] => {
+            let encoded = transport::parse_hex(hex).ok_or_else(|| format!("Invalid hex: {hex}"))?;
+            Ok(Command::Certificate {
+                encoded: Some(encoded),
+            })
+        }
+        _ => Err("Too many arguments".into()),
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut simulate = false;
+    let mut args: Vec<String> = args
+        .filter(|arg| {
+            let is_simulate = arg == "--simulate";
+            simulate |= is_simulate;
+            !is_simulate
+        })
+        .collect();
+    if args.is_empty() {
+        return Err("Missing command".into());
+    }
+    let command = match args.remove(0).as_str() {
+        "calibrate" => parse_calibrate(&args)?,
+        "certificate" => parse_certificate(&args)?,
+        other => return Err(format!("Unknown command: {other}")),
+    };
+    Ok(Args { command, simulate })
+}
+
+fn calibrate(transport: &mut impl Transport, reference_kg: f32, cell: Option<u8>) -> ExitCode {
+    let mut ui = TerminalUi::new(io::stdin(), io::stdout());
+    match wizard::calibrate(transport, &mut ui, cell, reference_kg) {
+        Ok(calibration) => {
+            println!(
+                "Calibration saved: m = {:e}, b = {}",
+                calibration.m, calibration.b
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Calibration failed: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn show_certificates(transport: &mut impl Transport, encoded: Option<&[u8]>) -> ExitCode {
+    let certificates = match encoded {
+        Some(encoded) => {
+            certificate::decode(encoded).map(|certificate| certificate.into_iter().collect())
+        }
+        None => certificate::fetch(transport),
+    };
+    match certificates {
+        Ok(certificates) if certificates.is_empty() => {
+            println!("No calibration certificates");
+            ExitCode::SUCCESS
+        }
+        Ok(certificates) => {
+            for certificate in &certificates {
+                println!("{}", certificate::render(certificate));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to get calibration certificates: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(transport: &mut impl Transport, command: &Command) -> ExitCode {
+    match command {
+        Command::Calibrate { reference_kg, cell } => calibrate(transport, *reference_kg, *cell),
+        Command::Certificate { encoded } => show_certificates(transport, encoded.as_deref()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.simulate {
+        run(&mut SimulatedDevice::default(), &args.command)
+    } else {
+        run(
+            &mut ManualTransport::new(io::stdin(), io::stdout()),
+            &args.command,
+        )
+    }
+}
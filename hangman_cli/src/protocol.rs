@@ -0,0 +1,356 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payloads of the Progressor service, as implemented by `hangman::ble::gatt_types`
+//!
+//! Commands are written to the control characteristic. Responses are notified on the data
+//! characteristic as an opcode byte, a length byte, and the payload. All values are
+//! little-endian.
+
+use hangman_utils::two_point_cal::Rejection;
+use std::fmt;
+
+/// Characteristic that commands are written to
+pub const CONTROL_UUID: &str = "7e4e1703-1ea6-40c9-9dcc-13d34ffead57";
+/// Characteristic that responses are notified on
+pub const DATA_UUID: &str = "7e4e1702-1ea6-40c9-9dcc-13d34ffead57";
+
+/// Data opcode of responses to commands
+const RESPONSE_OPCODE: u8 = 0x00;
+/// Data opcode of responses to commands that were rejected without running
+const REJECTED_OPCODE: u8 = 0x80;
+/// Rejection reason: the device is measuring
+const BUSY_CODE: u8 = 0x01;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Capture a point of the calibration curve with the given weight in kg hanging from the scale
+    AddCalibrationPoint(f32),
+    SaveCalibration,
+    GetCalibrationCurve,
+    /// Select the load cell that calibration commands apply to
+    SelectCell(u8),
+    GetCertificates,
+}
+
+impl Command {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Command::AddCalibrationPoint(weight) => {
+                let mut payload = vec![0x69, 4];
+                payload.extend_from_slice(&weight.to_le_bytes());
+                payload
+            }
+            Command::SaveCalibration => vec![0x6A],
+            Command::GetCalibrationCurve => vec![0x72],
+            Command::SelectCell(cell) => vec![0xA1, *cell],
+            Command::GetCertificates => vec![0xAC],
+        }
+    }
+}
+
+/// Averaged reading captured for a calibration point
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CalibrationPoint {
+    pub reading: i32,
+    /// Standard deviation of the averaged readings, in counts
+    pub std_dev: f32,
+    /// Standard deviation of the averaged readings, converted to kg with the previous calibration
+    pub std_dev_kg: f32,
+    pub n_samples: u32,
+}
+
+/// Part of a calibration certificate, as encoded by `hangman_utils::certificate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CertificateChunk {
+    /// Number of certificates being sent
+    pub count: u8,
+    pub cell: u8,
+    /// Offset of `data` in the encoded certificate
+    pub offset: u8,
+    pub data: Vec<u8>,
+}
+
+/// Malformed or unexpected response
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Shorter than the opcode and length bytes, or than the length byte says
+    Truncated,
+    UnexpectedOpcode(u8),
+    UnexpectedLength {
+        expected: usize,
+        actual: usize,
+    },
+    /// A status or rejection code that this tool doesn't know about
+    UnknownCode(u8),
+    /// The device is measuring and didn't run the command
+    Busy,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "truncated response"),
+            Error::UnexpectedOpcode(opcode) => write!(f, "unexpected opcode 0x{opcode:02X}"),
+            Error::UnexpectedLength { expected, actual } => {
+                write!(f, "expected {expected} bytes of payload, got {actual}")
+            }
+            Error::UnknownCode(code) => write!(f, "unknown code 0x{code:02X}"),
+            Error::Busy => write!(f, "device is busy measuring"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Extract the payload of a response
+fn any_payload(data: &[u8]) -> Result<&[u8], Error> {
+    let [opcode, length, payload @ ..] = data else {
+        return Err(Error::Truncated);
+    };
+    if *opcode == REJECTED_OPCODE {
+        // The rejected command's opcode, followed by the reason
+        return match payload.get(..usize::from(*length)) {
+            Some([_, BUSY_CODE]) => Err(Error::Busy),
+            Some([_, code]) => Err(Error::UnknownCode(*code)),
+            _ => Err(Error::Truncated),
+        };
+    }
+    if *opcode != RESPONSE_OPCODE {
+        return Err(Error::UnexpectedOpcode(*opcode));
+    }
+    payload.get(..usize::from(*length)).ok_or(Error::Truncated)
+}
+
+/// Extract the payload of a response, checking its length
+fn payload(data: &[u8], expected: usize) -> Result<&[u8], Error> {
+    let payload = any_payload(data)?;
+    if payload.len() != expected {
+        return Err(Error::UnexpectedLength {
+            expected,
+            actual: payload.len(),
+        });
+    }
+    Ok(payload)
+}
+
+fn le_bytes<const N: usize>(payload: &[u8], offset: usize) -> [u8; N] {
+    payload[offset..offset + N].try_into().unwrap()
+}
+
+/// Parse the response to `AddCalibrationPoint`: `None` if the weight didn't settle in time
+pub fn parse_calibration_point(data: &[u8]) -> Result<Option<CalibrationPoint>, Error> {
+    let payload = payload(data, 17)?;
+    match payload[0] {
+        0 => Ok(Some(CalibrationPoint {
+            reading: i32::from_le_bytes(le_bytes(payload, 1)),
+            std_dev: f32::from_le_bytes(le_bytes(payload, 5)),
+            std_dev_kg: f32::from_le_bytes(le_bytes(payload, 9)),
+            n_samples: u32::from_le_bytes(le_bytes(payload, 13)),
+        })),
+        1 => Ok(None),
+        code => Err(Error::UnknownCode(code)),
+    }
+}
+
+/// Parse the response to `SaveCalibration`
+pub fn parse_save_calibration(data: &[u8]) -> Result<Result<(), Rejection>, Error> {
+    match payload(data, 1)?[0] {
+        0 => Ok(Ok(())),
+        code => Rejection::from_code(code)
+            .map(Err)
+            .ok_or(Error::UnknownCode(code)),
+    }
+}
+
+/// Parse the response to `GetCalibrationCurve` into `m` and `b`
+///
+/// The layout is Hangman's own. A Progressor's calibration curve is opaque and can't be parsed.
+pub fn parse_calibration_curve(data: &[u8]) -> Result<(f32, i32), Error> {
+    // The last four bytes are reserved
+    let payload = payload(data, 12)?;
+    Ok((
+        f32::from_le_bytes(le_bytes(payload, 0)),
+        i32::from_le_bytes(le_bytes(payload, 4)),
+    ))
+}
+
+/// Parse a response to `GetCertificates`. If there are no certificates, a single chunk with a
+/// count of zero and no data is sent.
+pub fn parse_certificate_chunk(data: &[u8]) -> Result<CertificateChunk, Error> {
+    match any_payload(data)? {
+        [0] => Ok(CertificateChunk {
+            count: 0,
+            cell: 0,
+            offset: 0,
+            data: Vec::new(),
+        }),
+        [count, cell, offset, data @ ..] if *count > 0 => Ok(CertificateChunk {
+            count: *count,
+            cell: *cell,
+            offset: *offset,
+            data: data.to_vec(),
+        }),
+        _ => Err(Error::Truncated),
+    }
+}
+
+/// Response frames, as sent by the device. Used by the simulated device.
+pub(crate) mod response {
+    use super::*;
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![RESPONSE_OPCODE, payload.len() as u8];
+        data.extend_from_slice(payload);
+        data
+    }
+
+    pub(crate) fn calibration_point(point: Option<CalibrationPoint>) -> Vec<u8> {
+        let mut payload = [0; 17];
+        match point {
+            Some(point) => {
+                payload[1..5].copy_from_slice(&point.reading.to_le_bytes());
+                payload[5..9].copy_from_slice(&point.std_dev.to_le_bytes());
+                payload[9..13].copy_from_slice(&point.std_dev_kg.to_le_bytes());
+                payload[13..17].copy_from_slice(&point.n_samples.to_le_bytes());
+            }
+            None => payload[0] = 1,
+        }
+        frame(&payload)
+    }
+
+    pub(crate) fn save_calibration(result: Result<(), Rejection>) -> Vec<u8> {
+        frame(&[result.err().map_or(0, Rejection::code)])
+    }
+
+    pub(crate) fn calibration_curve(m: f32, b: i32) -> Vec<u8> {
+        let mut payload = [0; 12];
+        payload[0..4].copy_from_slice(&m.to_le_bytes());
+        payload[4..8].copy_from_slice(&b.to_le_bytes());
+        frame(&payload)
+    }
+
+    /// Responses to `GetCertificates` for the given encoded certificates
+    pub(crate) fn certificates(certificates: &[(u8, &[u8])]) -> Vec<Vec<u8>> {
+        let count = certificates.len() as u8;
+        if certificates.is_empty() {
+            return vec![frame(&[count])];
+        }
+        certificates
+            .iter()
+            .flat_map(|(cell, encoded)| {
+                encoded.chunks(25).enumerate().map(move |(i, chunk)| {
+                    let mut payload = vec![count, *cell, (i * 25) as u8];
+                    payload.extend_from_slice(chunk);
+                    frame(&payload)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            Command::AddCalibrationPoint(20.0).encode(),
+            [0x69, 0x04, 0x00, 0x00, 0xA0, 0x41]
+        );
+        assert_eq!(Command::SaveCalibration.encode(), [0x6A]);
+        assert_eq!(Command::GetCalibrationCurve.encode(), [0x72]);
+        assert_eq!(Command::SelectCell(1).encode(), [0xA1, 0x01]);
+        assert_eq!(Command::GetCertificates.encode(), [0xAC]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let point = CalibrationPoint {
+            reading: -123_456,
+            std_dev: 12.5,
+            std_dev_kg: 0.01,
+            n_samples: 80,
+        };
+        assert_eq!(
+            parse_calibration_point(&response::calibration_point(Some(point))),
+            Ok(Some(point))
+        );
+        assert_eq!(
+            parse_calibration_point(&response::calibration_point(None)),
+            Ok(None)
+        );
+        assert_eq!(
+            parse_save_calibration(&response::save_calibration(Err(Rejection::WrongSign))),
+            Ok(Err(Rejection::WrongSign))
+        );
+        assert_eq!(
+            parse_calibration_curve(&response::calibration_curve(1e-5, -42)),
+            Ok((1e-5, -42))
+        );
+    }
+
+    #[test]
+    fn certificate_chunks() {
+        let encoded: Vec<u8> = (0..30).collect();
+        let responses = response::certificates(&[(1, &encoded)]);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            parse_certificate_chunk(&responses[1]),
+            Ok(CertificateChunk {
+                count: 1,
+                cell: 1,
+                offset: 25,
+                data: vec![25, 26, 27, 28, 29],
+            })
+        );
+        let responses = response::certificates(&[]);
+        assert_eq!(responses, [[0x00, 0x01, 0x00]]);
+        assert_eq!(parse_certificate_chunk(&responses[0]).unwrap().count, 0);
+        assert_eq!(
+            parse_certificate_chunk(&[0x00, 0x02, 0x01, 0x00]),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(parse_save_calibration(&[0x00]), Err(Error::Truncated));
+        assert_eq!(parse_save_calibration(&[0x00, 2, 0]), Err(Error::Truncated));
+        assert_eq!(
+            parse_save_calibration(&[0x01, 1, 0]),
+            Err(Error::UnexpectedOpcode(0x01))
+        );
+        assert_eq!(
+            parse_save_calibration(&[0x00, 0]),
+            Err(Error::UnexpectedLength {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            parse_save_calibration(&[0x00, 1, 0x7F]),
+            Err(Error::UnknownCode(0x7F))
+        );
+        assert_eq!(
+            parse_calibration_point(&[0x80, 2, 0x69, 0x01]),
+            Err(Error::Busy)
+        );
+        assert_eq!(
+            parse_save_calibration(&[0x00, 1, 0x01]),
+            Ok(Err(Rejection::MissingPoint))
+        );
+    }
+}
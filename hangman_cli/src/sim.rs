@@ -0,0 +1,205 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simulated device for trying out the tools without a scale
+//!
+//! Implements the calibration commands like the firmware does, using the same validation from
+//! `hangman_utils`. Readings are derived from a fixed "true" calibration of the load cell.
+
+use crate::protocol::{response, CalibrationPoint};
+use crate::transport::Transport;
+use hangman_utils::certificate::Certificate;
+use hangman_utils::hysteresis::Branch;
+use hangman_utils::two_point_cal::{CalPoint, Limits, TwoPoint};
+use std::collections::VecDeque;
+use std::io;
+
+/// Same as the firmware's `weight::CAPACITY_KG`
+const CAPACITY_KG: f32 = 150.0;
+/// Same range as the HX711
+const FULL_SCALE: i32 = (1 << 23) - 1;
+/// Same as the firmware's `weight::DEFAULT_CALIBRATION_M` and `weight::DEFAULT_CALIBRATION_B`
+const DEFAULT_CALIBRATION_M: f32 = 4.675_038e-6;
+const DEFAULT_CALIBRATION_B: i32 = -100_598;
+const N_SAMPLES: u32 = 80;
+const STD_DEV: f32 = 20.0;
+const DEVICE_ID: u64 = 42;
+const FIRMWARE_VERSION: &str = "sim";
+const TEMPERATURE_C: f32 = 21.0;
+
+/// Same as the firmware's `calibrate::limits`
+fn limits() -> Limits {
+    let full_scale = FULL_SCALE as f32;
+    Limits {
+        capacity: CAPACITY_KG,
+        min_m: CAPACITY_KG / 20.0 / full_scale,
+        max_m: CAPACITY_KG * 100.0 / full_scale,
+    }
+}
+
+pub struct SimulatedDevice {
+    /// Calibration of the simulated load cell, used to generate readings
+    true_m: f32,
+    true_b: i32,
+    /// Weights actually hanging from the scale for successive calibration points. The weights that
+    /// the client claims are used once these run out.
+    actual_weights: VecDeque<f32>,
+    cell: u8,
+    calibration: TwoPoint<i32>,
+    /// Calibration stored on the device
+    m: f32,
+    b: i32,
+    /// Certificate of the most recent calibration of each load cell
+    certificates: Vec<Certificate>,
+    responses: VecDeque<Vec<u8>>,
+}
+
+impl Default for SimulatedDevice {
+    fn default() -> Self {
+        Self::new(2.5e-5, 12_345)
+    }
+}
+
+impl SimulatedDevice {
+    pub fn new(true_m: f32, true_b: i32) -> Self {
+        Self {
+            true_m,
+            true_b,
+            actual_weights: VecDeque::new(),
+            cell: 0,
+            calibration: TwoPoint::default(),
+            m: DEFAULT_CALIBRATION_M,
+            b: DEFAULT_CALIBRATION_B,
+            certificates: Vec::new(),
+            responses: VecDeque::new(),
+        }
+    }
+
+    /// Hang different weights than the client claims, e.g. to simulate a mistake by the user
+    pub fn with_actual_weights(mut self, weights: &[f32]) -> Self {
+        self.actual_weights = weights.iter().copied().collect();
+        self
+    }
+
+    /// Load cell selected by the client
+    pub fn cell(&self) -> u8 {
+        self.cell
+    }
+
+    /// Calibration constants stored on the device
+    pub fn calibration(&self) -> (f32, i32) {
+        (self.m, self.b)
+    }
+
+    fn add_calibration_point(&mut self, claimed: f32) -> Vec<u8> {
+        let actual = self.actual_weights.pop_front().unwrap_or(claimed);
+        let reading = (actual / self.true_m) as i32 + self.true_b;
+        self.calibration.add_point(CalPoint {
+            expected_value: claimed,
+            measured_value: reading,
+        });
+        response::calibration_point(Some(CalibrationPoint {
+            reading,
+            std_dev: STD_DEV,
+            std_dev_kg: STD_DEV * self.m,
+            n_samples: N_SAMPLES,
+        }))
+    }
+
+    fn save_calibration(&mut self) -> Vec<u8> {
+        let result = self
+            .calibration
+            .get_validated_cal_constants(&limits())
+            .map(|constants| {
+                self.m = constants.m;
+                self.b = constants.b;
+            });
+        if result.is_ok() {
+            self.write_certificate();
+        }
+        response::save_calibration(result)
+    }
+
+    fn write_certificate(&mut self) {
+        let mut certificate = Certificate::new(
+            self.cell,
+            1,
+            DEVICE_ID,
+            FIRMWARE_VERSION,
+            Some(TEMPERATURE_C),
+            self.m,
+            self.b,
+        );
+        // Validation guarantees that both points exist
+        let reference = self.calibration.reference().unwrap();
+        certificate.add_point(
+            Branch::Loading,
+            0.0,
+            self.calibration.zero().unwrap(),
+            STD_DEV,
+        );
+        certificate.add_point(
+            Branch::Loading,
+            reference.expected_value,
+            reference.measured_value,
+            STD_DEV,
+        );
+        self.certificates.retain(|c| c.cell != self.cell);
+        self.certificates.push(certificate);
+    }
+}
+
+impl Transport for SimulatedDevice {
+    fn write_control(&mut self, payload: &[u8]) -> io::Result<()> {
+        let response = match payload {
+            [0x69, 4, weight @ ..] | [0x69, weight @ ..] if weight.len() == 4 => {
+                self.add_calibration_point(f32::from_le_bytes(weight.try_into().unwrap()))
+            }
+            [0x6A] => self.save_calibration(),
+            [0x72] => response::calibration_curve(self.m, self.b),
+            [0xAC] => {
+                let encoded: Vec<_> = self
+                    .certificates
+                    .iter()
+                    .map(|certificate| (certificate.cell, certificate.encode()))
+                    .collect();
+                let encoded: Vec<_> = encoded
+                    .iter()
+                    .map(|(cell, data)| (*cell, data.as_slice()))
+                    .collect();
+                self.responses.extend(response::certificates(&encoded));
+                return Ok(());
+            }
+            [0xA1, cell] => {
+                self.cell = *cell;
+                self.calibration = TwoPoint::default();
+                return Ok(());
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported command {payload:02X?}"),
+                ))
+            }
+        };
+        self.responses.push_back(response);
+        Ok(())
+    }
+
+    fn read_data(&mut self) -> io::Result<Vec<u8>> {
+        self.responses
+            .pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "no response"))
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ways of talking to a device
+
+use crate::protocol::{CONTROL_UUID, DATA_UUID};
+use std::io::{self, BufRead, Write};
+
+/// Link to the Progressor service of a device
+pub trait Transport {
+    /// Write to the control characteristic
+    fn write_control(&mut self, payload: &[u8]) -> io::Result<()>;
+    /// Wait for the next notification on the data characteristic
+    fn read_data(&mut self) -> io::Result<Vec<u8>>;
+}
+
+/// Source of lines typed by the user
+///
+/// Implemented for `Stdin` rather than `StdinLock` so that several readers can share the terminal.
+pub trait Input {
+    /// Read a line, without the line ending. Fails at the end of the input.
+    fn read_line(&mut self) -> io::Result<String>;
+}
+
+fn read_line_from(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end().to_owned())
+}
+
+impl Input for io::Stdin {
+    fn read_line(&mut self) -> io::Result<String> {
+        read_line_from(&mut self.lock())
+    }
+}
+
+impl Input for &[u8] {
+    fn read_line(&mut self) -> io::Result<String> {
+        read_line_from(self)
+    }
+}
+
+/// Parse bytes in hex, as shown by apps like nRF Connect, e.g. `(0x) 00-01-FF` or `00 01 ff`
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let text = text.strip_prefix("(0x)").unwrap_or(text);
+    let text = text.trim_start().strip_prefix("0x").unwrap_or(text);
+    let digits: Vec<char> = text
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | ':'))
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Has the user relay payloads through a generic BLE app such as nRF Connect
+pub struct ManualTransport<I, W> {
+    input: I,
+    output: W,
+}
+
+impl<I: Input, W: Write> ManualTransport<I, W> {
+    pub fn new(input: I, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<I: Input, W: Write> Transport for ManualTransport<I, W> {
+    fn write_control(&mut self, payload: &[u8]) -> io::Result<()> {
+        writeln!(
+            self.output,
+            "Write {} to characteristic {CONTROL_UUID}",
+            to_hex(payload)
+        )
+    }
+
+    fn read_data(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            write!(
+                self.output,
+                "Paste the value notified on characteristic {DATA_UUID}: "
+            )?;
+            self.output.flush()?;
+            let line = self.input.read_line()?;
+            match parse_hex(&line) {
+                Some(data) if !data.is_empty() => return Ok(data),
+                _ => writeln!(self.output, "Not a hex value: {line}")?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse_hex("(0x) 00-01-FF"), Some(vec![0x00, 0x01, 0xFF]));
+        assert_eq!(parse_hex("0x0001ff"), Some(vec![0x00, 0x01, 0xFF]));
+        assert_eq!(parse_hex("00 01 ff\n"), Some(vec![0x00, 0x01, 0xFF]));
+        assert_eq!(parse_hex("0"), None);
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(to_hex(&[0x69, 0x04, 0xA0]), "69-04-A0");
+    }
+
+    #[test]
+    fn manual() {
+        let mut output = Vec::new();
+        let mut transport = ManualTransport::new(&b"oops\n00-01-00\n"[..], &mut output);
+        transport.write_control(&[0x6A]).unwrap();
+        assert_eq!(transport.read_data().unwrap(), [0x00, 0x01, 0x00]);
+        assert!(transport.read_data().is_err());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(&format!("Write 6A to characteristic {CONTROL_UUID}")));
+        assert!(output.contains("Not a hex value: oops"));
+    }
+}
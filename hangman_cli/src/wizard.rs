@@ -0,0 +1,252 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Two-point calibration, step by step
+//!
+//! Walks the user through the same procedure as the "Calibration" chapter of the docs, but builds
+//! the payloads and checks the responses itself.
+
+use crate::protocol::{self, CalibrationPoint, Command};
+use crate::transport::{Input, Transport};
+use hangman_utils::two_point_cal::Rejection;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Standard deviation of a calibration point above which the weight probably wasn't still
+const MAX_STD_DEV_KG: f32 = 0.05;
+
+/// Interaction with the user between steps
+pub trait Ui {
+    /// Ask the user to do something and wait until they're done
+    fn prompt(&mut self, instruction: &str) -> io::Result<()>;
+    fn message(&mut self, message: &str);
+}
+
+/// Prompts on the terminal, continuing when the user presses Enter
+pub struct TerminalUi<I, W> {
+    input: I,
+    output: W,
+}
+
+impl<I: Input, W: Write> TerminalUi<I, W> {
+    pub fn new(input: I, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<I: Input, W: Write> Ui for TerminalUi<I, W> {
+    fn prompt(&mut self, instruction: &str) -> io::Result<()> {
+        write!(self.output, "{instruction}, then press Enter")?;
+        self.output.flush()?;
+        self.input.read_line().map(drop)
+    }
+
+    fn message(&mut self, message: &str) {
+        // Nothing useful to do if the terminal is gone
+        let _ = writeln!(self.output, "{message}");
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Protocol(protocol::Error),
+    /// The weight didn't settle in time while capturing the named point
+    NotSettled(&'static str),
+    Rejected(Rejection),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Protocol(e) => write!(f, "invalid response: {e}"),
+            Error::NotSettled(point) => write!(
+                f,
+                "the weight didn't settle while capturing the {point} point. Make sure that \
+                 nothing is touching the scale."
+            ),
+            Error::Rejected(rejection) => write!(f, "the device rejected the calibration: {}", {
+                match rejection {
+                    Rejection::MissingPoint => "a point is missing",
+                    Rejection::DegenerateReadings => "both points have the same reading",
+                    Rejection::WeightOutOfRange => "the reference weight is out of range",
+                    Rejection::WrongSign => {
+                        "the readings decreased with weight. Check the load cell's wiring."
+                    }
+                    Rejection::SlopeOutOfRange => {
+                        "the readings changed too much or too little. Check the reference weight."
+                    }
+                }
+            }),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<protocol::Error> for Error {
+    fn from(e: protocol::Error) -> Self {
+        Error::Protocol(e)
+    }
+}
+
+/// Result of a successful calibration
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub zero: CalibrationPoint,
+    pub reference: CalibrationPoint,
+    /// Calibration constants reported by the device: weight = m * (reading - b)
+    pub m: f32,
+    pub b: i32,
+}
+
+fn request<T: Transport>(transport: &mut T, command: Command) -> Result<Vec<u8>, Error> {
+    transport.write_control(&command.encode())?;
+    Ok(transport.read_data()?)
+}
+
+fn add_point<T: Transport, U: Ui>(
+    transport: &mut T,
+    ui: &mut U,
+    name: &'static str,
+    weight: f32,
+) -> Result<CalibrationPoint, Error> {
+    let response = request(transport, Command::AddCalibrationPoint(weight))?;
+    let point = protocol::parse_calibration_point(&response)?.ok_or(Error::NotSettled(name))?;
+    ui.message(&format!(
+        "Captured the {name} point: reading {} ± {:.1} over {} samples",
+        point.reading, point.std_dev, point.n_samples
+    ));
+    // Only meaningful if the scale was calibrated before, so just a warning
+    if point.std_dev_kg > MAX_STD_DEV_KG {
+        ui.message(&format!(
+            "Warning: the readings varied by about {:.3} kg. Consider starting over with the \
+             weight hanging still.",
+            point.std_dev_kg
+        ));
+    }
+    Ok(point)
+}
+
+/// Calibrate a load cell with a reference weight in kg
+///
+/// If `cell` is `None`, the device's currently selected load cell is calibrated.
+pub fn calibrate<T: Transport, U: Ui>(
+    transport: &mut T,
+    ui: &mut U,
+    cell: Option<u8>,
+    reference_kg: f32,
+) -> Result<Calibration, Error> {
+    if let Some(cell) = cell {
+        transport.write_control(&Command::SelectCell(cell).encode())?;
+    }
+
+    ui.prompt("Remove all weight from the scale")?;
+    let zero = add_point(transport, ui, "zero", 0.0)?;
+    ui.prompt(&format!(
+        "Hang the reference weight of {reference_kg:.3} kg from the scale"
+    ))?;
+    let reference = add_point(transport, ui, "reference", reference_kg)?;
+
+    let response = request(transport, Command::SaveCalibration)?;
+    protocol::parse_save_calibration(&response)?.map_err(Error::Rejected)?;
+    let response = request(transport, Command::GetCalibrationCurve)?;
+    let (m, b) = protocol::parse_calibration_curve(&response)?;
+    Ok(Calibration {
+        zero,
+        reference,
+        m,
+        b,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim::SimulatedDevice;
+
+    /// Continues right away, keeping the messages
+    #[derive(Default)]
+    struct ScriptedUi {
+        prompts: Vec<String>,
+        messages: Vec<String>,
+    }
+
+    impl Ui for ScriptedUi {
+        fn prompt(&mut self, instruction: &str) -> io::Result<()> {
+            self.prompts.push(instruction.to_owned());
+            Ok(())
+        }
+
+        fn message(&mut self, message: &str) {
+            self.messages.push(message.to_owned());
+        }
+    }
+
+    #[test]
+    fn calibrates() {
+        let mut device = SimulatedDevice::new(2.5e-5, 12_345);
+        let mut ui = ScriptedUi::default();
+        let calibration = calibrate(&mut device, &mut ui, Some(1), 20.0).unwrap();
+        assert_eq!(device.cell(), 1);
+        assert_eq!(device.calibration(), (calibration.m, calibration.b));
+        assert_eq!(calibration.b, 12_345);
+        assert!((calibration.m - 2.5e-5).abs() < 1e-9, "{}", calibration.m);
+        assert_eq!(calibration.zero.reading, 12_345);
+        assert_eq!(calibration.reference.reading, 12_345 + 800_000);
+        assert_eq!(ui.prompts.len(), 2);
+        assert!(ui.prompts[1].contains("20.000 kg"));
+    }
+
+    #[test]
+    fn rejected() {
+        // Forgot to hang the reference weight
+        let mut device = SimulatedDevice::default().with_actual_weights(&[0.0, 0.0]);
+        let result = calibrate(&mut device, &mut ScriptedUi::default(), None, 20.0);
+        assert!(matches!(
+            result,
+            Err(Error::Rejected(Rejection::DegenerateReadings))
+        ));
+
+        // A slope that's 100 times too small
+        let mut device = SimulatedDevice::default().with_actual_weights(&[0.0, 0.2]);
+        let result = calibrate(&mut device, &mut ScriptedUi::default(), None, 20.0);
+        assert!(matches!(
+            result,
+            Err(Error::Rejected(Rejection::SlopeOutOfRange))
+        ));
+    }
+
+    #[test]
+    fn no_response() {
+        struct Silent;
+        impl Transport for Silent {
+            fn write_control(&mut self, _payload: &[u8]) -> io::Result<()> {
+                Ok(())
+            }
+            fn read_data(&mut self) -> io::Result<Vec<u8>> {
+                Err(io::ErrorKind::TimedOut.into())
+            }
+        }
+        let result = calibrate(&mut Silent, &mut ScriptedUi::default(), None, 20.0);
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+}
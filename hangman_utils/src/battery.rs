@@ -0,0 +1,69 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Battery level estimation from voltage
+
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// Battery level in percent at the given voltage, interpolated linearly between the points of a
+/// discharge curve
+///
+/// `curve` is a list of (millivolts, percent) points in order of increasing voltage. Voltages
+/// outside of the curve are clamped to its ends.
+pub fn level_pct(millivolts: u32, curve: &[(u32, u8)]) -> u8 {
+    let (Some(&(min_mv, min_pct)), Some(&(max_mv, max_pct))) = (curve.first(), curve.last()) else {
+        return 0;
+    };
+    if millivolts <= min_mv {
+        return min_pct;
+    }
+    if millivolts >= max_mv {
+        return max_pct;
+    }
+    let upper = curve
+        .iter()
+        .position(|&(mv, _)| mv >= millivolts)
+        .expect("Voltage within the curve");
+    let (low_mv, low_pct) = curve[upper - 1];
+    let (high_mv, high_pct) = curve[upper];
+    let fraction = (millivolts - low_mv) as f32 / (high_mv - low_mv) as f32;
+    let pct = f32::from(low_pct) + fraction * (f32::from(high_pct) - f32::from(low_pct));
+    pct.round() as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CURVE: &[(u32, u8)] = &[(2700, 0), (2800, 20), (3000, 100)];
+
+    #[test]
+    fn interpolates() {
+        assert_eq!(level_pct(2700, CURVE), 0);
+        assert_eq!(level_pct(2750, CURVE), 10);
+        assert_eq!(level_pct(2800, CURVE), 20);
+        assert_eq!(level_pct(2900, CURVE), 60);
+        assert_eq!(level_pct(2999, CURVE), 100);
+        assert_eq!(level_pct(3000, CURVE), 100);
+    }
+
+    #[test]
+    fn clamps() {
+        assert_eq!(level_pct(0, CURVE), 0);
+        assert_eq!(level_pct(3600, CURVE), 100);
+        assert_eq!(level_pct(3000, &[]), 0);
+    }
+}
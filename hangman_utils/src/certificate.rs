@@ -0,0 +1,331 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Calibration certificates: a record of when and how a load cell was calibrated
+//!
+//! A certificate is stored and exported in the following format. All values are little-endian.
+//!
+//! | Offset | Size | Field                                                          |
+//! | ------ | ---- | -------------------------------------------------------------- |
+//! | 0      | 1    | Format version, currently 1. Zero if there is no certificate.  |
+//! | 1      | 1    | Load cell                                                      |
+//! | 2      | 1    | Number of points                                               |
+//! | 3      | 1    | Reserved                                                       |
+//! | 4      | 4    | Boot count (`u32`)                                             |
+//! | 8      | 8    | Device ID (`u64`)                                              |
+//! | 16     | 16   | Firmware version, ASCII padded with zeros                      |
+//! | 32     | 4    | Temperature in °C (`f32`), NaN if unknown                      |
+//! | 36     | 4    | `m` in kg per count (`f32`)                                    |
+//! | 40     | 4    | `b` in counts (`i32`)                                          |
+//! | 44     | 80   | Up to four points of 20 bytes each, unused points are zero     |
+//! | 124    | 4    | Reserved                                                       |
+//!
+//! Each point is:
+//!
+//! | Offset | Size | Field                                                          |
+//! | ------ | ---- | -------------------------------------------------------------- |
+//! | 0      | 1    | Branch: 0 = loading, 1 = unloading                             |
+//! | 1      | 3    | Reserved                                                       |
+//! | 4      | 4    | Reference weight in kg (`f32`)                                 |
+//! | 8      | 4    | Averaged reading in counts (`i32`)                             |
+//! | 12     | 4    | Standard deviation of the averaged readings in kg (`f32`)      |
+//! | 16     | 4    | Residual in kg (`f32`)                                         |
+//!
+//! The residual is the weight of the point's reading according to `m` and `b`, minus its reference
+//! weight. It's only due to rounding for the loading points, which `m` and `b` were derived from.
+//! For unloading points, it's the hysteresis of the load cell.
+
+use crate::hysteresis::Branch;
+use defmt::Format;
+
+pub const FORMAT_VERSION: u8 = 1;
+/// Encoded size in bytes
+pub const SIZE: usize = 128;
+/// Zero and reference points of both branches
+pub const MAX_POINTS: usize = 4;
+const FIRMWARE_VERSION_LEN: usize = 16;
+const HEADER_SIZE: usize = 44;
+const POINT_SIZE: usize = 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Point {
+    pub branch: Branch,
+    /// Reference weight in kg
+    pub weight: f32,
+    pub reading: i32,
+    /// Standard deviation of the averaged readings in kg
+    pub std_dev_kg: f32,
+    /// Weight of `reading` according to the calibration, minus `weight`
+    pub residual_kg: f32,
+}
+
+impl Point {
+    fn encode(&self, out: &mut [u8]) {
+        out[0] = match self.branch {
+            Branch::Loading => 0,
+            Branch::Unloading => 1,
+        };
+        out[4..8].copy_from_slice(&self.weight.to_le_bytes());
+        out[8..12].copy_from_slice(&self.reading.to_le_bytes());
+        out[12..16].copy_from_slice(&self.std_dev_kg.to_le_bytes());
+        out[16..20].copy_from_slice(&self.residual_kg.to_le_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let branch = match data[0] {
+            0 => Branch::Loading,
+            1 => Branch::Unloading,
+            _ => return Err(DecodeError::InvalidPoint),
+        };
+        Ok(Self {
+            branch,
+            weight: f32::from_le_bytes(le_bytes(data, 4)),
+            reading: i32::from_le_bytes(le_bytes(data, 8)),
+            std_dev_kg: f32::from_le_bytes(le_bytes(data, 12)),
+            residual_kg: f32::from_le_bytes(le_bytes(data, 16)),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+pub enum DecodeError {
+    WrongSize,
+    UnknownVersion(u8),
+    InvalidPoint,
+}
+
+fn le_bytes<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    data[offset..offset + N].try_into().unwrap()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Certificate {
+    pub cell: u8,
+    /// Boot count at the time of the calibration
+    pub boot_count: u32,
+    pub device_id: u64,
+    firmware_version: [u8; FIRMWARE_VERSION_LEN],
+    /// NaN if unknown
+    temperature_c: f32,
+    pub m: f32,
+    pub b: i32,
+    points: [Point; MAX_POINTS],
+    n_points: u8,
+}
+
+impl Certificate {
+    /// Certificate without any points. `firmware_version` is truncated to 16 bytes.
+    pub fn new(
+        cell: u8,
+        boot_count: u32,
+        device_id: u64,
+        firmware_version: &str,
+        temperature_c: Option<f32>,
+        m: f32,
+        b: i32,
+    ) -> Self {
+        let mut version = [0; FIRMWARE_VERSION_LEN];
+        let len = firmware_version.len().min(FIRMWARE_VERSION_LEN);
+        version[..len].copy_from_slice(&firmware_version.as_bytes()[..len]);
+        Self {
+            cell,
+            boot_count,
+            device_id,
+            firmware_version: version,
+            temperature_c: temperature_c.unwrap_or(f32::NAN),
+            m,
+            b,
+            points: [Point {
+                branch: Branch::Loading,
+                weight: 0.0,
+                reading: 0,
+                std_dev_kg: 0.0,
+                residual_kg: 0.0,
+            }; MAX_POINTS],
+            n_points: 0,
+        }
+    }
+
+    /// Add a calibration point, given its standard deviation in counts. Ignored if there are
+    /// already `MAX_POINTS` points.
+    pub fn add_point(&mut self, branch: Branch, weight: f32, reading: i32, std_dev: f32) {
+        let Some(point) = self.points.get_mut(usize::from(self.n_points)) else {
+            return;
+        };
+        *point = Point {
+            branch,
+            weight,
+            reading,
+            std_dev_kg: std_dev * self.m.abs(),
+            residual_kg: self.m * (i64::from(reading) - i64::from(self.b)) as f32 - weight,
+        };
+        self.n_points += 1;
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points[..usize::from(self.n_points)]
+    }
+
+    /// Firmware version, up to the first invalid character
+    pub fn firmware_version(&self) -> &str {
+        let len = self
+            .firmware_version
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(FIRMWARE_VERSION_LEN);
+        match core::str::from_utf8(&self.firmware_version[..len]) {
+            Ok(version) => version,
+            Err(e) => core::str::from_utf8(&self.firmware_version[..e.valid_up_to()]).unwrap(),
+        }
+    }
+
+    pub fn temperature_c(&self) -> Option<f32> {
+        (!self.temperature_c.is_nan()).then_some(self.temperature_c)
+    }
+
+    pub fn encode(&self) -> [u8; SIZE] {
+        let mut out = [0; SIZE];
+        out[0] = FORMAT_VERSION;
+        out[1] = self.cell;
+        out[2] = self.n_points;
+        out[4..8].copy_from_slice(&self.boot_count.to_le_bytes());
+        out[8..16].copy_from_slice(&self.device_id.to_le_bytes());
+        out[16..32].copy_from_slice(&self.firmware_version);
+        out[32..36].copy_from_slice(&self.temperature_c.to_le_bytes());
+        out[36..40].copy_from_slice(&self.m.to_le_bytes());
+        out[40..44].copy_from_slice(&self.b.to_le_bytes());
+        for (point, out) in self
+            .points()
+            .iter()
+            .zip(out[HEADER_SIZE..].chunks_exact_mut(POINT_SIZE))
+        {
+            point.encode(out);
+        }
+        out
+    }
+
+    /// Decode a certificate. `None` if the data is marked as not holding a certificate.
+    pub fn decode(data: &[u8]) -> Result<Option<Self>, DecodeError> {
+        if data.len() != SIZE {
+            return Err(DecodeError::WrongSize);
+        }
+        match data[0] {
+            0 => return Ok(None),
+            FORMAT_VERSION => (),
+            version => return Err(DecodeError::UnknownVersion(version)),
+        }
+        let n_points = data[2];
+        if usize::from(n_points) > MAX_POINTS {
+            return Err(DecodeError::InvalidPoint);
+        }
+        let mut certificate = Self::new(
+            data[1],
+            u32::from_le_bytes(le_bytes(data, 4)),
+            u64::from_le_bytes(le_bytes(data, 8)),
+            "",
+            None,
+            f32::from_le_bytes(le_bytes(data, 36)),
+            i32::from_le_bytes(le_bytes(data, 40)),
+        );
+        certificate.firmware_version = le_bytes(data, 16);
+        certificate.temperature_c = f32::from_le_bytes(le_bytes(data, 32));
+        for (point, data) in certificate
+            .points
+            .iter_mut()
+            .zip(data[HEADER_SIZE..].chunks_exact(POINT_SIZE))
+            .take(usize::from(n_points))
+        {
+            *point = Point::decode(data)?;
+        }
+        certificate.n_points = n_points;
+        Ok(Some(certificate))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn certificate() -> Certificate {
+        let mut certificate = Certificate::new(1, 42, 1234, "1.2.3.4", Some(21.5), 1e-4, 1000);
+        certificate.add_point(Branch::Loading, 0.0, 1000, 20.0);
+        certificate.add_point(Branch::Loading, 20.0, 201_000, 30.0);
+        certificate.add_point(Branch::Unloading, 20.0, 202_000, 30.0);
+        certificate
+    }
+
+    #[test]
+    fn round_trip() {
+        let certificate = certificate();
+        let encoded = certificate.encode();
+        assert_eq!(encoded[0], FORMAT_VERSION);
+        assert_eq!(Certificate::decode(&encoded), Ok(Some(certificate)));
+
+        let decoded = Certificate::decode(&encoded).unwrap().unwrap();
+        assert_eq!(decoded.firmware_version(), "1.2.3.4");
+        assert_eq!(decoded.temperature_c(), Some(21.5));
+        assert_eq!(decoded.points().len(), 3);
+    }
+
+    #[test]
+    fn residuals() {
+        let certificate = certificate();
+        let points = certificate.points();
+        assert!(points[0].residual_kg.abs() < 1e-6);
+        assert!(points[1].residual_kg.abs() < 1e-4);
+        // Unloading reads 0.1 kg high
+        assert!((points[2].residual_kg - 0.1).abs() < 1e-4);
+        assert!((points[1].std_dev_kg - 0.003).abs() < 1e-6);
+    }
+
+    #[test]
+    fn no_temperature() {
+        let certificate = Certificate::new(0, 1, 2, "", None, 1e-4, 0);
+        let decoded = Certificate::decode(&certificate.encode()).unwrap().unwrap();
+        assert_eq!(decoded.temperature_c(), None);
+        assert!(decoded.points().is_empty());
+    }
+
+    #[test]
+    fn long_firmware_version() {
+        let certificate = Certificate::new(0, 1, 2, "0123456789abcdefgh", None, 1e-4, 0);
+        assert_eq!(certificate.firmware_version(), "0123456789abcdef");
+    }
+
+    #[test]
+    fn too_many_points() {
+        let mut certificate = certificate();
+        certificate.add_point(Branch::Unloading, 0.0, 1100, 20.0);
+        certificate.add_point(Branch::Unloading, 0.0, 1100, 20.0);
+        assert_eq!(certificate.points().len(), MAX_POINTS);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Certificate::decode(&[0; SIZE]), Ok(None));
+        assert_eq!(Certificate::decode(&[0; 4]), Err(DecodeError::WrongSize));
+        let mut encoded = certificate().encode();
+        encoded[0] = 2;
+        assert_eq!(
+            Certificate::decode(&encoded),
+            Err(DecodeError::UnknownVersion(2))
+        );
+        let mut encoded = certificate().encode();
+        encoded[2] = 5;
+        assert_eq!(
+            Certificate::decode(&encoded),
+            Err(DecodeError::InvalidPoint)
+        );
+    }
+}
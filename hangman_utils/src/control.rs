@@ -0,0 +1,205 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payloads of the Hangman extensions to the Progressor control opcodes
+//!
+//! A control message is an opcode byte, a length byte, and the payload. As with the Progressor
+//! opcodes, the length byte may be omitted. The payload lengths each opcode accepts are listed here
+//! so that the firmware and the tests agree on them.
+
+/// 0xA1 SelectCell: index of the load cell
+pub const SELECT_CELL: &[usize] = &[1];
+/// 0xA2 CharacterizeNoise: nothing, or the `u32` number of samples
+pub const CHARACTERIZE_NOISE: &[usize] = &[0, 4];
+/// 0xA4 RollBackCalibration: index in the calibration history
+pub const ROLL_BACK_CALIBRATION: &[usize] = &[1];
+/// 0xA8 LearnCreep: `f32` weight, optionally followed by the learning time in minutes
+pub const LEARN_CREEP: &[usize] = &[4, 5];
+/// 0xAA SetReferenceWeight: `f32` weight
+pub const SET_REFERENCE_WEIGHT: &[usize] = &[4];
+/// 0xAB SetUnits: unit, optionally followed by the `f32` local gravity
+pub const SET_UNITS: &[usize] = &[1, 5];
+/// 0xAD SetBroadcast: enabled flag, optionally followed by the `u16` interval in ms
+pub const SET_BROADCAST: &[usize] = &[1, 3];
+/// 0xAE SetReconnectGrace: `u16` grace period in seconds
+pub const SET_RECONNECT_GRACE: &[usize] = &[2];
+/// 0xB0 SetOversampling: ratio, 0 for the board's default
+pub const SET_OVERSAMPLING: &[usize] = &[1];
+
+/// Payload of the control message `data`, after the opcode and the optional length byte, or `None`
+/// if its length isn't one of `lengths`
+///
+/// The message is read without a length byte whenever its length allows. Only otherwise is the
+/// second byte taken to be a length byte, if it matches the length of the rest of the message.
+/// That way a payload whose first byte happens to equal a shorter allowed length, e.g. a LearnCreep
+/// weight whose first byte is 4, isn't cut short.
+pub fn payload<'a>(data: &'a [u8], lengths: &[usize]) -> Option<&'a [u8]> {
+    match data {
+        [_, rest @ ..] if lengths.contains(&rest.len()) => Some(rest),
+        [_, length, rest @ ..]
+            if usize::from(*length) == rest.len() && lengths.contains(&rest.len()) =>
+        {
+            Some(rest)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_cell() {
+        assert_eq!(payload(&[0xA1, 2], SELECT_CELL), Some([2].as_slice()));
+        assert_eq!(payload(&[0xA1, 1, 2], SELECT_CELL), Some([2].as_slice()));
+        // Selecting cell 1 with a length byte
+        assert_eq!(payload(&[0xA1, 1, 1], SELECT_CELL), Some([1].as_slice()));
+        assert_eq!(payload(&[0xA1], SELECT_CELL), None);
+        assert_eq!(payload(&[0xA1, 2, 1], SELECT_CELL), None);
+    }
+
+    #[test]
+    fn characterize_noise() {
+        assert_eq!(payload(&[0xA2], CHARACTERIZE_NOISE), Some([].as_slice()));
+        assert_eq!(payload(&[0xA2, 0], CHARACTERIZE_NOISE), Some([].as_slice()));
+        let n_samples = 2400_u32.to_le_bytes();
+        let message = [[0xA2].as_slice(), &n_samples].concat();
+        assert_eq!(
+            payload(&message, CHARACTERIZE_NOISE),
+            Some(n_samples.as_slice())
+        );
+        let message = [[0xA2, 4].as_slice(), &n_samples].concat();
+        assert_eq!(
+            payload(&message, CHARACTERIZE_NOISE),
+            Some(n_samples.as_slice())
+        );
+        assert_eq!(payload(&[0xA2, 0x60, 0x09], CHARACTERIZE_NOISE), None);
+    }
+
+    #[test]
+    fn roll_back_calibration() {
+        assert_eq!(
+            payload(&[0xA4, 3], ROLL_BACK_CALIBRATION),
+            Some([3].as_slice())
+        );
+        assert_eq!(
+            payload(&[0xA4, 1, 3], ROLL_BACK_CALIBRATION),
+            Some([3].as_slice())
+        );
+        assert_eq!(payload(&[0xA4], ROLL_BACK_CALIBRATION), None);
+    }
+
+    #[test]
+    fn learn_creep() {
+        let weight = 20.0_f32.to_le_bytes();
+        let message = [[0xA8].as_slice(), &weight].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(weight.as_slice()));
+        // Ambiguous, so read without a length byte. Clients sending one must send the minutes too.
+        let message = [[0xA8, 4].as_slice(), &weight].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(&message[1..]));
+        let message = [[0xA8].as_slice(), &weight, &[5]].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(&message[1..]));
+        let message = [[0xA8, 5].as_slice(), &weight, &[5]].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(&message[2..]));
+        // The length byte doesn't match
+        let message = [[0xA8, 4].as_slice(), &weight, &[5]].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), None);
+    }
+
+    #[test]
+    fn learn_creep_weight_starting_with_length() {
+        // Just over 10 kg, with a first byte that matches the length of a weight on its own
+        let weight = [0x04, 0x00, 0x20, 0x41];
+        assert!((f32::from_le_bytes(weight) - 10.0).abs() < 1e-5);
+        let message = [[0xA8].as_slice(), &weight, &[5]].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(&message[1..]));
+        let message = [[0xA8, 5].as_slice(), &weight, &[5]].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(&message[2..]));
+        let message = [[0xA8].as_slice(), &weight].concat();
+        assert_eq!(payload(&message, LEARN_CREEP), Some(weight.as_slice()));
+    }
+
+    #[test]
+    fn set_reference_weight() {
+        let weight = 20.0_f32.to_le_bytes();
+        let message = [[0xAA].as_slice(), &weight].concat();
+        assert_eq!(
+            payload(&message, SET_REFERENCE_WEIGHT),
+            Some(weight.as_slice())
+        );
+        let message = [[0xAA, 4].as_slice(), &weight].concat();
+        assert_eq!(
+            payload(&message, SET_REFERENCE_WEIGHT),
+            Some(weight.as_slice())
+        );
+        assert_eq!(payload(&[0xAA, 3, 0, 0, 0, 0], SET_REFERENCE_WEIGHT), None);
+        assert_eq!(payload(&[0xAA, 0, 0], SET_REFERENCE_WEIGHT), None);
+    }
+
+    #[test]
+    fn set_units() {
+        assert_eq!(payload(&[0xAB, 1], SET_UNITS), Some([1].as_slice()));
+        assert_eq!(payload(&[0xAB, 1, 1], SET_UNITS), Some([1].as_slice()));
+        let gravity = 9.81_f32.to_le_bytes();
+        let message = [[0xAB, 1].as_slice(), &gravity].concat();
+        assert_eq!(payload(&message, SET_UNITS), Some(&message[1..]));
+        let message = [[0xAB, 5, 1].as_slice(), &gravity].concat();
+        assert_eq!(payload(&message, SET_UNITS), Some(&message[2..]));
+        assert_eq!(payload(&[0xAB], SET_UNITS), None);
+    }
+
+    #[test]
+    fn set_broadcast() {
+        assert_eq!(payload(&[0xAD, 1], SET_BROADCAST), Some([1].as_slice()));
+        assert_eq!(payload(&[0xAD, 1, 0], SET_BROADCAST), Some([0].as_slice()));
+        assert_eq!(
+            payload(&[0xAD, 1, 0xE8, 0x03], SET_BROADCAST),
+            Some([1, 0xE8, 0x03].as_slice())
+        );
+        assert_eq!(
+            payload(&[0xAD, 3, 1, 0xE8, 0x03], SET_BROADCAST),
+            Some([1, 0xE8, 0x03].as_slice())
+        );
+    }
+
+    #[test]
+    fn set_reconnect_grace() {
+        assert_eq!(
+            payload(&[0xAE, 30, 0], SET_RECONNECT_GRACE),
+            Some([30, 0].as_slice())
+        );
+        assert_eq!(
+            payload(&[0xAE, 2, 30, 0], SET_RECONNECT_GRACE),
+            Some([30, 0].as_slice())
+        );
+        assert_eq!(payload(&[0xAE, 30], SET_RECONNECT_GRACE), None);
+        assert_eq!(payload(&[0xAE, 3, 30, 0], SET_RECONNECT_GRACE), None);
+    }
+
+    #[test]
+    fn set_oversampling() {
+        assert_eq!(payload(&[0xB0, 4], SET_OVERSAMPLING), Some([4].as_slice()));
+        assert_eq!(
+            payload(&[0xB0, 1, 4], SET_OVERSAMPLING),
+            Some([4].as_slice())
+        );
+        assert_eq!(payload(&[0xB0, 0], SET_OVERSAMPLING), Some([0].as_slice()));
+        assert_eq!(
+            payload(&[0xB0, 1, 0], SET_OVERSAMPLING),
+            Some([0].as_slice())
+        );
+        assert_eq!(payload(&[0xB0], SET_OVERSAMPLING), None);
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load cell creep: under a constant load, the reading slowly drifts towards a slightly different
+//! value
+//!
+//! Creep is modelled as a first-order response to the load being applied. A load `W` applied at
+//! `t = 0` reads as `W * (1 + magnitude * (1 - exp(-t / tau)))`. The parameters are learned by
+//! hanging a known weight for a few minutes, and the correction divides the reading by the same
+//! factor based on the time since the load was applied.
+
+use defmt::Format;
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// Tared load at which the load counts as applied, starting the creep clock
+pub const ONSET_KG: f32 = 1.0;
+/// Tared load below which the load counts as removed
+pub const RELEASE_KG: f32 = 0.5;
+/// Readings this soon after the load was applied are still settling and aren't used for learning
+pub const SETTLE_S: f32 = 2.0;
+/// Largest plausible creep, relative to the load
+pub const MAX_MAGNITUDE: f32 = 0.05;
+/// Number of averaged points used for learning, regardless of how long the weight hangs
+const N_BINS: usize = 64;
+/// Number of time constants tried when learning
+const N_TAUS: usize = 48;
+const MIN_TAU_S: f32 = 1.0;
+
+/// Reasons for failing to learn creep parameters
+///
+/// The discriminant is the code reported to BLE clients. Zero is reserved for success.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum Rejection {
+    /// The weight was never hung
+    NoLoad = 1,
+    /// The weight was removed before the end
+    Unloaded = 2,
+    /// The measured load doesn't match the known weight
+    WeightMismatch = 3,
+    /// The readings don't follow the creep model or the creep is implausibly large
+    NoFit = 4,
+}
+
+impl Rejection {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Params {
+    /// Eventual creep relative to the load, e.g. 0.002 for a reading that ends up 0.2% high
+    pub magnitude: f32,
+    /// Time constant
+    pub tau_s: f32,
+}
+
+impl Params {
+    /// Ratio of the reading to the actual load, `t_s` seconds after the load was applied
+    pub fn factor(&self, t_s: f32) -> f32 {
+        1.0 + self.magnitude * (1.0 - (-t_s / self.tau_s).exp())
+    }
+
+    pub fn is_plausible(&self) -> bool {
+        self.magnitude.abs() <= MAX_MAGNITUDE && self.tau_s.is_finite() && self.tau_s > 0.0
+    }
+}
+
+/// Removes creep from tared readings
+///
+/// The load counts as applied once it reaches `ONSET_KG` and as removed once it drops below
+/// `RELEASE_KG`. The correction assumes that the load stays roughly constant in between, as during
+/// a hold. Recovery after the load is removed isn't modelled.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Compensator {
+    params: Option<Params>,
+    /// Time that the load was applied
+    onset_us: Option<u64>,
+}
+
+impl Compensator {
+    pub fn new(params: Option<Params>) -> Self {
+        Self {
+            params,
+            onset_us: None,
+        }
+    }
+
+    pub fn params(&self) -> Option<Params> {
+        self.params
+    }
+
+    pub fn set_params(&mut self, params: Option<Params>) {
+        self.params = params;
+    }
+
+    /// Forget about the current load, e.g. after taring
+    pub fn reset(&mut self) {
+        self.onset_us = None;
+    }
+
+    pub fn compensate(&mut self, timestamp_us: u64, load: f32) -> f32 {
+        match self.onset_us {
+            None if load >= ONSET_KG => self.onset_us = Some(timestamp_us),
+            Some(_) if load < RELEASE_KG => self.onset_us = None,
+            _ => (),
+        }
+        match (self.params, self.onset_us) {
+            (Some(params), Some(onset_us)) => {
+                let t_s = timestamp_us.saturating_sub(onset_us) as f32 / 1e6;
+                load / params.factor(t_s)
+            }
+            _ => load,
+        }
+    }
+}
+
+/// Learned creep parameters
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Fit {
+    pub params: Params,
+    /// Load when it was applied, i.e. without creep
+    pub load: f32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Bin {
+    sum: f64,
+    n: u32,
+}
+
+/// Learns creep parameters from readings of a constant load
+///
+/// Readings are averaged into a fixed number of bins in time, so that long holds don't need more
+/// memory. The fit tries a range of time constants and solves for the load and creep at each by
+/// linear least squares.
+#[derive(Copy, Clone, Debug)]
+pub struct Learner {
+    duration_s: f32,
+    bins: [Bin; N_BINS],
+}
+
+impl Learner {
+    /// Learn from readings up to `duration_s` after the load was applied
+    pub fn new(duration_s: f32) -> Self {
+        Self {
+            duration_s,
+            bins: [Bin::default(); N_BINS],
+        }
+    }
+
+    fn bin_width_s(&self) -> f32 {
+        (self.duration_s - SETTLE_S) / N_BINS as f32
+    }
+
+    /// Add a reading taken `t_s` seconds after the load was applied
+    pub fn add_sample(&mut self, t_s: f32, load: f32) {
+        if !(SETTLE_S..self.duration_s).contains(&t_s) {
+            return;
+        }
+        let i = (((t_s - SETTLE_S) / self.bin_width_s()) as usize).min(N_BINS - 1);
+        self.bins[i].sum += f64::from(load);
+        self.bins[i].n += 1;
+    }
+
+    /// Averaged readings as `(t_s, load)` at the middle of each bin
+    fn points(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let width = f64::from(self.bin_width_s());
+        self.bins
+            .iter()
+            .enumerate()
+            .filter(|(_, bin)| bin.n > 0)
+            .map(move |(i, bin)| {
+                let t_s = f64::from(SETTLE_S) + (i as f64 + 0.5) * width;
+                (t_s, bin.sum / f64::from(bin.n))
+            })
+    }
+
+    /// Least squares fit of `load = a + b * (1 - exp(-t / tau))` for a fixed `tau`
+    ///
+    /// Returns `a`, `b`, and the sum of squared residuals.
+    fn fit_tau(&self, tau_s: f64) -> Option<(f64, f64, f64)> {
+        let g = |t_s: f64| 1.0 - (-t_s / tau_s).exp();
+        let (mut n, mut sg, mut sgg, mut sy, mut sgy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (t_s, y) in self.points() {
+            n += 1.0;
+            sg += g(t_s);
+            sgg += g(t_s) * g(t_s);
+            sy += y;
+            sgy += g(t_s) * y;
+        }
+        let det = n * sgg - sg * sg;
+        if det <= f64::EPSILON * n * sgg {
+            return None;
+        }
+        let b = (n * sgy - sg * sy) / det;
+        let a = (sy - b * sg) / n;
+        let residual = self
+            .points()
+            .map(|(t_s, y)| (y - a - b * g(t_s)).powi(2))
+            .sum();
+        Some((a, b, residual))
+    }
+
+    pub fn finish(&self) -> Result<Fit, Rejection> {
+        // Need a reasonable share of the bins to tell the time constant apart from noise
+        if self.points().count() < N_BINS / 4 {
+            return Err(Rejection::NoFit);
+        }
+        let max_tau_s = 2.0 * self.duration_s;
+        let (tau_s, (a, b, _)) = (0..N_TAUS)
+            .map(|i| {
+                let fraction = i as f32 / (N_TAUS - 1) as f32;
+                MIN_TAU_S * (max_tau_s / MIN_TAU_S).powf(fraction)
+            })
+            .filter_map(|tau_s| Some((tau_s, self.fit_tau(f64::from(tau_s))?)))
+            .min_by(|(_, (_, _, x)), (_, (_, _, y))| x.total_cmp(y))
+            .ok_or(Rejection::NoFit)?;
+        if a <= 0.0 {
+            return Err(Rejection::NoFit);
+        }
+        let params = Params {
+            magnitude: (b / a) as f32,
+            tau_s,
+        };
+        if !params.is_plausible() {
+            return Err(Rejection::NoFit);
+        }
+        Ok(Fit {
+            params,
+            load: a as f32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PARAMS: Params = Params {
+        magnitude: 0.004,
+        tau_s: 20.0,
+    };
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() < tolerance, "{a} != {b}");
+    }
+
+    #[test]
+    fn factor() {
+        assert_close(PARAMS.factor(0.0), 1.0, 1e-6);
+        assert_close(
+            PARAMS.factor(20.0),
+            1.0 + 0.004 * (1.0 - (-1.0_f32).exp()),
+            1e-6,
+        );
+        assert_close(PARAMS.factor(1e6), 1.004, 1e-6);
+    }
+
+    #[test]
+    fn compensate_hold() {
+        let mut compensator = Compensator::new(Some(PARAMS));
+        assert_close(compensator.compensate(0, 0.0), 0.0, 1e-6);
+        // Load applied at 1 s
+        for i in 10..600 {
+            let t_s = i as f32 / 10.0;
+            let reading = 50.0 * PARAMS.factor(t_s - 1.0);
+            let compensated = compensator.compensate(i * 100_000, reading);
+            assert_close(compensated, 50.0, 1e-4);
+        }
+        // Removed, then applied again, starting over
+        assert_close(compensator.compensate(60_000_000, 0.2), 0.2, 1e-6);
+        assert_close(compensator.compensate(61_000_000, 30.0), 30.0, 1e-6);
+        let reading = 30.0 * PARAMS.factor(10.0);
+        assert_close(compensator.compensate(71_000_000, reading), 30.0, 1e-4);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut compensator = Compensator::new(None);
+        assert_close(compensator.compensate(0, 50.0), 50.0, 1e-6);
+        assert_close(compensator.compensate(60_000_000, 50.2), 50.2, 1e-6);
+    }
+
+    #[test]
+    fn reset() {
+        let mut compensator = Compensator::new(Some(PARAMS));
+        compensator.compensate(0, 50.0);
+        compensator.reset();
+        // Counts as a fresh load
+        assert_close(compensator.compensate(100_000_000, 50.0), 50.0, 1e-6);
+    }
+
+    fn learn(params: Params, load: f32, duration_s: f32) -> Result<Fit, Rejection> {
+        let mut learner = Learner::new(duration_s);
+        for i in 0..(duration_s * 80.0) as u32 {
+            let t_s = i as f32 / 80.0;
+            // Deterministic noise of 10 g
+            let noise = if i % 2 == 0 { 0.01 } else { -0.01 };
+            learner.add_sample(t_s, load * params.factor(t_s) + noise);
+        }
+        learner.finish()
+    }
+
+    #[test]
+    fn learn_creep() {
+        let fit = learn(PARAMS, 40.0, 180.0).unwrap();
+        assert_close(fit.load, 40.0, 0.01);
+        assert_close(fit.params.magnitude, PARAMS.magnitude, 2e-4);
+        // The grid of time constants is about 13% apart
+        assert_close(fit.params.tau_s, PARAMS.tau_s, 0.15 * PARAMS.tau_s);
+    }
+
+    #[test]
+    fn learn_negative_creep() {
+        let params = Params {
+            magnitude: -0.003,
+            tau_s: 45.0,
+        };
+        let fit = learn(params, 60.0, 300.0).unwrap();
+        assert_close(fit.params.magnitude, params.magnitude, 2e-4);
+        assert_close(fit.params.tau_s, params.tau_s, 0.15 * params.tau_s);
+    }
+
+    #[test]
+    fn learn_rejections() {
+        // Too short to fill the bins
+        assert_eq!(learn(PARAMS, 40.0, SETTLE_S).unwrap_err(), Rejection::NoFit);
+        let huge = Params {
+            magnitude: 0.2,
+            tau_s: 20.0,
+        };
+        assert_eq!(learn(huge, 40.0, 180.0).unwrap_err(), Rejection::NoFit);
+        assert_eq!(Learner::new(180.0).finish().unwrap_err(), Rejection::NoFit);
+    }
+}
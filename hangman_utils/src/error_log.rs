@@ -0,0 +1,247 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log of coded errors with how often and when they last occurred
+//!
+//! Only one entry is kept per error code, ordered from the most to the least recently occurred.
+//! Once the log is full, the least recent entry makes way for new codes.
+//!
+//! The log is stored as `MAX_ENTRIES` entries of 16 bytes each, unused entries being zero. All
+//! values are little-endian.
+//!
+//! | Offset | Size | Field                                                          |
+//! | ------ | ---- | -------------------------------------------------------------- |
+//! | 0      | 1    | Error code, zero for an unused entry                           |
+//! | 1      | 3    | Reserved                                                       |
+//! | 4      | 4    | Number of occurrences (`u32`)                                  |
+//! | 8      | 4    | Boot count at the last occurrence (`u32`)                      |
+//! | 12     | 4    | Uptime in seconds at the last occurrence (`u32`)               |
+
+use defmt::Format;
+
+pub const MAX_ENTRIES: usize = 8;
+const ENTRY_SIZE: usize = 16;
+/// Encoded size in bytes
+pub const SIZE: usize = MAX_ENTRIES * ENTRY_SIZE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+pub struct Entry {
+    pub code: u8,
+    /// Number of occurrences, saturating at `u32::MAX`
+    pub count: u32,
+    /// Boot count at the last occurrence
+    pub boot_count: u32,
+    /// Uptime in seconds at the last occurrence
+    pub uptime_s: u32,
+}
+
+impl Entry {
+    const UNUSED: Self = Self {
+        code: 0,
+        count: 0,
+        boot_count: 0,
+        uptime_s: 0,
+    };
+
+    fn encode(&self, out: &mut [u8]) {
+        out[0] = self.code;
+        out[4..8].copy_from_slice(&self.count.to_le_bytes());
+        out[8..12].copy_from_slice(&self.boot_count.to_le_bytes());
+        out[12..16].copy_from_slice(&self.uptime_s.to_le_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Self {
+        let le_bytes = |offset: usize| -> [u8; 4] { data[offset..offset + 4].try_into().unwrap() };
+        Self {
+            code: data[0],
+            count: u32::from_le_bytes(le_bytes(4)),
+            boot_count: u32::from_le_bytes(le_bytes(8)),
+            uptime_s: u32::from_le_bytes(le_bytes(12)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ErrorLog {
+    entries: [Entry; MAX_ENTRIES],
+    len: usize,
+}
+
+impl Default for ErrorLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ErrorLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: [Entry::UNUSED; MAX_ENTRIES],
+            len: 0,
+        }
+    }
+
+    /// Record an occurrence of `code`, which must not be zero
+    pub fn record(&mut self, code: u8, boot_count: u32, uptime_s: u32) {
+        self.add(code, 1, boot_count, uptime_s);
+    }
+
+    fn add(&mut self, code: u8, count: u32, boot_count: u32, uptime_s: u32) {
+        debug_assert_ne!(code, 0);
+        let (index, count) = match self.entries().iter().position(|e| e.code == code) {
+            Some(index) => (index, self.entries[index].count.saturating_add(count)),
+            None => {
+                self.len = (self.len + 1).min(MAX_ENTRIES);
+                // Evicts the least recent entry if the log was full
+                (self.len - 1, count)
+            }
+        };
+        // Move the entry to the front
+        self.entries[..=index].rotate_right(1);
+        self.entries[0] = Entry {
+            code,
+            count,
+            boot_count,
+            uptime_s,
+        };
+    }
+
+    /// Entries from the most to the least recently occurred
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Merge the errors recorded so far into a log stored by a previous boot, and use the result
+    /// from now on. The errors recorded so far are attributed to `boot_count`.
+    pub fn restore(&mut self, stored: &ErrorLog, boot_count: u32) {
+        let this_boot = core::mem::replace(self, *stored);
+        for entry in this_boot.entries().iter().rev() {
+            self.add(entry.code, entry.count, boot_count, entry.uptime_s);
+        }
+    }
+
+    pub fn encode(&self) -> [u8; SIZE] {
+        let mut out = [0; SIZE];
+        for (entry, out) in self.entries().iter().zip(out.chunks_exact_mut(ENTRY_SIZE)) {
+            entry.encode(out);
+        }
+        out
+    }
+
+    /// Decode a log, up to the first unused entry. Data of the wrong size decodes to an empty log.
+    pub fn decode(data: &[u8]) -> Self {
+        let mut log = Self::new();
+        if data.len() != SIZE {
+            return log;
+        }
+        for data in data.chunks_exact(ENTRY_SIZE) {
+            let entry = Entry::decode(data);
+            if entry.code == 0 {
+                break;
+            }
+            log.entries[log.len] = entry;
+            log.len += 1;
+        }
+        log
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn codes(log: &ErrorLog) -> Vec<u8> {
+        log.entries().iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn record() {
+        let mut log = ErrorLog::new();
+        assert!(log.is_empty());
+        log.record(1, 5, 10);
+        log.record(2, 5, 11);
+        log.record(1, 5, 12);
+        assert_eq!(codes(&log), [1, 2]);
+        assert_eq!(
+            log.entries()[0],
+            Entry {
+                code: 1,
+                count: 2,
+                boot_count: 5,
+                uptime_s: 12
+            }
+        );
+        assert_eq!(log.entries()[1].count, 1);
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn evicts_least_recent() {
+        let mut log = ErrorLog::new();
+        for code in 1..=MAX_ENTRIES as u8 {
+            log.record(code, 1, u32::from(code));
+        }
+        // Makes code 1 the most recent
+        log.record(1, 1, 100);
+        log.record(100, 1, 101);
+        assert_eq!(log.entries().len(), MAX_ENTRIES);
+        assert_eq!(codes(&log)[..3], [100, 1, 8]);
+        assert!(!codes(&log).contains(&2));
+    }
+
+    #[test]
+    fn restore() {
+        let mut stored = ErrorLog::new();
+        stored.record(1, 3, 10);
+        stored.record(2, 3, 20);
+
+        let mut log = ErrorLog::new();
+        log.record(1, 0, 1);
+        log.record(3, 0, 2);
+        log.restore(&stored, 4);
+        assert_eq!(codes(&log), [3, 1, 2]);
+        assert_eq!(
+            log.entries()[1],
+            Entry {
+                code: 1,
+                count: 2,
+                boot_count: 4,
+                uptime_s: 1
+            }
+        );
+        assert_eq!(log.entries()[2].boot_count, 3);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut log = ErrorLog::new();
+        assert_eq!(log.encode(), [0; SIZE]);
+        log.record(7, 1, 2);
+        log.record(8, 3, 4);
+        let encoded = log.encode();
+        assert_eq!(encoded[..4], [8, 0, 0, 0]);
+        assert_eq!(ErrorLog::decode(&encoded), log);
+        assert!(ErrorLog::decode(&[1; 4]).is_empty());
+    }
+}
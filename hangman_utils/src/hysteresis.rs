@@ -0,0 +1,186 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load cell hysteresis: readings differ depending on whether the load is increasing or decreasing
+//!
+//! Each direction gets its own calibration, called a branch. The branch to use is picked from the
+//! recent trend of the load.
+
+use crate::two_point_cal::{CalPoint, Constants, TwoPoint};
+use defmt::Format;
+use num_traits::PrimInt;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Format)]
+pub enum Branch {
+    /// Load is increasing
+    #[default]
+    Loading,
+    /// Load is decreasing
+    Unloading,
+}
+
+/// Tracks whether the load is increasing or decreasing
+///
+/// The branch only changes once the load has moved more than `deadband` away from the most extreme
+/// value in the current direction, so that noise doesn't flip it back and forth.
+#[derive(Copy, Clone, Debug)]
+pub struct Trend {
+    deadband: f32,
+    branch: Branch,
+    /// Highest load while loading, lowest while unloading
+    extreme: Option<f32>,
+}
+
+impl Trend {
+    pub fn new(deadband: f32) -> Self {
+        Self {
+            deadband,
+            branch: Branch::Loading,
+            extreme: None,
+        }
+    }
+
+    pub fn branch(&self) -> Branch {
+        self.branch
+    }
+
+    /// Start over, e.g. after the calibration changed
+    pub fn reset(&mut self) {
+        *self = Self::new(self.deadband);
+    }
+
+    pub fn update(&mut self, load: f32) -> Branch {
+        let extreme = *self.extreme.get_or_insert(load);
+        match self.branch {
+            Branch::Loading if load > extreme => self.extreme = Some(load),
+            Branch::Loading if load < extreme - self.deadband => {
+                self.branch = Branch::Unloading;
+                self.extreme = Some(load);
+            }
+            Branch::Unloading if load < extreme => self.extreme = Some(load),
+            Branch::Unloading if load > extreme + self.deadband => {
+                self.branch = Branch::Loading;
+                self.extreme = Some(load);
+            }
+            _ => (),
+        }
+        self.branch
+    }
+}
+
+/// Measured hysteresis, as the error of the loading branch at points captured while unloading
+#[derive(Copy, Clone, Debug, Default, Format)]
+pub struct Report {
+    /// Error at zero. NaN if no zero point was captured while unloading.
+    pub zero_error: f32,
+    /// Error at the reference weight. NaN if no reference point was captured while unloading.
+    pub reference_error: f32,
+    /// Reference weight of the unloading branch. NaN if unknown.
+    pub reference: f32,
+}
+
+impl Report {
+    /// Error at the reference weight relative to the reference weight, in percent
+    pub fn reference_error_pct(&self) -> f32 {
+        100.0 * self.reference_error / self.reference
+    }
+}
+
+fn error<Reading: PrimInt>(loading: &Constants<Reading>, point: &CalPoint<Reading>) -> f32 {
+    let counts = point.measured_value.to_f32().unwrap() - loading.b.to_f32().unwrap();
+    loading.m * counts - point.expected_value
+}
+
+/// Compare points captured while unloading against the loading branch
+pub fn characterize<Reading: PrimInt + Format>(
+    loading: &Constants<Reading>,
+    unloading: &TwoPoint<Reading>,
+) -> Report {
+    let zero_error = unloading.zero().map_or(f32::NAN, |zero| {
+        error(
+            loading,
+            &CalPoint {
+                expected_value: 0.0,
+                measured_value: zero,
+            },
+        )
+    });
+    let (reference_error, reference) =
+        unloading.reference().map_or((f32::NAN, f32::NAN), |point| {
+            (error(loading, &point), point.expected_value)
+        });
+    Report {
+        zero_error,
+        reference_error,
+        reference,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trend() {
+        let mut trend = Trend::new(1.0);
+        assert_eq!(trend.update(0.0), Branch::Loading);
+        assert_eq!(trend.update(10.0), Branch::Loading);
+        assert_eq!(trend.update(20.0), Branch::Loading);
+        // Within the deadband of the peak
+        assert_eq!(trend.update(19.5), Branch::Loading);
+        assert_eq!(trend.update(18.5), Branch::Unloading);
+        assert_eq!(trend.update(10.0), Branch::Unloading);
+        // Within the deadband of the trough
+        assert_eq!(trend.update(10.8), Branch::Unloading);
+        assert_eq!(trend.update(11.5), Branch::Loading);
+        trend.reset();
+        assert_eq!(trend.branch(), Branch::Loading);
+        assert_eq!(trend.update(5.0), Branch::Loading);
+        assert_eq!(trend.update(3.0), Branch::Unloading);
+    }
+
+    #[test]
+    fn noise_at_rest() {
+        let mut trend = Trend::new(1.0);
+        for i in 0..100 {
+            let noise = if i % 2 == 0 { 0.3 } else { -0.3 };
+            assert_eq!(trend.update(50.0 + noise), Branch::Loading);
+        }
+    }
+
+    #[test]
+    fn hysteresis() {
+        // 1 kg per 1000 counts
+        let loading = Constants { m: 1e-3, b: 1_000 };
+        let mut unloading = TwoPoint::default();
+        let report = characterize(&loading, &unloading);
+        assert!(report.zero_error.is_nan());
+        assert!(report.reference_error.is_nan());
+
+        // Reads 0.2 kg high at zero and 0.5 kg high at 100 kg on the way down
+        unloading.add_point(CalPoint {
+            expected_value: 0.0,
+            measured_value: 1_200,
+        });
+        unloading.add_point(CalPoint {
+            expected_value: 100.0,
+            measured_value: 101_500,
+        });
+        let report = characterize(&loading, &unloading);
+        assert!((report.zero_error - 0.2).abs() < 1e-4);
+        assert!((report.reference_error - 0.5).abs() < 1e-4);
+        assert_eq!(report.reference, 100.0);
+        assert!((report.reference_error_pct() - 0.5).abs() < 1e-4);
+    }
+}
@@ -17,14 +17,28 @@
 
 #[macro_use]
 pub mod log;
+pub mod battery;
+pub mod certificate;
+pub mod control;
+pub mod creep;
+pub mod error_log;
+pub mod hysteresis;
+pub mod noise;
+pub mod self_test;
+pub mod sim;
+pub mod stability;
+pub mod stream;
 pub mod two_point_cal;
+pub mod units;
+pub mod weight_scale;
+pub mod wh_c06;
 
 /// Convert a signed integer in a u32 container to a signed integer
 pub const fn convert_signed_to_i32<const BITS: u32>(mut input: u32) -> i32 {
     assert!(input < (1 << BITS), "Out of range");
     // Extend sign bits if negative
     if input & (1 << (BITS - 1)) != 0 {
-        input |= u32::MAX & !((1 << BITS) - 1);
+        input |= !((1 << BITS) - 1);
     }
     input as i32
 }
@@ -59,5 +73,30 @@ mod tests {
         assert_eq!(convert_signed_to_i32::<24>(0xFFFFFE), -2);
     }
 
+    /// Sign extension as originally written, with a mask that clippy flags as having no effect
+    #[allow(clippy::identity_op)]
+    fn masked_sign_extension<const BITS: u32>(mut input: u32) -> i32 {
+        if input & (1 << (BITS - 1)) != 0 {
+            input |= u32::MAX & !((1 << BITS) - 1);
+        }
+        input as i32
+    }
+
+    #[test]
+    fn same_as_masked_sign_extension() {
+        for input in 0..(1 << 20) {
+            assert_eq!(
+                convert_signed_to_i32::<20>(input),
+                masked_sign_extension::<20>(input)
+            );
+        }
+        for input in 0..(1 << 24) {
+            assert_eq!(
+                convert_signed_to_i32::<24>(input),
+                masked_sign_extension::<24>(input)
+            );
+        }
+    }
+
     // TODO: add unit tests for out-of-range
 }
@@ -15,7 +15,7 @@
 #[macro_export]
 macro_rules! trace {
     ($($arg:tt)*) => {
-        if !cfg!(test) {
+        if !cfg!(any(test, feature = "host")) {
             defmt::trace!($($arg)*);
         }
     };
@@ -24,7 +24,7 @@ macro_rules! trace {
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {
-        if !cfg!(test) {
+        if !cfg!(any(test, feature = "host")) {
             defmt::debug!($($arg)*);
         }
     };
@@ -33,7 +33,7 @@ macro_rules! debug {
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => {
-        if !cfg!(test) {
+        if !cfg!(any(test, feature = "host")) {
             defmt::info!($($arg)*);
         }
     };
@@ -42,7 +42,7 @@ macro_rules! info {
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => {
-        if !cfg!(test) {
+        if !cfg!(any(test, feature = "host")) {
             defmt::warn!($($arg)*);
         }
     };
@@ -51,7 +51,7 @@ macro_rules! warn {
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {
-        if !cfg!(test) {
+        if !cfg!(any(test, feature = "host")) {
             defmt::error!($($arg)*);
         }
     };
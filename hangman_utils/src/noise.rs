@@ -0,0 +1,288 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Noise statistics for ADC readings
+//!
+//! Used to characterize ADCs and load cells at rest, e.g. during PCB bring-up. Readings are
+//! processed one at a time so that long captures don't need to be buffered.
+
+use defmt::Format;
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// Averaging times, in samples, at which the Allan deviation is computed
+pub const ALLAN_TAUS: [u32; 6] = [1, 4, 16, 64, 256, 1024];
+
+/// Noise characterization results. All values are in ADC counts unless noted otherwise.
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Report {
+    pub n_samples: u32,
+    pub mean: f32,
+    /// Standard deviation of the readings
+    pub rms: f32,
+    pub peak_to_peak: u32,
+    /// Effective resolution in bits, based on the RMS noise
+    pub effective_bits: f32,
+    /// Noise-free resolution in bits, based on the peak-to-peak noise
+    pub noise_free_bits: f32,
+    /// Allan deviation at each of the averaging times in `ALLAN_TAUS`. NaN if there weren't
+    /// enough readings to compare at least two averages.
+    pub allan_deviation: [f32; ALLAN_TAUS.len()],
+}
+
+/// Non-overlapping Allan variance at one averaging time
+#[derive(Copy, Clone, Debug, Default)]
+struct Allan {
+    tau: u32,
+    block_sum: i64,
+    block_len: u32,
+    previous_mean: Option<f64>,
+    sum_of_squares: f64,
+    n_differences: u32,
+}
+
+impl Allan {
+    fn new(tau: u32) -> Self {
+        Self {
+            tau,
+            ..Default::default()
+        }
+    }
+
+    fn add_sample(&mut self, value: i32) {
+        self.block_sum += i64::from(value);
+        self.block_len += 1;
+        if self.block_len < self.tau {
+            return;
+        }
+        let mean = self.block_sum as f64 / f64::from(self.tau);
+        if let Some(previous_mean) = self.previous_mean {
+            self.sum_of_squares += (mean - previous_mean).powi(2);
+            self.n_differences += 1;
+        }
+        self.previous_mean = Some(mean);
+        self.block_sum = 0;
+        self.block_len = 0;
+    }
+
+    fn deviation(&self) -> f32 {
+        if self.n_differences == 0 {
+            return f32::NAN;
+        }
+        (self.sum_of_squares / (2.0 * f64::from(self.n_differences))).sqrt() as f32
+    }
+}
+
+/// Accumulates readings for a noise characterization
+#[derive(Copy, Clone, Debug)]
+pub struct Analyzer {
+    n_samples: u32,
+    // Running mean and sum of squared deviations, as per Welford's algorithm
+    mean: f64,
+    m2: f64,
+    min: i32,
+    max: i32,
+    allan: [Allan; ALLAN_TAUS.len()],
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self {
+            n_samples: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: i32::MAX,
+            max: i32::MIN,
+            allan: ALLAN_TAUS.map(Allan::new),
+        }
+    }
+
+    pub fn add_sample(&mut self, value: i32) {
+        self.n_samples += 1;
+        let delta = f64::from(value) - self.mean;
+        self.mean += delta / f64::from(self.n_samples);
+        self.m2 += delta * (f64::from(value) - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        for allan in &mut self.allan {
+            allan.add_sample(value);
+        }
+    }
+
+    /// `full_scale` is the largest positive reading the ADC can return. The ADC's range is assumed
+    /// to be symmetrical, as with two's complement output.
+    pub fn finish(&self, full_scale: i32) -> Report {
+        let range = 2.0 * (f64::from(full_scale) + 1.0);
+        let resolution_bits = |noise: f64| -> f32 {
+            // Noise below one count can't be resolved
+            (range / noise.max(1.0)).log2() as f32
+        };
+        let (rms, peak_to_peak) = if self.n_samples > 0 {
+            (
+                (self.m2 / f64::from(self.n_samples)).sqrt(),
+                self.max.abs_diff(self.min),
+            )
+        } else {
+            (0.0, 0)
+        };
+        Report {
+            n_samples: self.n_samples,
+            mean: self.mean as f32,
+            rms: rms as f32,
+            peak_to_peak,
+            effective_bits: resolution_bits(rms),
+            noise_free_bits: resolution_bits(f64::from(peak_to_peak)),
+            allan_deviation: self.allan.map(|allan| allan.deviation()),
+        }
+    }
+}
+
+/// Expected improvement in noise-free bits from averaging `ratio` conversions
+///
+/// Averaging N readings with uncorrelated noise reduces the noise by a factor of sqrt(N), i.e. half
+/// a bit for every doubling of N. Correlated noise such as drift doesn't average out, so treat this
+/// as an upper bound.
+pub fn oversampling_gain_bits(ratio: u32) -> f32 {
+    if ratio == 0 {
+        return 0.0;
+    }
+    0.5 * (ratio as f32).log2()
+}
+
+/// Largest oversampling ratio for an ADC converting at `sampling_rate_hz`
+///
+/// Leaves at least two readings per second, so that windows of half a second, e.g. for taring,
+/// hold at least one reading.
+pub fn max_oversampling_ratio(sampling_rate_hz: u32) -> u32 {
+    (sampling_rate_hz / 2).max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FULL_SCALE: i32 = (1 << 19) - 1;
+
+    fn analyze(values: impl IntoIterator<Item = i32>) -> Report {
+        let mut analyzer = Analyzer::new();
+        for value in values {
+            analyzer.add_sample(value);
+        }
+        analyzer.finish(FULL_SCALE)
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() <= tolerance, "{a} != {b}");
+    }
+
+    #[test]
+    fn constant() {
+        let report = analyze([1000; 10]);
+        assert_eq!(report.n_samples, 10);
+        assert_close(report.mean, 1000.0, 1e-6);
+        assert_close(report.rms, 0.0, 1e-6);
+        assert_eq!(report.peak_to_peak, 0);
+        // Limited by the ADC
+        assert_close(report.effective_bits, 20.0, 1e-6);
+        assert_close(report.noise_free_bits, 20.0, 1e-6);
+        assert_close(report.allan_deviation[0], 0.0, 1e-6);
+        assert_close(report.allan_deviation[1], 0.0, 1e-6);
+        // Not enough samples for the longer averaging times
+        assert!(report.allan_deviation[2..].iter().all(|dev| dev.is_nan()));
+    }
+
+    #[test]
+    fn square_wave() {
+        // Alternating readings have an RMS noise of 8 counts and 16 counts peak-to-peak
+        let report = analyze((0..4096).map(|i| if i % 2 == 0 { -8 } else { 8 }));
+        assert_close(report.mean, 0.0, 1e-6);
+        assert_close(report.rms, 8.0, 1e-6);
+        assert_eq!(report.peak_to_peak, 16);
+        assert_close(report.effective_bits, 17.0, 1e-6);
+        assert_close(report.noise_free_bits, 16.0, 1e-6);
+        // Consecutive readings differ by 16 counts, so the Allan variance is 16^2 / 2
+        assert_close(report.allan_deviation[0], 128.0_f32.sqrt(), 1e-4);
+        // Longer averages cancel out completely
+        assert!(report.allan_deviation[1..].iter().all(|&dev| dev == 0.0));
+    }
+
+    #[test]
+    fn drift() {
+        // A ramp of one count per reading: averages at tau samples apart differ by tau counts
+        let report = analyze(0..4096);
+        for (tau, dev) in ALLAN_TAUS.iter().zip(report.allan_deviation) {
+            assert_close(dev, *tau as f32 / 2.0_f32.sqrt(), 1e-3);
+        }
+    }
+
+    #[test]
+    fn white_noise() {
+        // Allan deviation of white noise falls with the square root of the averaging time
+        let mut state: u32 = 1;
+        let mut uniform = || {
+            // xorshift
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 + 1.0) / (u32::MAX as f32 + 2.0)
+        };
+        let report =
+            analyze((0..65536).map(|_| {
+                (100.0 * crate::sim::standard_normal(uniform(), uniform())).round() as i32
+            }));
+        assert_close(report.mean, 0.0, 2.0);
+        assert_close(report.rms, 100.0, 2.0);
+        assert_close(report.effective_bits, (1048576.0_f32 / 100.0).log2(), 0.05);
+        assert_close(report.allan_deviation[0], 100.0, 3.0);
+        assert_close(report.allan_deviation[1], 50.0, 3.0);
+        assert_close(report.allan_deviation[2], 25.0, 3.0);
+    }
+
+    #[test]
+    fn empty() {
+        let report = analyze([]);
+        assert_eq!(report.n_samples, 0);
+        assert!(report.allan_deviation.iter().all(|dev| dev.is_nan()));
+    }
+
+    #[test]
+    fn oversampling_gain() {
+        assert_eq!(oversampling_gain_bits(0), 0.0);
+        assert_eq!(oversampling_gain_bits(1), 0.0);
+        assert_eq!(oversampling_gain_bits(4), 1.0);
+        assert_eq!(oversampling_gain_bits(16), 2.0);
+        assert!((oversampling_gain_bits(8) - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn max_oversampling_ratio_leaves_half_second_window() {
+        // 10 Hz and 80 Hz are the rates of the supported ADCs
+        for rate in [10, 80].into_iter().chain(2..=255) {
+            let ratio = max_oversampling_ratio(rate);
+            assert!(ratio >= 1);
+            let output_rate = rate / ratio;
+            assert!(output_rate / 2 >= 1, "{rate} Hz / {ratio}");
+        }
+        assert_eq!(max_oversampling_ratio(80), 40);
+        assert_eq!(max_oversampling_ratio(10), 5);
+        assert_eq!(max_oversampling_ratio(1), 1);
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load cell self-test
+//!
+//! Raw ADC readings are taken at rest and checked against a handful of signatures that indicate a
+//! broken load cell or ADC. The checks are deliberately coarse: they're meant to catch a snapped
+//! wire, not to grade a healthy load cell.
+
+use defmt::Format;
+
+/// Faults that can be detected by the self-test
+///
+/// The discriminant is the fault code reported to BLE clients. Zero is reserved for "no fault".
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum Fault {
+    /// The ADC never signalled that data was ready
+    NoData = 0x01,
+    /// The ADC signalled data ready at an unexpected rate
+    DataRate = 0x02,
+    /// Readings are pinned at the ADC's full-scale limits, as with a broken excitation or signal
+    /// wire
+    OpenBridge = 0x03,
+    /// Readings are suspiciously quiet and sit at the ADC's own zero instead of the bridge's
+    /// offset, as with shorted signal wires
+    ShortedBridge = 0x04,
+    /// Readings are far away from the calibrated zero
+    ZeroOutOfRange = 0x05,
+    /// Readings are noisier than expected
+    ExcessiveNoise = 0x06,
+}
+
+impl Fault {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(Self::NoData),
+            0x02 => Some(Self::DataRate),
+            0x03 => Some(Self::OpenBridge),
+            0x04 => Some(Self::ShortedBridge),
+            0x05 => Some(Self::ZeroOutOfRange),
+            0x06 => Some(Self::ExcessiveNoise),
+            _ => None,
+        }
+    }
+}
+
+/// Share of the load cell's capacity by which the average reading may deviate from the calibrated
+/// zero, e.g. because of drift. Anything beyond that is a stuck or damaged cell, or a load left
+/// hanging at boot.
+pub const ZERO_WINDOW_PCT: u32 = 5;
+
+/// Allowed deviation from the calibrated zero of a load cell whose capacity is `capacity` counts
+pub fn zero_window(capacity: u32) -> i32 {
+    (u64::from(capacity) * u64::from(ZERO_WINDOW_PCT) / 100) as i32
+}
+
+/// Thresholds used to decide whether a set of readings is healthy
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Limits {
+    /// Largest positive reading the ADC can return
+    pub full_scale: i32,
+    /// Expected time between readings
+    pub expected_interval_us: u32,
+    /// Allowed deviation of the average time between readings, in percent
+    pub interval_tolerance_pct: u32,
+    /// Calibrated zero, i.e. the raw reading with nothing hanging from the scale
+    pub zero: i32,
+    /// Allowed deviation of the average reading from `zero`
+    pub zero_window: i32,
+    /// Maximum allowed peak-to-peak noise
+    pub max_peak_to_peak: u32,
+    /// Readings whose average is within this distance of the ADC's zero and whose peak-to-peak
+    /// noise is within this value look like a shorted bridge, unless the calibrated zero is also
+    /// within this distance of the ADC's zero
+    pub short_window: i32,
+}
+
+/// Self-test results
+#[derive(Copy, Clone, Debug, Default, Format)]
+pub struct Report {
+    pub fault: Option<Fault>,
+    pub n_samples: u32,
+    pub mean: i32,
+    pub peak_to_peak: u32,
+    /// Average time between readings
+    pub mean_interval_us: u32,
+}
+
+/// Accumulates readings for the self-test without buffering them
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Analyzer {
+    n_samples: u32,
+    sum: i64,
+    min: i32,
+    max: i32,
+    first_timestamp_us: u64,
+    last_timestamp_us: u64,
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sample(&mut self, timestamp_us: u64, value: i32) {
+        if self.n_samples == 0 {
+            self.min = value;
+            self.max = value;
+            self.first_timestamp_us = timestamp_us;
+        }
+        self.n_samples += 1;
+        self.sum += i64::from(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.last_timestamp_us = timestamp_us;
+    }
+
+    pub fn finish(&self, limits: &Limits) -> Report {
+        if self.n_samples == 0 {
+            return Report {
+                fault: Some(Fault::NoData),
+                ..Default::default()
+            };
+        }
+
+        let mean = (self.sum / i64::from(self.n_samples)) as i32;
+        let peak_to_peak = self.max.abs_diff(self.min);
+        let mean_interval_us = if self.n_samples > 1 {
+            ((self.last_timestamp_us - self.first_timestamp_us) / u64::from(self.n_samples - 1))
+                as u32
+        } else {
+            0
+        };
+        let mut report = Report {
+            fault: None,
+            n_samples: self.n_samples,
+            mean,
+            peak_to_peak,
+            mean_interval_us,
+        };
+
+        let interval_tolerance = limits.expected_interval_us * limits.interval_tolerance_pct / 100;
+        // Treat anything within 1% of either rail as saturated
+        let saturation_threshold = limits.full_scale - limits.full_scale / 100;
+
+        report.fault = if self.n_samples > 1
+            && mean_interval_us.abs_diff(limits.expected_interval_us) > interval_tolerance
+        {
+            Some(Fault::DataRate)
+        } else if self.max >= saturation_threshold || self.min <= -saturation_threshold {
+            Some(Fault::OpenBridge)
+        } else if mean.abs() <= limits.short_window
+            && peak_to_peak <= limits.short_window.unsigned_abs()
+            && limits.zero.unsigned_abs() > limits.short_window.unsigned_abs()
+        {
+            Some(Fault::ShortedBridge)
+        } else if mean.abs_diff(limits.zero) > limits.zero_window.unsigned_abs() {
+            Some(Fault::ZeroOutOfRange)
+        } else if peak_to_peak > limits.max_peak_to_peak {
+            Some(Fault::ExcessiveNoise)
+        } else {
+            None
+        };
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        full_scale: (1 << 19) - 1,
+        expected_interval_us: 12_500,
+        interval_tolerance_pct: 20,
+        zero: -100_000,
+        // 5% of a capacity of 1M counts
+        zero_window: 50_000,
+        max_peak_to_peak: 1_000,
+        short_window: 50,
+    };
+
+    fn analyze(values: &[i32], interval_us: u64) -> Report {
+        let mut analyzer = Analyzer::new();
+        for (i, &value) in values.iter().enumerate() {
+            analyzer.add_sample(i as u64 * interval_us, value);
+        }
+        analyzer.finish(&LIMITS)
+    }
+
+    #[test]
+    fn healthy() {
+        let report = analyze(&[-100_010, -99_990, -100_005, -99_995], 12_500);
+        assert_eq!(report.fault, None);
+        assert_eq!(report.n_samples, 4);
+        assert_eq!(report.mean, -100_000);
+        assert_eq!(report.peak_to_peak, 20);
+        assert_eq!(report.mean_interval_us, 12_500);
+    }
+
+    #[test]
+    fn no_data() {
+        assert_eq!(analyze(&[], 12_500).fault, Some(Fault::NoData));
+    }
+
+    #[test]
+    fn data_rate() {
+        let report = analyze(&[-100_000, -100_000, -100_000], 100_000);
+        assert_eq!(report.fault, Some(Fault::DataRate));
+    }
+
+    #[test]
+    fn open_bridge() {
+        let report = analyze(&[-100_000, LIMITS.full_scale, -100_000], 12_500);
+        assert_eq!(report.fault, Some(Fault::OpenBridge));
+        let report = analyze(&[-LIMITS.full_scale - 1; 3], 12_500);
+        assert_eq!(report.fault, Some(Fault::OpenBridge));
+    }
+
+    #[test]
+    fn shorted_bridge() {
+        let report = analyze(&[-3, 2, 0, 4], 12_500);
+        assert_eq!(report.fault, Some(Fault::ShortedBridge));
+    }
+
+    #[test]
+    fn zero_out_of_range() {
+        let report = analyze(&[200_000, 200_010], 12_500);
+        assert_eq!(report.fault, Some(Fault::ZeroOutOfRange));
+    }
+
+    #[test]
+    fn zero_window_of_capacity() {
+        assert_eq!(zero_window(1_000_000), LIMITS.zero_window);
+        assert_eq!(zero_window(u32::MAX), 214_748_364);
+        // Within 5% of capacity of the calibrated zero
+        assert_eq!(analyze(&[-60_000, -60_010], 12_500).fault, None);
+        // A load or offset of 6% of capacity
+        let report = analyze(&[-40_000, -40_010], 12_500);
+        assert_eq!(report.fault, Some(Fault::ZeroOutOfRange));
+    }
+
+    #[test]
+    fn excessive_noise() {
+        let report = analyze(&[-105_000, -95_000, -100_000], 12_500);
+        assert_eq!(report.fault, Some(Fault::ExcessiveNoise));
+    }
+
+    #[test]
+    fn fault_codes() {
+        for fault in [
+            Fault::NoData,
+            Fault::DataRate,
+            Fault::OpenBridge,
+            Fault::ShortedBridge,
+            Fault::ZeroOutOfRange,
+            Fault::ExcessiveNoise,
+        ] {
+            assert_eq!(Fault::from_code(fault.code()), Some(fault));
+        }
+        assert_eq!(Fault::from_code(0), None);
+    }
+}
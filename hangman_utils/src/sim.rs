@@ -0,0 +1,234 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scripted force profiles for simulating a load cell
+
+use core::f32::consts::PI;
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// One piece of a force profile
+#[derive(Copy, Clone, Debug)]
+pub enum Segment<'a> {
+    /// Constant weight
+    Hold { kg: f32, duration_s: f32 },
+    /// Linear change from one weight to another
+    Ramp {
+        from_kg: f32,
+        to_kg: f32,
+        duration_s: f32,
+    },
+    /// Sine wave starting at `offset_kg` and heading upwards
+    Sine {
+        offset_kg: f32,
+        amplitude_kg: f32,
+        period_s: f32,
+        duration_s: f32,
+    },
+    /// Recorded trace of weights in kg, stored as little-endian `f32`s
+    Trace {
+        samples: &'a [u8],
+        sample_rate_hz: f32,
+    },
+}
+
+impl Segment<'_> {
+    pub fn duration_s(&self) -> f32 {
+        match *self {
+            Segment::Hold { duration_s, .. }
+            | Segment::Ramp { duration_s, .. }
+            | Segment::Sine { duration_s, .. } => duration_s,
+            Segment::Trace {
+                samples,
+                sample_rate_hz,
+            } => (samples.len() / 4) as f32 / sample_rate_hz,
+        }
+    }
+
+    /// Weight at `t_s` seconds after the start of the segment
+    fn weight_at(&self, t_s: f32) -> f32 {
+        match *self {
+            Segment::Hold { kg, .. } => kg,
+            Segment::Ramp {
+                from_kg,
+                to_kg,
+                duration_s,
+            } => from_kg + (to_kg - from_kg) * (t_s / duration_s).min(1.0),
+            Segment::Sine {
+                offset_kg,
+                amplitude_kg,
+                period_s,
+                ..
+            } => offset_kg + amplitude_kg * (2.0 * PI * t_s / period_s).sin(),
+            Segment::Trace {
+                samples,
+                sample_rate_hz,
+            } => {
+                let n_samples = samples.len() / 4;
+                if n_samples == 0 {
+                    return 0.0;
+                }
+                let i = ((t_s * sample_rate_hz) as usize).min(n_samples - 1);
+                f32::from_le_bytes(samples[4 * i..4 * i + 4].try_into().unwrap())
+            }
+        }
+    }
+}
+
+/// A sequence of segments that repeats forever
+#[derive(Copy, Clone, Debug)]
+pub struct Profile<'a> {
+    segments: &'a [Segment<'a>],
+}
+
+impl<'a> Profile<'a> {
+    pub const fn new(segments: &'a [Segment<'a>]) -> Self {
+        Self { segments }
+    }
+
+    pub fn duration_s(&self) -> f32 {
+        self.segments.iter().map(Segment::duration_s).sum()
+    }
+
+    /// Weight at `t_s` seconds after the start of the profile
+    pub fn weight_at(&self, t_s: f32) -> f32 {
+        let duration_s = self.duration_s();
+        if duration_s <= 0.0 {
+            return 0.0;
+        }
+        let mut t_s = t_s - (t_s / duration_s).floor() * duration_s;
+        for segment in self.segments {
+            if t_s < segment.duration_s() {
+                return segment.weight_at(t_s);
+            }
+            t_s -= segment.duration_s();
+        }
+        // Only reachable through floating point rounding at the very end of the profile
+        self.segments
+            .last()
+            .map_or(0.0, |segment| segment.weight_at(segment.duration_s()))
+    }
+}
+
+/// Convert two uniformly-distributed numbers in (0, 1] into a normally-distributed number with
+/// zero mean and unit standard deviation using the Box-Muller transform
+pub fn standard_normal(u1: f32, u2: f32) -> f32 {
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn steps() {
+        let segments = [
+            Segment::Hold {
+                kg: 0.0,
+                duration_s: 2.0,
+            },
+            Segment::Hold {
+                kg: 20.0,
+                duration_s: 3.0,
+            },
+        ];
+        let profile = Profile::new(&segments);
+        assert_close(profile.duration_s(), 5.0);
+        assert_close(profile.weight_at(0.0), 0.0);
+        assert_close(profile.weight_at(1.9), 0.0);
+        assert_close(profile.weight_at(2.0), 20.0);
+        assert_close(profile.weight_at(4.9), 20.0);
+        // Loops back around
+        assert_close(profile.weight_at(5.5), 0.0);
+        assert_close(profile.weight_at(12.5), 20.0);
+    }
+
+    #[test]
+    fn ramp() {
+        let segments = [Segment::Ramp {
+            from_kg: 10.0,
+            to_kg: 30.0,
+            duration_s: 4.0,
+        }];
+        let profile = Profile::new(&segments);
+        assert_close(profile.weight_at(0.0), 10.0);
+        assert_close(profile.weight_at(1.0), 15.0);
+        assert_close(profile.weight_at(3.0), 25.0);
+    }
+
+    #[test]
+    fn sine() {
+        let segments = [Segment::Sine {
+            offset_kg: 20.0,
+            amplitude_kg: 5.0,
+            period_s: 4.0,
+            duration_s: 8.0,
+        }];
+        let profile = Profile::new(&segments);
+        assert_close(profile.weight_at(0.0), 20.0);
+        assert_close(profile.weight_at(1.0), 25.0);
+        assert_close(profile.weight_at(3.0), 15.0);
+        assert_close(profile.weight_at(6.0), 20.0);
+    }
+
+    #[test]
+    fn trace() {
+        let samples: Vec<u8> = [1.0_f32, 2.0, 3.0, 4.0]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let segments = [Segment::Trace {
+            samples: &samples,
+            sample_rate_hz: 2.0,
+        }];
+        let profile = Profile::new(&segments);
+        assert_close(profile.duration_s(), 2.0);
+        assert_close(profile.weight_at(0.0), 1.0);
+        assert_close(profile.weight_at(0.6), 2.0);
+        assert_close(profile.weight_at(1.9), 4.0);
+    }
+
+    #[test]
+    fn empty() {
+        let segments = [Segment::Trace {
+            samples: &[],
+            sample_rate_hz: 80.0,
+        }];
+        assert_close(Profile::new(&segments).weight_at(1.0), 0.0);
+        assert_close(Profile::new(&[]).weight_at(1.0), 0.0);
+    }
+
+    #[test]
+    fn normal_distribution() {
+        assert_close(standard_normal(1.0, 0.0), 0.0);
+        let n = 100;
+        let samples: Vec<f32> = (0..n * n)
+            .map(|i| {
+                let u1 = ((i / n) as f32 + 0.5) / n as f32;
+                let u2 = ((i % n) as f32 + 0.5) / n as f32;
+                standard_normal(u1, u2)
+            })
+            .collect();
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.01, "mean = {mean}");
+        assert!((variance - 1.0).abs() < 0.05, "variance = {variance}");
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stability-gated averaging, e.g. for capturing calibration points
+//!
+//! Readings are split into blocks. A block is stable if its standard deviation is small and its
+//! mean is close to the previous block's. Once enough consecutive blocks are stable, their readings
+//! are averaged. An unstable block, e.g. because the weight is still swinging, starts over.
+
+use defmt::Format;
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Settings {
+    /// Number of readings per block
+    pub block_len: u32,
+    /// Number of consecutive stable blocks to average
+    pub n_blocks: u32,
+    /// Largest standard deviation within a block, and largest change in mean between consecutive
+    /// blocks, that are considered stable
+    pub max_std_dev: f64,
+}
+
+/// Average of a stable stretch of readings
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Point {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub n_samples: u32,
+}
+
+/// Count, mean, and sum of squared deviations, as per Welford's algorithm
+#[derive(Copy, Clone, Debug, Default)]
+struct Stats {
+    n: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl Stats {
+    fn add(&mut self, value: f64) {
+        self.n += 1;
+        let delta = value - self.mean;
+        self.mean += delta / f64::from(self.n);
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Combine two sets of statistics, as per Chan et al.
+    fn merge(&mut self, other: &Stats) {
+        if other.n == 0 {
+            return;
+        }
+        let n = self.n + other.n;
+        let delta = other.mean - self.mean;
+        self.mean += delta * f64::from(other.n) / f64::from(n);
+        self.m2 += other.m2 + delta * delta * f64::from(self.n) * f64::from(other.n) / f64::from(n);
+        self.n = n;
+    }
+
+    fn std_dev(&self) -> f64 {
+        if self.n == 0 {
+            return 0.0;
+        }
+        (self.m2 / f64::from(self.n)).sqrt()
+    }
+}
+
+pub struct Capture {
+    settings: Settings,
+    block: Stats,
+    previous_block_mean: Option<f64>,
+    /// Consecutive stable blocks so far
+    stable: Stats,
+    n_stable_blocks: u32,
+}
+
+impl Capture {
+    pub fn new(settings: Settings) -> Self {
+        assert!(settings.block_len > 0 && settings.n_blocks > 0);
+        Self {
+            settings,
+            block: Stats::default(),
+            previous_block_mean: None,
+            stable: Stats::default(),
+            n_stable_blocks: 0,
+        }
+    }
+
+    /// Add a reading. Returns the captured point once enough consecutive blocks are stable.
+    pub fn add_sample(&mut self, value: f64) -> Option<Point> {
+        self.block.add(value);
+        if self.block.n < self.settings.block_len {
+            return None;
+        }
+
+        let block = core::mem::take(&mut self.block);
+        let settled = self
+            .previous_block_mean
+            .is_none_or(|previous| (block.mean - previous).abs() <= self.settings.max_std_dev);
+        let quiet = block.std_dev() <= self.settings.max_std_dev;
+        self.previous_block_mean = Some(block.mean);
+        if !quiet {
+            self.stable = Stats::default();
+            self.n_stable_blocks = 0;
+            return None;
+        }
+        if !settled {
+            // This block is quiet, so it's a fine start for the next stretch
+            self.stable = Stats::default();
+            self.n_stable_blocks = 0;
+        }
+        self.stable.merge(&block);
+        self.n_stable_blocks += 1;
+        if self.n_stable_blocks < self.settings.n_blocks {
+            return None;
+        }
+
+        let point = Point {
+            mean: self.stable.mean,
+            std_dev: self.stable.std_dev(),
+            n_samples: self.stable.n,
+        };
+        self.stable = Stats::default();
+        self.n_stable_blocks = 0;
+        Some(point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SETTINGS: Settings = Settings {
+        block_len: 4,
+        n_blocks: 2,
+        max_std_dev: 1.0,
+    };
+
+    /// Feed readings until a point is captured. Returns the point and the number of readings used.
+    fn capture(values: impl IntoIterator<Item = f64>) -> Option<(Point, usize)> {
+        let mut capture = Capture::new(SETTINGS);
+        values
+            .into_iter()
+            .enumerate()
+            .find_map(|(i, value)| capture.add_sample(value).map(|point| (point, i + 1)))
+    }
+
+    #[test]
+    fn steady() {
+        let (point, used) = capture([10.0, 10.5, 9.5, 10.0, 10.0, 10.5, 9.5, 10.0]).unwrap();
+        assert_eq!(used, 8);
+        assert_eq!(point.n_samples, 8);
+        assert!((point.mean - 10.0).abs() < 1e-9);
+        // sqrt(mean of squared deviations) = sqrt((4 * 0.25) / 8)
+        assert!((point.std_dev - 0.125_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swinging_then_steady() {
+        let swinging = [0.0, 20.0, -20.0, 10.0];
+        let steady = [5.0; 8];
+        let (point, used) = capture(swinging.into_iter().chain(steady)).unwrap();
+        assert_eq!(used, 12);
+        assert_eq!(point.mean, 5.0);
+        assert_eq!(point.std_dev, 0.0);
+    }
+
+    #[test]
+    fn settling() {
+        // Each block is quiet, but the mean creeps until the weight settles
+        let values = [0.0, 0.0, 0.0, 0.0, 3.0, 3.0, 3.0, 3.0, 3.5, 3.5, 3.5, 3.5];
+        let (point, used) = capture(values).unwrap();
+        assert_eq!(used, 12);
+        assert_eq!(point.n_samples, 8);
+        assert!((point.mean - 3.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn never_stable() {
+        let values = (0..100).map(|i| if i % 2 == 0 { 0.0 } else { 10.0 });
+        assert!(capture(values).is_none());
+    }
+
+    #[test]
+    fn merge() {
+        let values = [1.0, 2.0, 4.0, 8.0, 16.0];
+        let mut all = Stats::default();
+        let mut first = Stats::default();
+        let mut second = Stats::default();
+        for (i, &value) in values.iter().enumerate() {
+            all.add(value);
+            if i < 2 {
+                first.add(value);
+            } else {
+                second.add(value);
+            }
+        }
+        first.merge(&second);
+        assert_eq!(first.n, all.n);
+        assert!((first.mean - all.mean).abs() < 1e-9);
+        assert!((first.m2 - all.m2).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Packets of several timestamped weights, for streaming at high sample rates
+//!
+//! Each packet is a header followed by up to as many samples as fit into one notification. All
+//! values are little-endian.
+//!
+//! | Offset | Size | Field                                                                  |
+//! | ------ | ---- | ---------------------------------------------------------------------- |
+//! | 0      | 2    | Sequence number (`u16`), incremented for every packet and wrapping     |
+//! | 2      | 1    | Number of samples                                                      |
+//! | 3      | 1    | Format version, currently 1                                            |
+//! | 4      | 4    | Timestamp of the first sample in µs since the start of the measurement |
+//!
+//! Each sample is 6 bytes:
+//!
+//! | Offset | Size | Field                                                                  |
+//! | ------ | ---- | ---------------------------------------------------------------------- |
+//! | 0      | 2    | Time since the first sample of the packet in units of 100 µs (`u16`)   |
+//! | 2      | 4    | Tared weight in kg (`f32`)                                             |
+//!
+//! A gap in sequence numbers means that packets were lost.
+
+pub const HEADER_SIZE: usize = 8;
+pub const SAMPLE_SIZE: usize = 6;
+/// Largest packet, fitting into a notification with the largest ATT MTU of 247 bytes
+pub const MAX_PACKET_SIZE: usize = 244;
+const VERSION: u8 = 1;
+const US_PER_OFFSET_LSB: u32 = 100;
+/// Longest time that a sample is held back before its packet is sent, so that live displays stay
+/// responsive
+pub const MAX_LATENCY_US: u32 = 50_000;
+
+/// Number of samples that fit into a packet of `size` bytes
+pub const fn capacity(size: usize) -> usize {
+    size.saturating_sub(HEADER_SIZE) / SAMPLE_SIZE
+}
+
+/// Collects samples into packets
+pub struct Packer {
+    data: [u8; MAX_PACKET_SIZE],
+    /// Number of samples in the current packet
+    len: usize,
+    /// Largest packet to send, in bytes
+    max_size: usize,
+    sequence: u16,
+}
+
+impl Packer {
+    /// Packer for packets of up to `max_size` bytes
+    pub fn new(max_size: usize) -> Self {
+        let mut packer = Self {
+            data: [0; MAX_PACKET_SIZE],
+            len: 0,
+            max_size: HEADER_SIZE,
+            sequence: 0,
+        };
+        packer.set_max_size(max_size);
+        packer
+    }
+
+    /// Change the size of packets, e.g. once the ATT MTU changes. Always fits at least one sample.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size.clamp(HEADER_SIZE + SAMPLE_SIZE, MAX_PACKET_SIZE);
+    }
+
+    fn base_us(&self) -> u32 {
+        u32::from_le_bytes(self.data[4..8].try_into().unwrap())
+    }
+
+    /// Add a sample, calling `send` with each packet that's ready: when it's full or its first
+    /// sample is `MAX_LATENCY_US` old
+    pub fn add_sample(&mut self, timestamp_us: u32, weight_kg: f32, mut send: impl FnMut(&[u8])) {
+        if self.len > 0 {
+            // A new measurement starts over at zero, and offsets only fit so much time
+            let fits = timestamp_us
+                .checked_sub(self.base_us())
+                .is_some_and(|elapsed| elapsed / US_PER_OFFSET_LSB <= u32::from(u16::MAX));
+            if !fits || self.len >= capacity(self.max_size) {
+                self.flush(&mut send);
+            }
+        }
+        if self.len == 0 {
+            self.data[4..8].copy_from_slice(&timestamp_us.to_le_bytes());
+        }
+        let elapsed_us = timestamp_us - self.base_us();
+        let offset = (elapsed_us / US_PER_OFFSET_LSB) as u16;
+        let start = HEADER_SIZE + self.len * SAMPLE_SIZE;
+        self.data[start..start + 2].copy_from_slice(&offset.to_le_bytes());
+        self.data[start + 2..start + 6].copy_from_slice(&weight_kg.to_le_bytes());
+        self.len += 1;
+        if self.len >= capacity(self.max_size) || elapsed_us >= MAX_LATENCY_US {
+            self.flush(&mut send);
+        }
+    }
+
+    /// Whether there are no samples waiting to be sent
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Send the current packet, if it has any samples
+    pub fn flush(&mut self, mut send: impl FnMut(&[u8])) {
+        if self.len == 0 {
+            return;
+        }
+        self.data[0..2].copy_from_slice(&self.sequence.to_le_bytes());
+        self.data[2] = self.len as u8;
+        self.data[3] = VERSION;
+        send(&self.data[..HEADER_SIZE + self.len * SAMPLE_SIZE]);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.len = 0;
+    }
+}
+
+/// Header of a packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub sequence: u16,
+    pub n_samples: u8,
+    pub base_us: u32,
+}
+
+/// Decode a packet into its header and (timestamp in µs, weight in kg) samples. Returns `None` if
+/// it's malformed or of an unknown version.
+pub fn decode(packet: &[u8]) -> Option<(Header, impl Iterator<Item = (u32, f32)> + '_)> {
+    if packet.len() < HEADER_SIZE || packet[3] != VERSION {
+        return None;
+    }
+    let header = Header {
+        sequence: u16::from_le_bytes([packet[0], packet[1]]),
+        n_samples: packet[2],
+        base_us: u32::from_le_bytes(packet[4..8].try_into().unwrap()),
+    };
+    let samples = &packet[HEADER_SIZE..];
+    if samples.len() != usize::from(header.n_samples) * SAMPLE_SIZE {
+        return None;
+    }
+    let samples = samples.chunks_exact(SAMPLE_SIZE).map(move |sample| {
+        let offset = u16::from_le_bytes([sample[0], sample[1]]);
+        let weight = f32::from_le_bytes(sample[2..6].try_into().unwrap());
+        (
+            header.base_us + u32::from(offset) * US_PER_OFFSET_LSB,
+            weight,
+        )
+    });
+    Some((header, samples))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn collect(packer: &mut Packer, samples: &[(u32, f32)]) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        for &(timestamp_us, weight) in samples {
+            packer.add_sample(timestamp_us, weight, |packet| packets.push(packet.to_vec()));
+        }
+        packets
+    }
+
+    #[test]
+    fn round_trip() {
+        // Room for two samples
+        let mut packer = Packer::new(HEADER_SIZE + 2 * SAMPLE_SIZE + 1);
+        let packets = collect(&mut packer, &[(1000, 1.5), (1500, 2.5), (2000, 3.5)]);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0][..4], [0, 0, 2, VERSION]);
+        let (header, samples) = decode(&packets[0]).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                sequence: 0,
+                n_samples: 2,
+                base_us: 1000
+            }
+        );
+        assert_eq!(samples.collect::<Vec<_>>(), [(1000, 1.5), (1500, 2.5)]);
+
+        let mut packets = Vec::new();
+        packer.flush(|packet| packets.push(packet.to_vec()));
+        let (header, mut samples) = decode(&packets[0]).unwrap();
+        assert_eq!(header.sequence, 1);
+        assert_eq!(samples.next(), Some((2000, 3.5)));
+    }
+
+    #[test]
+    fn sends_within_latency() {
+        let mut packer = Packer::new(MAX_PACKET_SIZE);
+        let packets = collect(&mut packer, &[(0, 1.0), (MAX_LATENCY_US / 2, 2.0)]);
+        assert!(packets.is_empty());
+        assert!(!packer.is_empty());
+        let packets = collect(&mut packer, &[(MAX_LATENCY_US, 3.0)]);
+        assert_eq!(decode(&packets[0]).unwrap().1.count(), 3);
+    }
+
+    #[test]
+    fn restarts_with_measurement() {
+        let mut packer = Packer::new(MAX_PACKET_SIZE);
+        let packets = collect(&mut packer, &[(40_000, 1.0), (0, 2.0)]);
+        assert_eq!(packets.len(), 1);
+        let (header, samples) = decode(&packets[0]).unwrap();
+        assert_eq!(header.base_us, 40_000);
+        assert_eq!(samples.count(), 1);
+    }
+
+    #[test]
+    fn capacity_of_packets() {
+        assert_eq!(capacity(MAX_PACKET_SIZE), 39);
+        assert_eq!(capacity(20), 2);
+        assert_eq!(capacity(0), 0);
+        assert!(decode(&[0, 0, 1, VERSION, 0, 0, 0, 0]).is_none());
+    }
+}
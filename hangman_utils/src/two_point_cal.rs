@@ -36,6 +36,66 @@ pub struct Constants<Reading> {
     pub b: Reading,
 }
 
+/// Reasons for rejecting a calibration
+///
+/// The discriminant is the code reported to BLE clients. Zero is reserved for "accepted".
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum Rejection {
+    /// The zero point or the reference point is missing
+    MissingPoint = 0x01,
+    /// Both points have the same reading, or their difference doesn't fit in a reading
+    DegenerateReadings = 0x02,
+    /// The reference weight is not positive or exceeds the capacity of the scale
+    WeightOutOfRange = 0x03,
+    /// The readings moved in the opposite direction of the weight
+    WrongSign = 0x04,
+    /// The resulting slope is implausible for the load cell and ADC
+    SlopeOutOfRange = 0x05,
+}
+
+impl Rejection {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(Self::MissingPoint),
+            0x02 => Some(Self::DegenerateReadings),
+            0x03 => Some(Self::WeightOutOfRange),
+            0x04 => Some(Self::WrongSign),
+            0x05 => Some(Self::SlopeOutOfRange),
+            _ => None,
+        }
+    }
+}
+
+/// Bounds for plausible calibrations
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Limits {
+    /// Capacity of the scale. Reference weights must be positive and no heavier than this.
+    pub capacity: f32,
+    /// Smallest plausible `m`
+    pub min_m: f32,
+    /// Largest plausible `m`
+    pub max_m: f32,
+}
+
+impl<Reading> Constants<Reading> {
+    /// Check the slope for plausibility, e.g. for constants that didn't come from `TwoPoint`
+    pub fn validate(&self, limits: &Limits) -> Result<(), Rejection> {
+        if self.m < 0.0 {
+            return Err(Rejection::WrongSign);
+        }
+        // Also rejects NaN
+        if !(limits.min_m..=limits.max_m).contains(&self.m) {
+            return Err(Rejection::SlopeOutOfRange);
+        }
+        Ok(())
+    }
+}
+
 impl<Reading: PrimInt + Format> TwoPoint<Reading> {
     pub fn add_point(&mut self, point: CalPoint<Reading>) {
         crate::debug!("New calibration point: {}", point);
@@ -46,6 +106,16 @@ impl<Reading: PrimInt + Format> TwoPoint<Reading> {
         }
     }
 
+    /// Reading at zero weight
+    pub fn zero(&self) -> Option<Reading> {
+        self.zero
+    }
+
+    /// Point with a non-zero reference weight
+    pub fn reference(&self) -> Option<CalPoint<Reading>> {
+        self.other
+    }
+
     pub fn get_cal_constants(&self) -> Option<Constants<Reading>> {
         let other = self.other?;
         let b = self.zero?;
@@ -60,6 +130,25 @@ impl<Reading: PrimInt + Format> TwoPoint<Reading> {
         let m = other.expected_value / denominator.to_f32().unwrap();
         Some(Constants { m, b })
     }
+
+    /// Like `get_cal_constants`, but also check the resulting constants for plausibility
+    pub fn get_validated_cal_constants(
+        &self,
+        limits: &Limits,
+    ) -> Result<Constants<Reading>, Rejection> {
+        let (Some(_), Some(other)) = (self.zero, self.other) else {
+            return Err(Rejection::MissingPoint);
+        };
+        // Written to also reject NaN
+        if !(other.expected_value > 0.0 && other.expected_value <= limits.capacity) {
+            return Err(Rejection::WeightOutOfRange);
+        }
+        let constants = self
+            .get_cal_constants()
+            .ok_or(Rejection::DegenerateReadings)?;
+        constants.validate(limits)?;
+        Ok(constants)
+    }
 }
 
 #[cfg(test)]
@@ -83,4 +172,111 @@ mod test {
         assert_eq!((zero.measured_value - b) as f32 * m, zero.expected_value);
         assert_eq!((other.measured_value - b) as f32 * m, other.expected_value);
     }
+
+    const LIMITS: Limits = Limits {
+        capacity: 150.0,
+        min_m: 1e-6,
+        max_m: 1e-3,
+    };
+
+    fn validate(zero: Option<i32>, other: Option<(f32, i32)>) -> Result<f32, Rejection> {
+        let mut cal = TwoPoint::default();
+        if let Some(measured_value) = zero {
+            cal.add_point(CalPoint {
+                expected_value: 0.0,
+                measured_value,
+            });
+        }
+        if let Some((expected_value, measured_value)) = other {
+            cal.add_point(CalPoint {
+                expected_value,
+                measured_value,
+            });
+        }
+        cal.get_validated_cal_constants(&LIMITS)
+            .map(|constants| constants.m)
+    }
+
+    #[test]
+    fn valid() {
+        let m = validate(Some(1_000), Some((100.0, 1_001_000))).unwrap();
+        assert_eq!(m, 1e-4);
+    }
+
+    #[test]
+    fn missing_point() {
+        assert_eq!(
+            validate(None, Some((100.0, 1_000))),
+            Err(Rejection::MissingPoint)
+        );
+        assert_eq!(validate(Some(1_000), None), Err(Rejection::MissingPoint));
+    }
+
+    #[test]
+    fn degenerate_readings() {
+        assert_eq!(
+            validate(Some(1_000), Some((100.0, 1_000))),
+            Err(Rejection::DegenerateReadings)
+        );
+        assert_eq!(
+            validate(Some(i32::MAX), Some((100.0, i32::MIN))),
+            Err(Rejection::DegenerateReadings)
+        );
+    }
+
+    #[test]
+    fn weight_out_of_range() {
+        for weight in [-10.0, 151.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                validate(Some(1_000), Some((weight, 1_001_000))),
+                Err(Rejection::WeightOutOfRange)
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_sign() {
+        assert_eq!(
+            validate(Some(1_000), Some((100.0, -999_000))),
+            Err(Rejection::WrongSign)
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let validate = |m| Constants { m, b: 0 }.validate(&LIMITS);
+        assert_eq!(validate(1e-4), Ok(()));
+        assert_eq!(validate(-1e-4), Err(Rejection::WrongSign));
+        assert_eq!(validate(1.0), Err(Rejection::SlopeOutOfRange));
+        assert_eq!(validate(f32::NAN), Err(Rejection::SlopeOutOfRange));
+    }
+
+    #[test]
+    fn slope_out_of_range() {
+        // 120 kg entered as a big-endian float
+        let big_endian = f32::from_le_bytes(120.0_f32.to_be_bytes());
+        assert_eq!(
+            validate(Some(1_000), Some((big_endian, 1_001_000))),
+            Err(Rejection::SlopeOutOfRange)
+        );
+        // Barely any change in readings
+        assert_eq!(
+            validate(Some(1_000), Some((100.0, 1_010))),
+            Err(Rejection::SlopeOutOfRange)
+        );
+    }
+
+    #[test]
+    fn rejection_codes() {
+        for rejection in [
+            Rejection::MissingPoint,
+            Rejection::DegenerateReadings,
+            Rejection::WeightOutOfRange,
+            Rejection::WrongSign,
+            Rejection::SlopeOutOfRange,
+        ] {
+            assert_eq!(Rejection::from_code(rejection.code()), Some(rejection));
+        }
+        assert_eq!(Rejection::from_code(0), None);
+    }
 }
@@ -0,0 +1,207 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measurement units
+//!
+//! The scale is calibrated with reference masses, so its native unit is kg as hung at the scale's
+//! location. Converting to a force needs the local gravitational acceleration, which varies by
+//! about 0.5% across the Earth's surface.
+
+use defmt::Format;
+
+/// Standard gravity in m/s², as used to define kgf
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+/// Plausible range of gravity on the Earth's surface, with some margin, in m/s²
+pub const GRAVITY_RANGE: core::ops::RangeInclusive<f32> = 9.7..=9.9;
+pub const KG_PER_LB: f32 = 0.453_592_37;
+
+/// The discriminant is the code used by BLE clients
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum Unit {
+    #[default]
+    Kilogram = 0,
+    Pound = 1,
+    Newton = 2,
+    /// Kilogram-force, i.e. the force on a kg under standard gravity
+    KilogramForce = 3,
+}
+
+impl Unit {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Kilogram),
+            1 => Some(Self::Pound),
+            2 => Some(Self::Newton),
+            3 => Some(Self::KilogramForce),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Kilogram => "kg",
+            Self::Pound => "lb",
+            Self::Newton => "N",
+            Self::KilogramForce => "kgf",
+        }
+    }
+
+    /// Inverse of `symbol`, ignoring case
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        [
+            Self::Kilogram,
+            Self::Pound,
+            Self::Newton,
+            Self::KilogramForce,
+        ]
+        .into_iter()
+        .find(|unit| unit.symbol().eq_ignore_ascii_case(symbol))
+    }
+}
+
+/// Output unit along with the local gravity needed to convert to forces
+#[derive(Copy, Clone, Debug, PartialEq, Format)]
+pub struct Units {
+    pub unit: Unit,
+    /// Local gravitational acceleration in m/s²
+    pub gravity: f32,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Units {
+    pub const DEFAULT: Self = Self {
+        unit: Unit::Kilogram,
+        gravity: STANDARD_GRAVITY,
+    };
+
+    pub fn is_valid(&self) -> bool {
+        GRAVITY_RANGE.contains(&self.gravity)
+    }
+
+    /// Convert a weight in kg into this unit
+    pub fn convert(&self, kg: f32) -> f32 {
+        match self.unit {
+            Unit::Kilogram => kg,
+            Unit::Pound => kg / KG_PER_LB,
+            Unit::Newton => kg * self.gravity,
+            Unit::KilogramForce => kg * self.gravity / STANDARD_GRAVITY,
+        }
+    }
+
+    /// Inverse of `convert`
+    pub fn to_kg(&self, value: f32) -> f32 {
+        match self.unit {
+            Unit::Kilogram => value,
+            Unit::Pound => value * KG_PER_LB,
+            Unit::Newton => value / self.gravity,
+            Unit::KilogramForce => value * STANDARD_GRAVITY / self.gravity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn convert() {
+        let units = |unit| Units {
+            unit,
+            gravity: 9.81,
+        };
+        assert_close(units(Unit::Kilogram).convert(10.0), 10.0);
+        assert_close(units(Unit::Pound).convert(10.0), 22.046_226);
+        assert_close(units(Unit::Newton).convert(10.0), 98.1);
+        assert_close(units(Unit::KilogramForce).convert(10.0), 10.003_416);
+        // kgf is the same as kg under standard gravity
+        assert_close(Units::DEFAULT.convert(10.0), 10.0);
+        let kgf = Units {
+            unit: Unit::KilogramForce,
+            ..Units::DEFAULT
+        };
+        assert_close(kgf.convert(10.0), 10.0);
+    }
+
+    #[test]
+    fn to_kg() {
+        for unit in [
+            Unit::Kilogram,
+            Unit::Pound,
+            Unit::Newton,
+            Unit::KilogramForce,
+        ] {
+            let units = Units {
+                unit,
+                gravity: 9.79,
+            };
+            assert_close(units.to_kg(units.convert(42.0)), 42.0);
+        }
+        let lb = Units {
+            unit: Unit::Pound,
+            ..Units::DEFAULT
+        };
+        assert_close(lb.to_kg(100.0), 45.359_237);
+    }
+
+    #[test]
+    fn gravity_range() {
+        assert!(Units::DEFAULT.is_valid());
+        let equator = Units {
+            gravity: 9.780,
+            ..Units::DEFAULT
+        };
+        assert!(equator.is_valid());
+        // Probably entered in ft/s²
+        let imperial = Units {
+            gravity: 32.17,
+            ..Units::DEFAULT
+        };
+        assert!(!imperial.is_valid());
+        let nan = Units {
+            gravity: f32::NAN,
+            ..Units::DEFAULT
+        };
+        assert!(!nan.is_valid());
+    }
+
+    #[test]
+    fn codes_and_symbols() {
+        for unit in [
+            Unit::Kilogram,
+            Unit::Pound,
+            Unit::Newton,
+            Unit::KilogramForce,
+        ] {
+            assert_eq!(Unit::from_code(unit.code()), Some(unit));
+            assert_eq!(Unit::from_symbol(unit.symbol()), Some(unit));
+        }
+        assert_eq!(Unit::from_code(4), None);
+        assert_eq!(Unit::from_symbol("LB"), Some(Unit::Pound));
+        assert_eq!(Unit::from_symbol("stone"), None);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bluetooth SIG Weight Scale Service: payloads and detection of stable weights to publish
+//!
+//! A Weight Measurement is a flags byte followed by the weight as a little-endian `u16`, in units
+//! of 0.005 kg or 0.01 lb depending on the flags. The Weight Scale Feature advertises the actual
+//! resolution of the scale, which can be coarser.
+
+use crate::stability::{Capture, Settings};
+use crate::units::{Unit, KG_PER_LB};
+use defmt::Format;
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// Weight Measurement flag: weight in lb rather than kg
+const FLAG_IMPERIAL: u8 = 0x01;
+const KG_PER_LSB: f32 = 0.005;
+const LB_PER_LSB: f32 = 0.01;
+/// Size of a Weight Measurement without any of the optional fields
+pub const MEASUREMENT_SIZE: usize = 3;
+/// Special value for a measurement that failed, e.g. out of range
+const MEASUREMENT_UNSUCCESSFUL: u16 = 0xFFFF;
+
+/// Weight resolution advertised in the Weight Scale Feature
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum Resolution {
+    /// 0.5 kg or 1 lb
+    Kg0_5 = 1,
+    /// 0.2 kg or 0.5 lb
+    Kg0_2 = 2,
+    /// 0.1 kg or 0.2 lb
+    Kg0_1 = 3,
+    /// 0.05 kg or 0.1 lb
+    Kg0_05 = 4,
+    /// 0.02 kg or 0.05 lb
+    Kg0_02 = 5,
+    /// 0.01 kg or 0.02 lb
+    Kg0_01 = 6,
+    /// 0.005 kg or 0.01 lb
+    Kg0_005 = 7,
+}
+
+/// Weight Scale Feature: no time stamps, users, or BMI, and the given weight resolution
+pub fn feature(resolution: Resolution) -> u32 {
+    (resolution as u32) << 3
+}
+
+/// Encode a Weight Measurement in lb if `unit` is `Unit::Pound`, or in kg otherwise
+///
+/// Weights that don't fit are reported as unsuccessful measurements. Negative weights are zero.
+pub fn measurement(weight_kg: f32, unit: Unit) -> [u8; MEASUREMENT_SIZE] {
+    let (flags, value) = match unit {
+        Unit::Pound => (FLAG_IMPERIAL, weight_kg / KG_PER_LB / LB_PER_LSB),
+        Unit::Kilogram | Unit::Newton | Unit::KilogramForce => (0, weight_kg / KG_PER_LSB),
+    };
+    let value = value.max(0.0).round();
+    let value = if value < f32::from(MEASUREMENT_UNSUCCESSFUL) {
+        value as u16
+    } else {
+        MEASUREMENT_UNSUCCESSFUL
+    };
+    let [low, high] = value.to_le_bytes();
+    [flags, low, high]
+}
+
+/// Finds one stable weight per weighing, like a bathroom scale
+///
+/// A weighing starts when the weight rises above `min_kg` and ends once it drops below half of
+/// that, e.g. when the load is taken off. Only the first stable weight of a weighing is reported.
+pub struct Detector {
+    settings: Settings,
+    capture: Capture,
+    min_kg: f32,
+    /// Whether a stable weight has been reported for the current weighing
+    reported: bool,
+}
+
+impl Detector {
+    pub fn new(settings: Settings, min_kg: f32) -> Self {
+        Self {
+            settings,
+            capture: Capture::new(settings),
+            min_kg,
+            reported: false,
+        }
+    }
+
+    /// Add a tared weight in kg. Returns the weight to report once it's stable.
+    pub fn add_sample(&mut self, weight_kg: f32) -> Option<f32> {
+        if weight_kg < self.min_kg / 2.0 {
+            self.reported = false;
+            self.capture = Capture::new(self.settings);
+            return None;
+        }
+        if self.reported {
+            return None;
+        }
+        let point = self.capture.add_sample(f64::from(weight_kg))?;
+        let weight_kg = point.mean as f32;
+        if weight_kg < self.min_kg {
+            return None;
+        }
+        self.reported = true;
+        Some(weight_kg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SETTINGS: Settings = Settings {
+        block_len: 4,
+        n_blocks: 2,
+        max_std_dev: 0.1,
+    };
+
+    #[test]
+    fn encode_measurement() {
+        assert_eq!(measurement(70.0, Unit::Kilogram), [0x00, 0xB0, 0x36]);
+        // 154.32 lb
+        assert_eq!(measurement(70.0, Unit::Pound), [0x01, 0x48, 0x3C]);
+        assert_eq!(measurement(70.0, Unit::Newton), [0x00, 0xB0, 0x36]);
+        assert_eq!(measurement(-1.0, Unit::Kilogram), [0x00, 0x00, 0x00]);
+        assert_eq!(measurement(400.0, Unit::Kilogram), [0x00, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn encode_feature() {
+        assert_eq!(feature(Resolution::Kg0_05), 0x20);
+        assert_eq!(feature(Resolution::Kg0_005), 0x38);
+    }
+
+    #[test]
+    fn one_report_per_weighing() {
+        let mut detector = Detector::new(SETTINGS, 1.0);
+        let mut reports = Vec::new();
+        let weights = [0.0; 8]
+            .into_iter()
+            // Swinging while loading
+            .chain([5.0, 25.0, 15.0, 22.0])
+            .chain([20.0; 24])
+            .chain([0.0; 4])
+            .chain([30.0; 8]);
+        for weight in weights {
+            reports.extend(detector.add_sample(weight));
+        }
+        assert_eq!(reports, [20.0, 30.0]);
+    }
+
+    #[test]
+    fn ignores_light_weights() {
+        let mut detector = Detector::new(SETTINGS, 1.0);
+        assert!((0..32).all(|_| detector.add_sample(0.8).is_none()));
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2026 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Advertising payload of WH-C06 style hanging scales
+//!
+//! These scales don't accept connections. They broadcast the current weight in the
+//! manufacturer-specific data of their advertisements instead, so any number of phones can watch
+//! one scale. There's no published specification: this is the layout that apps supporting the
+//! WH-C06 read, i.e. company ID 0x0100 followed by 12 bytes with the weight at offsets 10 and 11
+//! as a big-endian `u16` in units of 0.01 kg. The other bytes are left zero.
+
+// Floating point math functions live in std, so we need libm for no_std builds
+#[cfg(not(test))]
+use num_traits::Float;
+
+const COMPANY_ID: u16 = 0x0100;
+/// Advertising data type of manufacturer-specific data
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+const PAYLOAD_SIZE: usize = 12;
+const WEIGHT_OFFSET: usize = 10;
+const KG_PER_LSB: f32 = 0.01;
+/// Size of the advertising data structure, including its length and type
+pub const AD_SIZE: usize = 4 + PAYLOAD_SIZE;
+
+/// Manufacturer-specific advertising data structure carrying `weight_kg`
+///
+/// Negative weights are zero, and weights that don't fit saturate.
+pub fn advertising_data(weight_kg: f32) -> [u8; AD_SIZE] {
+    let mut data = [0; AD_SIZE];
+    data[0] = (AD_SIZE - 1) as u8;
+    data[1] = AD_TYPE_MANUFACTURER_DATA;
+    data[2..4].copy_from_slice(&COMPANY_ID.to_le_bytes());
+    let value = (weight_kg / KG_PER_LSB)
+        .round()
+        .clamp(0.0, f32::from(u16::MAX)) as u16;
+    let weight = 4 + WEIGHT_OFFSET;
+    data[weight..weight + 2].copy_from_slice(&value.to_be_bytes());
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            advertising_data(12.34),
+            [15, 0xFF, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x04, 0xD2]
+        );
+    }
+
+    #[test]
+    fn clamps() {
+        assert_eq!(advertising_data(-1.0)[14..16], [0, 0]);
+        assert_eq!(advertising_data(1000.0)[14..16], [0xFF, 0xFF]);
+    }
+}